@@ -11,7 +11,7 @@ use triangle::{
   engine::queue::Mino,
   types::{
     events::recv,
-    game::{Key, tick},
+    game::{self as game_types, Key, tick},
     room::Bracket,
   },
   utils::{EventEmitter, api::core::ApiError, events::WrapError},
@@ -27,9 +27,18 @@ use crate::lib::{
   logs::WSLogger,
 };
 use engine::{
-  Falcon,
-  game::{Board, Game, GameConfig, Garbage, data::Move, queue::Bag},
-  search::eval::Weights,
+  Falcon, StepResult,
+  game::{
+    BOARD_BUFFER, Board, Game, GameConfig, Garbage, SpawnRules,
+    data::Move,
+    garbage::{AttackTable, B2bBonus, GarbageEntry, Messiness, Passthrough},
+    queue::Bag,
+    timing::{Handling, Timing},
+  },
+  search::{
+    SearchLimits,
+    eval::{WeightProfiles, WeightsFile},
+  },
 };
 use settings::{ConstraintLevel, SettingsHandler};
 
@@ -106,6 +115,11 @@ pub struct EnabledState {
 pub struct GameState {
   last_piece_frame: u64,
   target_frame: u64,
+  // attack amounts in our garbage queue as of the last tick, new garbage is taken from these
+  queued: Vec<u16>,
+  // the attacks pondering last started with, pieces until each can enter and be cancelled.
+  // none once the engine dropped its search
+  pondered: Option<Vec<(u16, u8, u8)>>,
 }
 
 #[derive(Debug, Clone)]
@@ -113,10 +127,20 @@ pub struct State {
   enabled: EnabledState,
   game: Option<GameState>,
   restriction: Restriction,
+  // room options, the engine's initializer doesn't carry them
+  lockout: bool,
+  locktime: f64,
+  are: f64,
+  lineclear_are: f64,
+  // frames before received garbage can enter the board
+  garbage_speed: f64,
+  lock_resets: u8,
+  allow_180: bool,
 }
 
 pub struct Bot {
-  engine: Mutex<Falcon<7, 500>>,
+  engine: Mutex<Falcon>,
+  weights: WeightProfiles,
   pub client: Client,
   pub config: RwLock<Config>,
   pub state: RwLock<State>,
@@ -206,11 +230,19 @@ impl Bot {
     );
     commands::register(&mut cmd);
 
-    let weights = serde_json::from_str::<Weights>(&std::fs::read_to_string(env().weights.clone())?)
-      .map_err(|e| BotError::IoError(e.into()))?;
+    let weights: WeightProfiles =
+      serde_json::from_str::<WeightsFile>(&std::fs::read_to_string(env().weights.clone())?)
+        .map_err(|e| BotError::IoError(e.into()))?
+        .into();
+
+    let mut falcon = Falcon::new(weights.all.clone(), SearchLimits::fixed(7, 500));
+    if let Some(threads) = env().threads {
+      falcon.set_threads(threads);
+    }
 
     let bot = Arc::new(Bot {
-      engine: Mutex::new(Falcon::new(weights)),
+      engine: Mutex::new(falcon),
+      weights,
       client,
       settings: SettingsHandler::new(),
       config: RwLock::new(Config {
@@ -226,12 +258,19 @@ impl Bot {
         },
         game: None,
         restriction: Restriction::None,
+        lockout: false,
+        locktime: 30.0,
+        are: 0.0,
+        lineclear_are: 0.0,
+        garbage_speed: 20.0,
+        lock_resets: 15,
+        allow_180: true,
       }),
       events: EventEmitter::new(),
       commands: cmd,
     });
 
-    bot.handle_room_update(room_update_data, true).await;
+    bot.handle_room_update(room_update_data).await;
 
     if let Some(room) = bot.client.room() {
       room.chat(":oyes:/").await.ok();
@@ -268,7 +307,7 @@ impl Bot {
     let b = self.clone();
 
     self.client.on::<recv::room::Update>(async move |data| {
-      b.handle_room_update(data, false).await;
+      b.handle_room_update(data).await;
     });
 
     let b = self.clone();
@@ -277,6 +316,7 @@ impl Bot {
       .client
       .on::<recv::client::game::round::End>(async move |_| {
         b.state.write().game = None;
+        b.engine.lock().stop_pondering();
       });
 
     let b = self.clone();
@@ -405,21 +445,52 @@ impl Bot {
           None => return,
         };
 
+        // rooms with limited passthrough are refused in the settings check
+        let passthrough = match engine.initializer.garbage.passthrough {
+          game_types::Passthrough::Zero => Passthrough::Zero,
+          game_types::Passthrough::Consistent => Passthrough::Consistent,
+          game_types::Passthrough::Full => Passthrough::Full,
+          game_types::Passthrough::Limited => {
+            tracing::error!("falcon does not support limited passthrough");
+            return;
+          }
+        };
+
         b.client.game().unwrap().me.unwrap().set_pause_iges(true);
 
         let bag = match engine.queue.kind {
           BagType::Bag7 => Bag::Bag7,
-          _ => {
-            tracing::error!("Unsupported bag type: {:?}", engine.queue.kind);
-            return;
-          }
+          BagType::Bag14 => Bag::Bag14,
+          BagType::Classic => Bag::Classic,
+          BagType::Pairs => Bag::Pairs,
+          BagType::TotalMayhem => Bag::TotalMayhem,
+          BagType::Bag7Plus1 => Bag::Bag7Plus1,
+          BagType::Bag7Plus2 => Bag::Bag7Plus2,
+          BagType::Bag7PlusX => Bag::Bag7PlusX,
         };
 
+        let timing = b.timing(&engine);
+
         {
           let mut falcon = b.engine.lock();
+          falcon.set_weights(b.weights.get(engine.initializer.options.spin_bonuses));
           falcon.start(
             GameConfig {
-              b2b_chaining: engine.initializer.b2b.chaining,
+              attack: AttackTable {
+                pc: engine
+                  .initializer
+                  .pc
+                  .as_ref()
+                  .map(|pc| pc.garbage as u16)
+                  .unwrap_or(0),
+                b2b: if engine.initializer.b2b.chaining {
+                  B2bBonus::Chaining
+                } else {
+                  B2bBonus::Flat(1.0)
+                },
+                combo: engine.initializer.options.combo_table,
+                ..AttackTable::tetra_league()
+              },
               b2b_charging: engine.initializer.b2b.charging.is_some(),
               b2b_charge_at: engine
                 .initializer
@@ -435,24 +506,40 @@ impl Bot {
                 .as_ref()
                 .map(|v| v.base as i16)
                 .unwrap_or(0),
-              combo_table: engine.initializer.options.combo_table,
               garbage_multiplier: engine.initializer.garbage.multiplier.value as f32,
               garbage_cap: engine.initializer.garbage.cap.value as u16,
               garbage_special_bonus: engine.initializer.garbage.special_bonus,
+              messiness: Messiness {
+                change: engine.initializer.garbage.messiness.change,
+                within: engine.initializer.garbage.messiness.within,
+                nosame: engine.initializer.garbage.messiness.nosame,
+                timeout: b.frames_to_pieces(&timing, engine.initializer.garbage.messiness.timeout),
+              },
+              garbage_phase: b.frames_to_pieces(&timing, engine.initializer.garbage.phase as f64),
+              garbage_queue: engine.initializer.garbage.queue,
+              garbage_entry: match engine.initializer.garbage.entry {
+                game_types::GarbageEntry::Instant => GarbageEntry::Instant,
+                game_types::GarbageEntry::Continuous => GarbageEntry::Continuous,
+                game_types::GarbageEntry::Delayed => GarbageEntry::Delayed,
+              },
+              passthrough,
               kicks: engine.initializer.kick_table,
+              rotation: None,
+              allow_180: b.state.read().allow_180,
               pc_b2b: engine
                 .initializer
                 .pc
                 .as_ref()
                 .map(|pc| pc.b2b as u16)
                 .unwrap_or(0),
-              pc_send: engine
-                .initializer
-                .pc
-                .as_ref()
-                .map(|pc| pc.garbage as u8)
-                .unwrap_or(0),
               spins: engine.initializer.options.spin_bonuses,
+              board_width: engine.board.width as u8,
+              board_height: (engine.board.height as usize + BOARD_BUFFER) as u8,
+              spawn: SpawnRules {
+                lock_out: b.state.read().lockout,
+                ..SpawnRules::default()
+              },
+              timing,
               bag,
             },
             engine.queue.seed as u64,
@@ -464,11 +551,15 @@ impl Bot {
           b.state.write().game = Some(GameState {
             last_piece_frame: 0,
             target_frame: 0,
+            queued: Vec::new(),
+            pondered: None,
           });
           let target_frame = b.next_piece_frame(&engine, None, None);
           b.state.write().game = Some(GameState {
             last_piece_frame: engine.frame,
             target_frame,
+            queued: Vec::new(),
+            pondered: None,
           });
         }
 
@@ -483,7 +574,46 @@ impl Bot {
       });
   }
 
-  async fn handle_room_update(self: &Arc<Self>, data: recv::room::Update, initial: bool) {
+  async fn handle_room_update(self: &Arc<Self>, data: recv::room::Update) {
+    {
+      let mut state = self.state.write();
+      state.lockout = !data
+        .options
+        .as_ref()
+        .and_then(|o| o.nolockout)
+        .unwrap_or(false);
+      state.locktime = data
+        .options
+        .as_ref()
+        .and_then(|o| o.locktime)
+        .map_or(30.0, |v| v as f64);
+      state.are = data
+        .options
+        .as_ref()
+        .and_then(|o| o.are)
+        .map_or(0.0, |v| v as f64);
+      state.lineclear_are = data
+        .options
+        .as_ref()
+        .and_then(|o| o.lineclear_are)
+        .map_or(0.0, |v| v as f64);
+      state.garbage_speed = data
+        .options
+        .as_ref()
+        .and_then(|o| o.garbagespeed)
+        .map_or(20.0, |v| v as f64);
+      state.lock_resets = data
+        .options
+        .as_ref()
+        .and_then(|o| o.lockresets)
+        .map_or(15, |v| v.min(u8::MAX as _) as u8);
+      state.allow_180 = data
+        .options
+        .as_ref()
+        .and_then(|o| o.allow180)
+        .unwrap_or(true);
+    }
+
     let result = self.settings.check_room_update(&data);
 
     if let Some(result) = &result {
@@ -509,21 +639,6 @@ impl Bot {
           state.enabled.value = false;
         }
 
-        if initial
-          && result
-            .outputs
-            .iter()
-            .any(|o| o.message == "falcon requires 0 gravity increase.")
-          && result
-            .outputs
-            .iter()
-            .any(|o| o.message == "falcon requires 0 gravity.")
-        {
-          if let Some(room) = self.client.room() {
-            room.chat("Paste:\n\n/set options.g=0;options.gincrease=0;\n\nin chat and press enter to enable falcon.").await.ok();
-          }
-        }
-
         return;
       }
     }
@@ -618,6 +733,27 @@ impl Bot {
     (size / BUFFER * factor + 1.0).min(Self::max_burst_speed(pps))
   }
 
+  fn timing(&self, engine: &Engine) -> Timing {
+    let state = self.state.read();
+    Timing {
+      gravity: engine.dynamic.0.get(),
+      lock_delay: state.locktime,
+      lock_resets: state.lock_resets,
+      handling: Handling {
+        das: engine.handling.das,
+        arr: engine.handling.arr,
+        sdf: engine.handling.sdf,
+      },
+      are: state.are,
+      line_clear_are: state.lineclear_are,
+    }
+  }
+
+  // the engine counts garbage timings in pieces, placed at our configured pace
+  fn frames_to_pieces(&self, timing: &Timing, frames: f64) -> u8 {
+    timing.frames_to_pieces(frames, self.config.read().pps)
+  }
+
   fn effective_pps(&self, engine: &Engine, opponent: Option<&Engine>) -> f64 {
     let pps = self.config.read().pps;
     if !self.config.read().burst {
@@ -659,6 +795,13 @@ impl Bot {
     result.max(next_hd).max(engine.frame as f64 + 1.0) as u64
   }
 
+  // think for part of the time a piece takes at the current pace, the rest goes to the keys
+  fn search_limits(&self, engine: &Engine, opponent: Option<&Engine>) -> SearchLimits {
+    const THINK_SHARE: f64 = 0.5;
+    let pps = self.effective_pps(engine, opponent);
+    SearchLimits::timed(std::time::Duration::from_secs_f64(THINK_SHARE / pps))
+  }
+
   fn keypress_duration(&self, m: &Move, engine: &Engine) -> f64 {
     if m == &Move::SoftDrop {
       0.1
@@ -671,7 +814,7 @@ impl Bot {
 
   fn process_keys(
     &self,
-    raw: &[Move],
+    res: &StepResult,
     engine: &Engine,
     opponent: Option<&Engine>,
   ) -> Vec<tick::Keypress> {
@@ -682,9 +825,21 @@ impl Bot {
     }
 
     let now = engine.frame;
+    let raw = &res.keys;
 
     let finesse = self.config.read().finesse;
     let frames: Vec<InternalKeypress> = match finesse {
+      // under gravity the keys have to follow the timing the engine searched with
+      _ if engine.dynamic.0.get() > 0.0 => res
+        .plan
+        .iter()
+        .map(|p| InternalKeypress {
+          key: utils::move_to_key(p.mv),
+          frame: now as f64 + p.frame,
+          duration: p.duration.max(self.keypress_duration(&p.mv, engine)),
+        })
+        .collect(),
+
       Finesse::Instant => {
         let mut frame = FrameCounter::new(now);
         raw
@@ -706,10 +861,12 @@ impl Bot {
         const MAX_PIECE_FRAMES: u64 = 45;
 
         let mut frame = FrameCounter::new(now);
+        // the next piece's entry delay is spent waiting, not moving this one
         let time_to_next = (self
           .next_piece_frame(engine, None, opponent)
           .saturating_sub(now)
-          .saturating_sub(1))
+          .saturating_sub(1)
+          .saturating_sub(res.entry_delay.ceil() as u64))
         .min(MAX_PIECE_FRAMES);
 
         let arr = engine.handling.arr;
@@ -826,7 +983,112 @@ impl Bot {
       .collect()
   }
 
+  fn opponent_engine(&self) -> Option<Engine> {
+    self
+      .client
+      .game()
+      .map(|g| {
+        g.state
+          .lock()
+          .players
+          .iter()
+          .find(|p| p.userid != self.client.user.id.as_str())
+          .map(|p| p.state.lock().engine.clone())
+      })
+      .flatten()
+  }
+
+  // the opponent's board as the engine sees it, only used to judge how close they are to dying
+  fn opponent_game(engine: Option<&Engine>) -> Game {
+    match engine {
+      Some(engine) => {
+        let mut board = Board::with_size(
+          engine.board.width as usize,
+          engine.board.height as usize + BOARD_BUFFER,
+        );
+        for (i, row) in engine.board.state.iter().enumerate() {
+          if row
+            .iter()
+            .any(|mino| mino.as_ref().map_or(false, |t| t.mino == Mino::Garbage))
+          {
+            board.garbage = i as u8 + 1;
+          }
+          for (j, tile) in row.iter().enumerate() {
+            if tile.is_some() {
+              board.cols[j] |= 1 << i;
+            }
+          }
+        }
+
+        let mut game = Game::with_size(engine.falling.symbol, board.width(), board.height());
+        game.board = board;
+        game.b2b = engine.stats.b2b as i16;
+        game.combo = engine.stats.combo as i16;
+        game
+      }
+      None => Game::new(Mino::I),
+    }
+  }
+
+  // the attacks in our garbage queue and the pieces left until each can enter the board or be
+  // cancelled, counting the entry delay of every piece
+  fn queued_attacks(&self, engine: &Engine) -> Vec<(u16, u8, u8)> {
+    let timing = self.timing(engine);
+    let speed = self.state.read().garbage_speed;
+    let phase = engine.initializer.garbage.phase as f64;
+    engine
+      .garbage_queue
+      .queue
+      .iter()
+      .map(|g| {
+        let age = engine.frame.saturating_sub(g.frame as u64) as f64;
+        (
+          g.amount as u16,
+          self.frames_to_pieces(&timing, (speed - age).max(0.0)),
+          self.frames_to_pieces(&timing, (phase - age).max(0.0)),
+        )
+      })
+      .collect()
+  }
+
+  // searches ahead while waiting for the next piece, restarting only once the garbage queue
+  // changed since pondering began
+  fn ponder(&self, engine: &Engine) {
+    let attacks = self.queued_attacks(engine);
+    {
+      let mut state = self.state.write();
+      let Some(game) = &mut state.game else {
+        return;
+      };
+      if game.pondered.as_ref() == Some(&attacks) {
+        return;
+      }
+      game.pondered = Some(attacks.clone());
+    }
+
+    let opponent = Self::opponent_game(self.opponent_engine().as_ref());
+    let mut falcon = self.engine.lock();
+    let garbage = falcon.predict_garbage(&attacks);
+    falcon.ponder(garbage, &opponent);
+  }
+
   async fn tick(&self, input: tick::In) -> tick::Out {
+    let queued: Vec<u16> = input
+      .engine
+      .garbage_queue
+      .queue
+      .iter()
+      .map(|g| g.amount as u16)
+      .collect();
+    let entered = {
+      let mut state = self.state.write();
+      state
+        .game
+        .as_mut()
+        .map(|game| std::mem::replace(&mut game.queued, queued))
+        .unwrap_or_default()
+    };
+
     if !input.new_garbage.is_empty() {
       self.engine.lock().insert_garbage(
         input
@@ -836,9 +1098,14 @@ impl Bot {
             col: g.column as u8,
             amt: g.amount as u16,
             time: 0,
+            phase: 0,
           })
           .collect(),
+        &entered,
       );
+      if let Some(game) = &mut self.state.write().game {
+        game.pondered = None;
+      }
     }
 
     let game_state = { self.state.read().game.as_ref().map(|g| g.target_frame) };
@@ -850,7 +1117,9 @@ impl Bot {
       };
     };
 
-    if input.engine.frame < target_frame {
+    // at 20G the piece lands the moment it spawns, so waiting only eats into the lock delay
+    if input.engine.frame < target_frame && input.engine.dynamic.0.get() < 20.0 {
+      self.ponder(&input.engine);
       return tick::Out {
         keys: vec![],
         run_after: vec![],
@@ -871,6 +1140,7 @@ impl Bot {
       .unwrap_or(false);
 
     if has_hard_drop {
+      self.ponder(&input.engine);
       return tick::Out {
         keys: vec![],
         run_after: vec![],
@@ -884,18 +1154,7 @@ impl Bot {
       }
     }
 
-    let opponent_engine = self
-      .client
-      .game()
-      .map(|g| {
-        g.state
-          .lock()
-          .players
-          .iter()
-          .find(|p| p.userid != self.client.user.id.as_str())
-          .map(|p| p.state.lock().engine.clone())
-      })
-      .flatten();
+    let opponent_engine = self.opponent_engine();
 
     let initial_target = self.next_piece_frame(&input.engine, None, opponent_engine.as_ref());
     {
@@ -905,62 +1164,42 @@ impl Bot {
       }
     }
 
-    let garbage_queue: Vec<Garbage> = input
-      .engine
-      .garbage_queue
-      .queue
-      .iter()
-      .map(|g| Garbage {
-        amt: g.amount as u16,
-        col: 0,
-        time: 0,
-      })
-      .collect();
-
-    let opponent_game = match &opponent_engine {
-      Some(engine) => {
-        let mut board = Board::new();
-        for (i, row) in engine.board.state.iter().enumerate() {
-          if row
-            .iter()
-            .any(|mino| mino.as_ref().map_or(false, |t| t.mino == Mino::Garbage))
-          {
-            board.garbage = i as u8 + 1;
-          }
-          for (j, tile) in row.iter().enumerate() {
-            if tile.is_some() {
-              board.cols[j] |= 1 << i;
-            }
-          }
-        }
-
-        let mut game = Game::new(engine.falling.symbol);
-        game.board = board;
-        game.b2b = engine.stats.b2b as i16;
-        game.combo = engine.stats.combo as i16;
-        game
-      }
-      None => Game::new(Mino::I),
+    let attacks = self.queued_attacks(&input.engine);
+    let opponent_game = Self::opponent_game(opponent_engine.as_ref());
+
+    let mv = {
+      let mut falcon = self.engine.lock();
+      falcon.set_gravity(input.engine.dynamic.0.get());
+      falcon.set_limits(self.search_limits(&input.engine, opponent_engine.as_ref()));
+      let garbage_queue = falcon.predict_garbage(&attacks);
+      let mv = falcon.step(garbage_queue.clone(), &opponent_game);
+      // the garbage may still change before the next piece, which restarts pondering
+      falcon.ponder(garbage_queue, &opponent_game);
+      mv.inspect_err(|err| tracing::error!("no move: {}", err))
+        .ok()
     };
-
-    let mv = self.engine.lock().step(garbage_queue, &opponent_game);
+    if let Some(game) = &mut self.state.write().game {
+      game.pondered = Some(attacks);
+    }
 
     tracing::info!(
       "keys: {:?}",
       mv.as_ref().map(|m| m.keys.clone()).unwrap_or_default()
     );
 
-    let keys = if let Some(res) = mv {
-      self.process_keys(&res.keys, &input.engine, opponent_engine.as_ref())
+    let keys = if let Some(res) = &mv {
+      self.process_keys(res, &input.engine, opponent_engine.as_ref())
     } else {
       vec![]
     };
 
+    // the next piece only spawns once the entry delay has passed
+    let entry_delay = mv.as_ref().map_or(0.0, |res| res.entry_delay);
     let hd_frame = keys
       .iter()
       .rev()
       .find(|kp| kp.data.key == Key::HardDrop)
-      .map(|kp| kp.frame as f64);
+      .map(|kp| kp.frame as f64 + entry_delay);
 
     let final_target = self.next_piece_frame(&input.engine, hd_frame, opponent_engine.as_ref());
     {
@@ -1,8 +1,9 @@
+use engine::game::{BOARD_BUFFER, MAX_HEIGHT, MAX_WIDTH, MIN_WIDTH};
 use triangle::{
-  engine::{queue::bag::BagType, utils::KickTable},
+  engine::utils::KickTable,
   types::{
     events::recv,
-    game::{GarbageEntry, GarbageTargetBonus, Passthrough, SpinBonuses},
+    game::{GarbageTargetBonus, Passthrough},
   },
 };
 
@@ -63,34 +64,25 @@ impl SettingsHandler {
         let ok = data
           .options
           .as_ref()
-          .and_then(|o| o.spinbonuses.as_ref())
-          .map_or(false, |v| matches!(v, SpinBonuses::AllMiniPlus | SpinBonuses::AllPlus));
+          .and_then(|o| o.kickset.as_ref())
+          .map_or(false, |v| {
+            matches!(v, KickTable::SRS | KickTable::SRSPlus | KickTable::SRSX)
+          });
         if !ok {
-          err("falcon does not support spin bonuses outside of all-mini+ and all+.")
+          err(r#"falcon only supports the "SRS", "SRS+" and "SRS-X" kick tables."#)
         } else {
           None
         }
       }),
       Box::new(|data| {
+        // tetr.io doesn't document how much limited passthrough lets through
         let ok = data
           .options
           .as_ref()
           .and_then(|o| o.passthrough.as_ref())
-          .map_or(false, |v| matches!(v, Passthrough::Zero));
+          .map_or(false, |v| !matches!(v, Passthrough::Limited));
         if !ok {
-          err("falcon only supports zero passthrough.")
-        } else {
-          None
-        }
-      }),
-      Box::new(|data| {
-        let ok = data
-          .options
-          .as_ref()
-          .and_then(|o| o.kickset.as_ref())
-          .map_or(false, |v| matches!(v, KickTable::SRSPlus));
-        if !ok {
-          err(r#"falcon only supports the "SRS+" kick table."#)
+          err("falcon does not support limited passthrough.")
         } else {
           None
         }
@@ -107,30 +99,6 @@ impl SettingsHandler {
           None
         }
       }),
-      Box::new(|data| {
-        let ok = data
-          .options
-          .as_ref()
-          .and_then(|o| o.are)
-          .map_or(false, |v| v == 0);
-        if !ok {
-          err(r#"falcon only supports "0" ARE."#)
-        } else {
-          None
-        }
-      }),
-      Box::new(|data| {
-        let ok = data
-          .options
-          .as_ref()
-          .and_then(|o| o.lineclear_are)
-          .map_or(false, |v| v == 0);
-        if !ok {
-          err(r#"falcon only supports "0" line clear ARE."#)
-        } else {
-          None
-        }
-      }),
       Box::new(|data| {
         let ok = data
           .options
@@ -148,21 +116,12 @@ impl SettingsHandler {
           .options
           .as_ref()
           .and_then(|o| o.boardwidth)
-          .map_or(false, |v| v == 10);
-        if !ok {
-          err("falcon currently only supports boards with a width of 10.")
-        } else {
-          None
-        }
-      }),
-      Box::new(|data| {
-        let ok = data
-          .options
-          .as_ref()
-          .and_then(|o| o.g)
-          .map_or(false, |v| v == 0.0);
+          .map_or(false, |v| (MIN_WIDTH..=MAX_WIDTH).contains(&(v as usize)));
         if !ok {
-          err("falcon requires 0 gravity.")
+          err(&format!(
+            "falcon only supports boards with a width between {} and {}.",
+            MIN_WIDTH, MAX_WIDTH
+          ))
         } else {
           None
         }
@@ -171,10 +130,13 @@ impl SettingsHandler {
         let ok = data
           .options
           .as_ref()
-          .and_then(|o| o.gincrease)
-          .map_or(false, |v| v == 0.0);
+          .and_then(|o| o.boardheight)
+          .map_or(true, |v| v as usize + BOARD_BUFFER <= MAX_HEIGHT);
         if !ok {
-          err("falcon requires 0 gravity increase.")
+          err(&format!(
+            "falcon only supports boards with a height of at most {}.",
+            MAX_HEIGHT - BOARD_BUFFER
+          ))
         } else {
           None
         }
@@ -191,18 +153,6 @@ impl SettingsHandler {
           None
         }
       }),
-      Box::new(|data| {
-        let ok = data
-          .options
-          .as_ref()
-          .and_then(|o| o.nolockout)
-          .unwrap_or(false);
-        if !ok {
-          err("falcon does not support lockout.")
-        } else {
-          None
-        }
-      }),
       Box::new(|data| {
         let ok = data
           .options
@@ -224,66 +174,6 @@ impl SettingsHandler {
           None
         }
       }),
-      Box::new(|data| {
-        let ok = data
-          .options
-          .as_ref()
-          .and_then(|o| o.garbagephase)
-          .map_or(false, |v| v == 0);
-        if !ok {
-          err("falcon does not yet support garbage phasing.")
-        } else {
-          None
-        }
-      }),
-      Box::new(|data| {
-        let ok = data
-          .options
-          .as_ref()
-          .and_then(|o| o.garbageentry.as_ref())
-          .map_or(false, |v| *v == GarbageEntry::Instant);
-        if !ok {
-          err("falcon does not support non-instant garbage entry.")
-        } else {
-          None
-        }
-      }),
-      Box::new(|data| {
-        let ok = data
-          .options
-          .as_ref()
-          .and_then(|o| o.garbagequeue)
-          .map_or(true, |v| !v);
-        if !ok {
-          err("falcon does not support garbage queue.")
-        } else {
-          None
-        }
-      }),
-      Box::new(|data| {
-        let ok = data
-          .options
-          .as_ref()
-          .and_then(|o| o.messiness_timeout)
-          .map_or(false, |v| v == 0.0);
-        if !ok {
-          err("falcon does not support messiness timeout.")
-        } else {
-          None
-        }
-      }),
-      Box::new(|data| {
-        let ok = data
-          .options
-          .as_ref()
-          .and_then(|o| o.bagtype.as_ref())
-          .map_or(true, |v| matches!(v, BagType::Bag7));
-        if !ok {
-          err(r#"falcon does not support the "classic" or "total mayhem" bag types."#)
-        } else {
-          None
-        }
-      }),
       Box::new(|data| {
         let ok = data
           .options
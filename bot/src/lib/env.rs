@@ -13,6 +13,10 @@ pub struct Env {
 
   #[arg(long, env = "WEIGHTS", default_value_t = String::from("weights/weights.json"))]
   pub weights: String,
+
+  // search threads, every core when unset
+  #[arg(long, env = "THREADS")]
+  pub threads: Option<usize>,
 }
 
 static ENV: OnceLock<Env> = OnceLock::new();
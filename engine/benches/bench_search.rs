@@ -1,34 +1,46 @@
 use criterion::{Criterion, criterion_group, criterion_main};
 use engine::{
   game::{
-    Game, GameConfig, StartState,
+    BOARD_HEIGHT, BOARD_WIDTH, Game, GameConfig, SpawnRules, StartState,
+    garbage::{AttackTable, GarbageEntry, Messiness, Passthrough},
     queue::{Bag, Queue},
+    timing::Timing,
   },
+  game2,
   search::{
-    beam_search,
+    beam_search, bitboard,
     eval::WEIGHTS_HANDTUNED,
     movegen::{Placement, expand},
   },
 };
 use triangle::{
   engine::{queue::Mino, utils::KickTable},
-  types::game::{ComboTable, Spin, SpinBonuses},
+  types::game::{Spin, SpinBonuses},
 };
 
 fn setup() -> (GameConfig, Game, Queue<32>) {
   let config = GameConfig {
     kicks: KickTable::SRSX,
+    rotation: None,
+    allow_180: true,
     spins: SpinBonuses::Handheld,
-    b2b_chaining: false,
+    attack: AttackTable::tetra_league(),
     b2b_charging: true,
     b2b_charge_at: 0,
     b2b_charge_base: 0,
     pc_b2b: 1,
-    pc_send: 5,
-    combo_table: ComboTable::Multiplier,
     garbage_multiplier: 1.0,
     garbage_cap: 8,
     garbage_special_bonus: true,
+    messiness: Messiness::default(),
+    garbage_phase: 0,
+    garbage_queue: false,
+    garbage_entry: GarbageEntry::Instant,
+    passthrough: Passthrough::Zero,
+    board_width: BOARD_WIDTH as u8,
+    board_height: BOARD_HEIGHT as u8,
+    spawn: SpawnRules::default(),
+    timing: Timing::default(),
     bag: Bag::Bag7,
   };
 
@@ -48,6 +60,13 @@ fn bench_beam_search(c: &mut Criterion) {
   c.bench_function("beam_search d7/w200", |b| {
     b.iter(|| beam_search::<7, 200>(game.clone(), &config, &start_state, &WEIGHTS_HANDTUNED, 0.0))
   });
+
+  let game = game2::Game::from_legacy(&game);
+  c.bench_function("beam_search bitboard d7/w200", |b| {
+    b.iter(|| {
+      bitboard::beam_search::<7, 200>(game.clone(), &config, &start_state, &WEIGHTS_HANDTUNED, 0.0)
+    })
+  });
 }
 
 fn bench_expand(c: &mut Criterion) {
@@ -81,7 +100,7 @@ fn bench_expand(c: &mut Criterion) {
 }
 
 fn bench_collision(c: &mut Criterion) {
-  let (_config, mut game, _queue) = setup();
+  let (config, mut game, _queue) = setup();
   for (x, y) in [
     (0, 0),
     (1, 0),
@@ -114,7 +133,7 @@ fn bench_collision(c: &mut Criterion) {
     Mino::L,
     Mino::Z,
   ] {
-    game.set_falling(mino);
+    game.set_falling(mino, &config);
     c.bench_function(&format!("collision-map {}", mino.as_str()), |b| {
       b.iter(|| game.board.collision_map(&game.piece));
     });
@@ -1,19 +1,14 @@
 use criterion::{Criterion, black_box, criterion_group, criterion_main};
 
-use engine::game2::{board::Board, data::Mino, map::CollisionMap};
-
-fn collision_map(c: &mut Criterion) {
-  println!(
-    "{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n",
-    Mino::I.data(),
-    Mino::J.data(),
-    Mino::L.data(),
-    Mino::T.data(),
-    Mino::S.data(),
-    Mino::Z.data(),
-    Mino::O.data(),
-  );
+use engine::game2::{
+  board::Board,
+  data::{KickTable, Mino},
+  dispatch,
+  map::CollisionMap,
+};
+use triangle::types::game::SpinBonuses;
 
+fn board() -> Board {
   let mut board = Board::new();
   for (x, y) in [
     (0, 0),
@@ -36,6 +31,23 @@ fn collision_map(c: &mut Criterion) {
     board.set(x, y);
   }
 
+  board
+}
+
+fn collision_map(c: &mut Criterion) {
+  println!(
+    "{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n{:?}\n",
+    Mino::I.data(),
+    Mino::J.data(),
+    Mino::L.data(),
+    Mino::T.data(),
+    Mino::S.data(),
+    Mino::Z.data(),
+    Mino::O.data(),
+  );
+
+  let board = board();
+
   board.print();
   println!();
 
@@ -64,5 +76,32 @@ fn collision_map(c: &mut Criterion) {
   }
 }
 
-criterion_group!(benches, collision_map);
+// tracking spins turns off the movegen's early exit for the pieces that can spin
+fn expand_spins(c: &mut Criterion) {
+  let board = board();
+  let initial_pos = (4, board.real_height() as u8);
+
+  for (name, spins) in [
+    ("none", SpinBonuses::None),
+    ("t-spins", SpinBonuses::TSpins),
+    ("all", SpinBonuses::All),
+  ] {
+    for mino in [Mino::I, Mino::S, Mino::T] {
+      c.bench_function(&format!("expand {} {}", mino.str(), name), |b| {
+        b.iter(|| {
+          dispatch::expand(
+            mino,
+            KickTable::SRSPlus,
+            true,
+            black_box(&board),
+            initial_pos,
+            spins,
+          )
+        })
+      });
+    }
+  }
+}
+
+criterion_group!(benches, collision_map, expand_spins);
 criterion_main!(benches);
@@ -1,29 +1,40 @@
 use engine::{
   game::{
-    GameConfig,
+    BOARD_HEIGHT, BOARD_WIDTH, GameConfig, SpawnRules,
+    garbage::{AttackTable, GarbageEntry, Messiness, Passthrough},
     queue::{Bag, Queue},
+    timing::Timing,
   },
   search::{beam_search, eval::WEIGHTS_HANDTUNED},
 };
 use triangle::{
   engine::{queue::Mino, utils::KickTable},
-  types::game::{ComboTable, SpinBonuses},
+  types::game::SpinBonuses,
 };
 
 fn main() {
   let config = GameConfig {
     kicks: KickTable::SRSX,
+    rotation: None,
+    allow_180: true,
     spins: SpinBonuses::Handheld,
-    b2b_chaining: false,
+    attack: AttackTable::tetra_league(),
     b2b_charging: true,
     b2b_charge_at: 0,
     b2b_charge_base: 0,
     pc_b2b: 1,
-    pc_send: 5,
-    combo_table: ComboTable::Multiplier,
     garbage_multiplier: 1.0,
     garbage_cap: 8,
     garbage_special_bonus: true,
+    messiness: Messiness::default(),
+    garbage_phase: 0,
+    garbage_queue: false,
+    garbage_entry: GarbageEntry::Instant,
+    passthrough: Passthrough::Zero,
+    board_width: BOARD_WIDTH as u8,
+    board_height: BOARD_HEIGHT as u8,
+    spawn: SpawnRules::default(),
+    timing: Timing::default(),
     bag: Bag::Bag7,
   };
 
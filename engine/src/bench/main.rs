@@ -1,27 +1,23 @@
-#![feature(stmt_expr_attributes)]
-
 use num_format::{Locale, ToFormattedString};
+use triangle::types::game::SpinBonuses;
 
 use engine::game2::{
   board::Board,
-  config::ConstConfig,
   data::{KickTable, Mino},
+  dispatch,
   map::CollisionMap,
-  movegen::expand,
 };
 
 fn expand_mapped(mino: Mino, board: &Board) -> [CollisionMap; 3] {
   let h = board.real_height() as u8;
-  #[rustfmt::skip]
-	match mino {
-		Mino::I => expand::<{ Mino::I }, { ConstConfig { kicktable: KickTable::SRS, enable_180: false }}>(&CollisionMap::usable::<{ Mino::I }>(board), (4, h)),
-		Mino::O => expand::<{ Mino::O }, { ConstConfig { kicktable: KickTable::SRS, enable_180: false }}>(&CollisionMap::usable::<{ Mino::O }>(board), (4, h)),
-		Mino::T => expand::<{ Mino::T }, { ConstConfig { kicktable: KickTable::SRS, enable_180: false }}>(&CollisionMap::usable::<{ Mino::T }>(board), (4, h)),
-		Mino::J => expand::<{ Mino::J }, { ConstConfig { kicktable: KickTable::SRS, enable_180: false }}>(&CollisionMap::usable::<{ Mino::J }>(board), (4, h)),
-		Mino::L => expand::<{ Mino::L }, { ConstConfig { kicktable: KickTable::SRS, enable_180: false }}>(&CollisionMap::usable::<{ Mino::L }>(board), (4, h)),
-		Mino::S => expand::<{ Mino::S }, { ConstConfig { kicktable: KickTable::SRS, enable_180: false }}>(&CollisionMap::usable::<{ Mino::S }>(board), (4, h)),
-		Mino::Z => expand::<{ Mino::Z }, { ConstConfig { kicktable: KickTable::SRS, enable_180: false }}>(&CollisionMap::usable::<{ Mino::Z }>(board), (4, h)),
-	}
+  dispatch::expand(
+    mino,
+    KickTable::SRS,
+    false,
+    board,
+    (4, h),
+    SpinBonuses::None,
+  )
 }
 
 fn perft(board: &Board, queue: &[Mino], depth: usize) -> u64 {
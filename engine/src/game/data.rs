@@ -15,6 +15,7 @@ pub trait MinoData {
   fn data(&self) -> &TetrominoMatrix;
   fn rot(&self, rot: u8) -> &[(u8, u8); 4];
   fn corner_table(&self, rot: u8) -> Option<&CornerTable>;
+  fn srs_blocks(&self, rot: u8) -> [(i8, i8); 4];
   fn str(&self) -> &str;
 }
 
@@ -81,6 +82,29 @@ impl MinoData for Mino {
     }
   }
 
+  // offsets around the srs rotation center (y up), the convention used by fumen and tbp
+  #[inline(always)]
+  fn srs_blocks(&self, rot: u8) -> [(i8, i8); 4] {
+    debug_assert!(rot < 4, "Invalid rotation index: {}", rot);
+
+    let mut blocks = match self {
+      Mino::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+      Mino::J => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+      Mino::L => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+      Mino::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+      Mino::S => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+      Mino::T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+      Mino::Z => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+      _ => panic!("Invalid mino type: {:?}", self),
+    };
+
+    for _ in 0..rot {
+      blocks = blocks.map(|(x, y)| (y, -x));
+    }
+
+    blocks
+  }
+
   fn str(&self) -> &str {
     match self {
       Mino::I => "I",
@@ -1343,7 +1367,7 @@ impl Move {
       Move::None => panic!("None move called...cf"),
       Move::DasLeft => game.das_left(&map),
       Move::DasRight => game.das_right(&map),
-      Move::Hold => game.hold(start),
+      Move::Hold => game.hold(config, start),
       Move::HardDrop => {
         game.soft_drop(&map);
         true
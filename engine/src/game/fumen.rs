@@ -0,0 +1,631 @@
+use triangle::engine::queue::Mino;
+
+use super::{BOARD_HEIGHT, BOARD_WIDTH, Board, Falling, Game, data::MinoData};
+
+// fumen v115 (https://github.com/knewjade/tetris-fumen)
+
+const ENCODE_TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const COMMENT_TABLE: &[u8] =
+  b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+const COMMENT_CHAR_VALUES: u64 = COMMENT_TABLE.len() as u64 + 1;
+const MAX_COMMENT_LENGTH: usize = 4095;
+
+const FIELD_TOP: usize = 23;
+const FIELD_WIDTH: usize = 10;
+const FIELD_BLOCKS: u64 = ((FIELD_TOP + 1) * FIELD_WIDTH) as u64;
+const GRAY: u8 = 8;
+
+// fumen rotation codes, indexed by rotation (the mapping is its own inverse)
+const ROTATION_CODES: [u8; 4] = [2, 1, 0, 3];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FumenError {
+  UnsupportedVersion,
+  InvalidCharacter(char),
+  UnexpectedEnd,
+  InvalidField,
+  InvalidComment,
+  // garbage and empty cells aren't pieces fumen can show
+  InvalidPiece,
+}
+
+impl std::fmt::Display for FumenError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      FumenError::UnsupportedVersion => write!(f, "only v115 fumen data is supported"),
+      FumenError::InvalidCharacter(c) => write!(f, "invalid character '{}' in fumen data", c),
+      FumenError::UnexpectedEnd => write!(f, "fumen data ended unexpectedly"),
+      FumenError::InvalidField => write!(f, "fumen field data is malformed"),
+      FumenError::InvalidComment => write!(f, "fumen comment is malformed"),
+      FumenError::InvalidPiece => write!(f, "fumen only holds playable pieces"),
+    }
+  }
+}
+
+impl std::error::Error for FumenError {}
+
+#[derive(Clone, Debug)]
+pub struct Page {
+  // the field before the piece is placed
+  pub board: Board,
+  pub piece: Option<Falling>,
+  pub comment: Option<String>,
+  pub lock: bool,
+  pub rise: bool,
+  pub mirror: bool,
+}
+
+impl Page {
+  pub fn new(board: Board) -> Self {
+    Page {
+      board,
+      piece: None,
+      comment: None,
+      lock: true,
+      rise: false,
+      mirror: false,
+    }
+  }
+
+  pub fn from_game(game: &Game) -> Self {
+    Page {
+      piece: Some(game.piece),
+      ..Page::new(game.board)
+    }
+  }
+}
+
+fn piece_code(mino: Mino) -> Result<u8, FumenError> {
+  match mino {
+    Mino::I => Ok(1),
+    Mino::L => Ok(2),
+    Mino::O => Ok(3),
+    Mino::Z => Ok(4),
+    Mino::T => Ok(5),
+    Mino::J => Ok(6),
+    Mino::S => Ok(7),
+    _ => Err(FumenError::InvalidPiece),
+  }
+}
+
+fn code_piece(code: u8) -> Option<Mino> {
+  match code {
+    1 => Some(Mino::I),
+    2 => Some(Mino::L),
+    3 => Some(Mino::O),
+    4 => Some(Mino::Z),
+    5 => Some(Mino::T),
+    6 => Some(Mino::J),
+    7 => Some(Mino::S),
+    _ => None,
+  }
+}
+
+fn char_piece(c: char) -> Option<Mino> {
+  match c.to_ascii_uppercase() {
+    'I' => Some(Mino::I),
+    'L' => Some(Mino::L),
+    'O' => Some(Mino::O),
+    'Z' => Some(Mino::Z),
+    'T' => Some(Mino::T),
+    'J' => Some(Mino::J),
+    'S' => Some(Mino::S),
+    _ => None,
+  }
+}
+
+// fumen stores some pieces relative to their pre-srs centers
+fn position_offset(mino: Mino, rot: u8) -> (i8, i8) {
+  match (mino, rot) {
+    (Mino::O, 0) => (0, 1),
+    (Mino::O, 2) => (-1, 0),
+    (Mino::O, 3) => (-1, 1),
+    (Mino::I, 2) => (-1, 0),
+    (Mino::I, 3) => (0, 1),
+    (Mino::S, 0) => (0, 1),
+    (Mino::S, 1) => (1, 0),
+    (Mino::Z, 0) => (0, 1),
+    (Mino::Z, 3) => (-1, 0),
+    _ => (0, 0),
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Field {
+  // piece codes, row 0 is the garbage row and row y + 1 is board row y
+  rows: [[u8; FIELD_WIDTH]; FIELD_TOP + 1],
+}
+
+impl Field {
+  fn empty() -> Self {
+    Field {
+      rows: [[0; FIELD_WIDTH]; FIELD_TOP + 1],
+    }
+  }
+
+  // filled cells keep their colour from `prev` where possible to keep the diff small
+  fn from_board(board: &Board, prev: &Field) -> Self {
+    let mut field = Field::empty();
+    // fumen fields are always 10 wide, anything beyond is dropped
+    for y in 0..FIELD_TOP.min(board.height()) {
+      for x in 0..FIELD_WIDTH.min(board.width()) {
+        if board.cols[x] & (1 << y) != 0 {
+          let old = prev.rows[y + 1][x];
+          field.rows[y + 1][x] = if old != 0 { old } else { GRAY };
+        }
+      }
+    }
+    field
+  }
+
+  fn to_board(&self) -> Board {
+    let mut board = Board::new();
+    for y in 0..FIELD_TOP.min(BOARD_HEIGHT) {
+      for x in 0..FIELD_WIDTH.min(BOARD_WIDTH) {
+        if self.rows[y + 1][x] != 0 {
+          board.set(x, y);
+        }
+      }
+    }
+    board
+  }
+
+  #[inline(always)]
+  fn cell(&mut self, index: u64) -> &mut u8 {
+    let index = index as usize;
+    &mut self.rows[FIELD_TOP - index / FIELD_WIDTH][index % FIELD_WIDTH]
+  }
+
+  fn put(&mut self, piece: &Falling, code: u8) {
+    let (cx, cy) = piece.srs_position();
+    for &(dx, dy) in piece.mino.srs_blocks(piece.rot).iter() {
+      let (x, y) = (cx + dx, cy + dy);
+      if x >= 0 && (x as usize) < FIELD_WIDTH && y >= 0 && (y as usize) < FIELD_TOP {
+        self.rows[y as usize + 1][x as usize] = code;
+      }
+    }
+  }
+
+  fn clear_lines(&mut self) {
+    let mut write = 1;
+    for read in 1..=FIELD_TOP {
+      if self.rows[read].iter().all(|&c| c != 0) {
+        continue;
+      }
+      self.rows[write] = self.rows[read];
+      write += 1;
+    }
+    for row in write..=FIELD_TOP {
+      self.rows[row] = [0; FIELD_WIDTH];
+    }
+  }
+
+  fn rise(&mut self) {
+    for row in (1..FIELD_TOP).rev() {
+      self.rows[row + 1] = self.rows[row];
+    }
+    self.rows[1] = self.rows[0];
+    self.rows[0] = [0; FIELD_WIDTH];
+  }
+
+  fn mirror(&mut self) {
+    for row in 1..=FIELD_TOP {
+      self.rows[row].reverse();
+    }
+  }
+}
+
+struct Values {
+  data: Vec<u8>,
+  ptr: usize,
+}
+
+impl Values {
+  fn new(data: &str) -> Result<Self, FumenError> {
+    let data = data
+      .chars()
+      .filter(|&c| c != '?')
+      .map(|c| {
+        ENCODE_TABLE
+          .iter()
+          .position(|&e| e as char == c)
+          .map(|v| v as u8)
+          .ok_or(FumenError::InvalidCharacter(c))
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Values { data, ptr: 0 })
+  }
+
+  fn is_empty(&self) -> bool {
+    self.ptr >= self.data.len()
+  }
+
+  fn poll(&mut self, count: u32) -> Result<u64, FumenError> {
+    let mut value = 0u64;
+    for i in 0..count {
+      let digit = *self.data.get(self.ptr).ok_or(FumenError::UnexpectedEnd)?;
+      self.ptr += 1;
+      value += digit as u64 * 64u64.pow(i);
+    }
+    Ok(value)
+  }
+}
+
+#[inline(always)]
+fn push(buffer: &mut Vec<u8>, mut value: u64, count: u32) {
+  for _ in 0..count {
+    buffer.push((value % 64) as u8);
+    value /= 64;
+  }
+}
+
+// equivalent to javascript's escape(), which fumen applies to comments
+fn escape(s: &str) -> String {
+  let mut out = String::with_capacity(s.len());
+  for c in s.chars() {
+    if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) {
+      out.push(c);
+    } else if (c as u32) < 256 {
+      out += &format!("%{:02X}", c as u32);
+    } else {
+      for unit in c.encode_utf16(&mut [0; 2]) {
+        out += &format!("%u{:04X}", unit);
+      }
+    }
+  }
+  out
+}
+
+fn unescape(s: &str) -> String {
+  let chars = s.chars().collect::<Vec<_>>();
+  let mut units: Vec<u16> = Vec::with_capacity(chars.len());
+  let hex = |from: usize, len: usize| -> Option<u16> {
+    let digits = chars.get(from..from + len)?.iter().collect::<String>();
+    u16::from_str_radix(&digits, 16).ok()
+  };
+
+  let mut i = 0;
+  while i < chars.len() {
+    if chars[i] == '%' {
+      if chars.get(i + 1) == Some(&'u')
+        && let Some(unit) = hex(i + 2, 4)
+      {
+        units.push(unit);
+        i += 6;
+        continue;
+      }
+      if let Some(unit) = hex(i + 1, 2) {
+        units.push(unit);
+        i += 3;
+        continue;
+      }
+    }
+    units.push(chars[i] as u16);
+    i += 1;
+  }
+
+  String::from_utf16_lossy(&units)
+}
+
+pub fn decode(data: &str) -> Result<Vec<Page>, FumenError> {
+  let start = data.find("v115@").ok_or(FumenError::UnsupportedVersion)?;
+  let mut values = Values::new(data[start + 5..].trim())?;
+
+  let mut pages = Vec::new();
+  let mut prev = Field::empty();
+  let mut repeat = 0u64;
+  let mut comment = String::new();
+
+  while !values.is_empty() {
+    let mut current = prev;
+
+    if repeat == 0 {
+      let mut index = 0;
+      while index < FIELD_BLOCKS {
+        let block = values.poll(2)?;
+        let diff = (block / FIELD_BLOCKS) as i16 - 8;
+        let count = block % FIELD_BLOCKS + 1;
+
+        if diff == 0 && count == FIELD_BLOCKS {
+          repeat = values.poll(1)?;
+        }
+
+        if index + count > FIELD_BLOCKS {
+          return Err(FumenError::InvalidField);
+        }
+
+        for i in index..index + count {
+          let cell = current.cell(i);
+          *cell = (*cell as i16 + diff)
+            .try_into()
+            .map_err(|_| FumenError::InvalidField)?;
+        }
+        index += count;
+      }
+    } else {
+      repeat -= 1;
+    }
+
+    let mut action = values.poll(3)?;
+    let code = (action % 8) as u8;
+    action /= 8;
+    let rot = ROTATION_CODES[(action % 4) as usize];
+    action /= 4;
+    let position = action % FIELD_BLOCKS;
+    action /= FIELD_BLOCKS;
+    let rise = action % 2 == 1;
+    action /= 2;
+    let mirror = action % 2 == 1;
+    action /= 2;
+    // colorize flag, only meaningful for the editor
+    action /= 2;
+    let has_comment = action % 2 == 1;
+    action /= 2;
+    let lock = action % 2 == 0;
+
+    if has_comment {
+      let length = values.poll(2)? as usize;
+      let mut escaped = String::with_capacity(length);
+      while escaped.len() < length {
+        let mut value = values.poll(5)?;
+        for _ in 0..4 {
+          if escaped.len() >= length {
+            break;
+          }
+          let c = COMMENT_TABLE
+            .get((value % COMMENT_CHAR_VALUES) as usize)
+            .ok_or(FumenError::InvalidComment)?;
+          escaped.push(*c as char);
+          value /= COMMENT_CHAR_VALUES;
+        }
+      }
+      comment = unescape(&escaped);
+    }
+
+    let piece = code_piece(code).map(|mino| {
+      let (dx, dy) = position_offset(mino, rot);
+      let mut falling = Falling {
+        x: 0,
+        y: 0,
+        rot,
+        mino,
+      };
+      falling.set_srs_position(
+        (position % FIELD_WIDTH as u64) as i8 - dx,
+        (FIELD_TOP as i64 - (position / FIELD_WIDTH as u64) as i64 - 1) as i8 - dy,
+      );
+      falling
+    });
+
+    pages.push(Page {
+      board: current.to_board(),
+      piece,
+      comment: if comment.is_empty() {
+        None
+      } else {
+        Some(comment.clone())
+      },
+      lock,
+      rise,
+      mirror,
+    });
+
+    if lock {
+      if let Some(piece) = &piece {
+        current.put(piece, code);
+      }
+      current.clear_lines();
+      if rise {
+        current.rise();
+      }
+      if mirror {
+        current.mirror();
+      }
+    }
+
+    prev = current;
+  }
+
+  Ok(pages)
+}
+
+pub fn encode(pages: &[Page]) -> Result<String, FumenError> {
+  let mut buffer: Vec<u8> = Vec::new();
+  let mut prev = Field::empty();
+  let mut prev_comment = String::new();
+  let mut repeat_index: Option<usize> = None;
+
+  for page in pages.iter() {
+    let mut current = Field::from_board(&page.board, &prev);
+
+    // field
+    let mut runs: Vec<u8> = Vec::new();
+    let mut run = (8, 0);
+    for index in 0..FIELD_BLOCKS {
+      let diff = (*current.cell(index) as i16 - *prev.cell(index) as i16 + 8) as u64;
+      if run.1 > 0 && diff != run.0 {
+        push(&mut runs, run.0 * FIELD_BLOCKS + run.1 - 1, 2);
+        run = (diff, 0);
+      }
+      run.0 = diff;
+      run.1 += 1;
+    }
+    push(&mut runs, run.0 * FIELD_BLOCKS + run.1 - 1, 2);
+
+    let changed = run != (8, FIELD_BLOCKS);
+    if changed {
+      buffer.extend(runs);
+      repeat_index = None;
+    } else {
+      match repeat_index {
+        Some(i) if (buffer[i] as usize) < ENCODE_TABLE.len() - 1 => buffer[i] += 1,
+        _ => {
+          buffer.extend(runs);
+          buffer.push(0);
+          repeat_index = Some(buffer.len() - 1);
+        }
+      }
+    }
+
+    // action
+    let comment = page.comment.clone().unwrap_or_default();
+    let comment_changed = comment != prev_comment;
+
+    let (code, rot, position) = match &page.piece {
+      Some(piece) => {
+        let (x, y) = piece.srs_position();
+        let (dx, dy) = position_offset(piece.mino, piece.rot);
+        (
+          piece_code(piece.mino)?,
+          piece.rot,
+          (FIELD_TOP as i64 - (y + dy) as i64 - 1) * FIELD_WIDTH as i64 + (x + dx) as i64,
+        )
+      }
+      None => (0, 2, 0),
+    };
+
+    let mut action = if page.lock { 0 } else { 1 };
+    action = action * 2 + comment_changed as u64;
+    action = action * 2 + 1;
+    action = action * 2 + page.mirror as u64;
+    action = action * 2 + page.rise as u64;
+    action = action * FIELD_BLOCKS + position as u64;
+    action = action * 4 + ROTATION_CODES[rot as usize] as u64;
+    action = action * 8 + code as u64;
+    push(&mut buffer, action, 3);
+
+    // comment
+    if comment_changed {
+      let escaped = escape(&comment).into_bytes();
+      let length = escaped.len().min(MAX_COMMENT_LENGTH);
+      push(&mut buffer, length as u64, 2);
+      for chunk in escaped[..length].chunks(4) {
+        let mut value = 0u64;
+        for (i, c) in chunk.iter().enumerate() {
+          let v = COMMENT_TABLE.iter().position(|t| t == c).unwrap_or(0) as u64;
+          value += v * COMMENT_CHAR_VALUES.pow(i as u32);
+        }
+        push(&mut buffer, value, 5);
+      }
+      prev_comment = comment;
+    }
+
+    if page.lock {
+      if let Some(piece) = &page.piece {
+        current.put(piece, code);
+      }
+      current.clear_lines();
+      if page.rise {
+        current.rise();
+      }
+      if page.mirror {
+        current.mirror();
+      }
+    }
+
+    prev = current;
+  }
+
+  Ok(
+    String::from("v115@")
+      + &buffer
+        .into_iter()
+        .map(|v| ENCODE_TABLE[v as usize] as char)
+        .collect::<String>(),
+  )
+}
+
+// quiz comments describe the hold, current piece and queue: "#Q=[H](C)NEXT"
+fn parse_quiz(comment: &str) -> Option<(Option<Mino>, Mino, Vec<Mino>)> {
+  let rest = comment.strip_prefix("#Q=[")?;
+  let (hold, rest) = rest.split_once(']')?;
+  let rest = rest.strip_prefix('(')?;
+  let (current, next) = rest.split_once(')')?;
+
+  let hold = match hold.chars().next() {
+    Some(c) => Some(char_piece(c)?),
+    None => None,
+  };
+  let current = char_piece(current.chars().next()?)?;
+  let next = next
+    .chars()
+    .take_while(|c| !c.is_whitespace() && *c != ';')
+    .map(char_piece)
+    .collect::<Option<Vec<_>>>()?;
+
+  Some((hold, current, next))
+}
+
+fn quiz(hold: Option<Mino>, current: Mino, next: &[Mino]) -> Result<String, FumenError> {
+  for &mino in hold.iter().chain([current].iter()).chain(next) {
+    piece_code(mino)?;
+  }
+
+  Ok(format!(
+    "#Q=[{}]({}){}",
+    hold.map_or(String::new(), |h| h.str().to_string()),
+    current.str(),
+    next.iter().map(|m| m.str()).collect::<String>()
+  ))
+}
+
+impl Board {
+  pub fn from_fumen(data: &str) -> Result<Board, FumenError> {
+    decode(data)?
+      .into_iter()
+      .next()
+      .map(|page| page.board)
+      .ok_or(FumenError::UnexpectedEnd)
+  }
+
+  pub fn to_fumen(&self) -> String {
+    encode(&[Page::new(*self)]).expect("a page without a piece always encodes")
+  }
+}
+
+impl Game {
+  /// Loads every page of a fumen, with the queue left after each. The current
+  /// piece, hold and queue are read from a quiz comment if present, otherwise
+  /// from the page's piece.
+  pub fn from_fumen(data: &str) -> Result<Vec<(Game, Vec<Mino>)>, FumenError> {
+    let pages = decode(data)?;
+    if pages.is_empty() {
+      return Err(FumenError::UnexpectedEnd);
+    }
+
+    Ok(
+      pages
+        .into_iter()
+        .map(|page| {
+          let (hold, current, queue) = page.comment.as_deref().and_then(parse_quiz).unwrap_or((
+            None,
+            page.piece.map_or(Mino::I, |p| p.mino),
+            Vec::new(),
+          ));
+
+          let mut game = Game::new(current);
+          game.board = page.board;
+          game.hold = hold;
+          game.rehash();
+          (game, queue)
+        })
+        .collect(),
+    )
+  }
+
+  /// Writes one page per game, each with its queue in a quiz comment.
+  pub fn to_fumen(pages: &[(Game, Vec<Mino>)]) -> Result<String, FumenError> {
+    let pages = pages
+      .iter()
+      .map(|(game, queue)| {
+        Ok(Page {
+          comment: Some(quiz(game.hold, game.piece.mino, queue)?),
+          ..Page::new(game.board)
+        })
+      })
+      .collect::<Result<Vec<_>, FumenError>>()?;
+
+    encode(&pages)
+  }
+}
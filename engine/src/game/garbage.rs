@@ -1,54 +1,313 @@
+use serde::{Deserialize, Serialize};
 use triangle::types::game::{ComboTable, Spin};
 
-pub fn damage_calc(
-  lines: u8,
-  spin: Spin,
-  b2b: i16,
-  combo: i16,
-  combo_table: ComboTable,
-  b2b_chaining: bool,
-) -> f32 {
-  assert!(lines <= 4, "Lines must be between 0 and 4");
-  #[rustfmt::skip]
-	let mut damage: f32 = match lines {
-		0 => 0.0,
-		1 => if spin == Spin::Normal { 2.0 } else { 0.0 },
-		2 => if spin == Spin::Normal { 4.0 } else { 1.0 },
-		3 => if spin == Spin::Normal { 6.0 } else { 2.0 },
-		4 => if spin != Spin::None { 10.0 } else { 4.0 },
-		_ => panic!("Invalid number of lines: {}", lines),
-	};
-
-  damage += if lines > 0 && b2b > 0 {
-    if b2b_chaining {
-      (1.0 + (b2b as f32 * 0.8).ln_1p()).floor()
-        + if b2b == 1 {
-          0.0
+use super::{Garbage, rng::RNG};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum B2bBonus {
+  None,
+  Flat(f32),
+  // tetr.io's logarithmic b2b chaining curve
+  Chaining,
+}
+
+impl B2bBonus {
+  pub fn bonus(&self, b2b: i16) -> f32 {
+    match *self {
+      B2bBonus::None => 0.0,
+      B2bBonus::Flat(bonus) => bonus,
+      B2bBonus::Chaining => {
+        (1.0 + (b2b as f32 * 0.8).ln_1p()).floor()
+          + if b2b == 1 {
+            0.0
+          } else {
+            (1.0 + (b2b as f32 * 0.8).ln_1p().fract()) / 3.0
+          }
+      }
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AttackTable {
+  // base damage, indexed by lines cleared
+  pub clears: [f32; 5],
+  pub spins: [f32; 5],
+  pub minis: [f32; 5],
+  pub pc: u16,
+  pub b2b: B2bBonus,
+  pub combo: ComboTable,
+}
+
+impl AttackTable {
+  pub fn tetra_league() -> Self {
+    AttackTable {
+      clears: [0.0, 0.0, 1.0, 2.0, 4.0],
+      spins: [0.0, 2.0, 4.0, 6.0, 10.0],
+      minis: [0.0, 0.0, 1.0, 2.0, 10.0],
+      pc: 5,
+      b2b: B2bBonus::Flat(1.0),
+      combo: ComboTable::Multiplier,
+    }
+  }
+
+  pub fn quick_play() -> Self {
+    AttackTable {
+      pc: 10,
+      b2b: B2bBonus::Chaining,
+      ..Self::tetra_league()
+    }
+  }
+
+  pub fn guideline() -> Self {
+    AttackTable {
+      clears: [0.0, 0.0, 1.0, 2.0, 4.0],
+      spins: [0.0, 2.0, 4.0, 6.0, 6.0],
+      minis: [0.0, 0.0, 1.0, 1.0, 1.0],
+      pc: 10,
+      b2b: B2bBonus::Flat(1.0),
+      combo: ComboTable::ClassicGuideline,
+    }
+  }
+
+  pub fn preset(name: &str) -> Option<Self> {
+    match name {
+      "tetra-league" => Some(Self::tetra_league()),
+      "quick-play" => Some(Self::quick_play()),
+      "guideline" => Some(Self::guideline()),
+      _ => None,
+    }
+  }
+
+  pub fn damage(&self, lines: u8, spin: Spin, b2b: i16, combo: i16) -> f32 {
+    assert!(lines <= 4, "Lines must be between 0 and 4");
+
+    let mut damage = match spin {
+      Spin::None => self.clears,
+      Spin::Mini => self.minis,
+      Spin::Normal => self.spins,
+    }[lines as usize];
+
+    if lines > 0 && b2b > 0 {
+      damage += self.b2b.bonus(b2b);
+    }
+
+    if combo > 0 {
+      if self.combo == ComboTable::Multiplier {
+        let g1 = damage * (1.0 + 0.25 * combo as f32);
+        if combo > 1 {
+          (combo as f32 * 1.25).ln_1p().max(g1)
         } else {
-          (1.0 + (b2b as f32 * 0.8).ln_1p().fract()) / 3.0
+          g1
         }
+      } else {
+        let t = self.combo.data();
+        damage + t[(combo - 1).clamp(0, t.len() as i16 - 1) as usize] as f32
+      }
     } else {
-      1.0
+      damage
     }
-  } else {
-    0.0
-  };
+  }
+}
+
+// chance of the hole column moving between attacks (change) and between lines of one attack (within)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Messiness {
+  pub change: f64,
+  pub within: f64,
+  pub nosame: bool,
+  // pieces without incoming garbage after which the hole always moves, 0 to disable
+  #[serde(default)]
+  pub timeout: u8,
+}
+
+impl Default for Messiness {
+  fn default() -> Self {
+    Messiness {
+      change: 1.0,
+      within: 0.0,
+      nosame: false,
+      timeout: 0,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum GarbageEntry {
+  // garbage enters when a piece locks without clearing lines
+  #[default]
+  Instant,
+  // garbage rises while the piece is in play, so line clears don't hold it back
+  Continuous,
+  // garbage rises one piece after it would have entered
+  Delayed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Passthrough {
+  // attacks in transit cancel each other
+  #[default]
+  Zero,
+  // attacks in transit pass each other, but still cancel garbage already queued
+  Consistent,
+  // attacks never cancel garbage
+  Full,
+}
+
+impl Passthrough {
+  #[inline(always)]
+  fn cancels_in_transit(&self) -> bool {
+    *self == Passthrough::Zero
+  }
 
-  damage = if combo > 0 {
-    if combo_table == ComboTable::Multiplier {
-      let g1 = damage * (1.0 + 0.25 * combo as f32);
-      if combo > 1 {
-        (combo as f32 * 1.25).ln_1p().max(g1)
+  /// Lines cancelled between two attacks sent at the same time.
+  pub fn in_transit(&self, a: u16, b: u16) -> u16 {
+    if self.cancels_in_transit() {
+      a.min(b)
+    } else {
+      0
+    }
+  }
+
+  /// Cancels queued garbage with an outgoing attack and returns what is left of it. `cursor` is
+  /// the first entry not cancelled yet and how many of its lines are already gone. Garbage that
+  /// can't enter the board before `timer` is still in transit.
+  pub fn cancel(
+    &self,
+    garbage: &[Garbage],
+    cursor: &mut (usize, u16),
+    mut sent: u16,
+    timer: u8,
+  ) -> u16 {
+    if *self == Passthrough::Full {
+      return sent;
+    }
+
+    while sent > 0 && cursor.0 < garbage.len() && garbage[cursor.0].phase <= timer {
+      let gb = &garbage[cursor.0];
+      if gb.time > timer && !self.cancels_in_transit() {
+        break;
+      }
+
+      let left = gb.amt - cursor.1;
+      let amt = left.min(sent);
+      sent -= amt;
+      if amt == left {
+        *cursor = (cursor.0 + 1, 0);
       } else {
-        g1
+        cursor.1 += amt;
+      }
+    }
+
+    sent
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GarbageGenerator {
+  pub messiness: Messiness,
+  pub rng: RNG,
+  pub width: u8,
+  pub last_column: Option<u8>,
+  // pieces since the last attack
+  pub idle: u8,
+}
+
+impl GarbageGenerator {
+  pub fn new(seed: u64, messiness: Messiness, width: u8) -> Self {
+    GarbageGenerator {
+      messiness,
+      rng: RNG::new(seed),
+      width,
+      last_column: None,
+      idle: 0,
+    }
+  }
+
+  fn reroll(&mut self) -> u8 {
+    let col = match self.last_column {
+      Some(last) if self.messiness.nosame => {
+        let col = (self.rng.next_float() * (self.width - 1) as f64) as u8;
+        if col >= last { col + 1 } else { col }
+      }
+      _ => (self.rng.next_float() * self.width as f64) as u8,
+    };
+
+    self.last_column = Some(col);
+    col
+  }
+
+  pub fn tick(&mut self) {
+    self.idle = self.idle.saturating_add(1);
+  }
+
+  /// Hole column of each line of a single attack, bottom line last.
+  pub fn columns(&mut self, amount: u16) -> Vec<u8> {
+    if self.messiness.timeout > 0 && self.idle >= self.messiness.timeout {
+      self.last_column = None;
+    }
+    self.idle = 0;
+
+    (0..amount)
+      .map(|i| {
+        let chance = if i == 0 {
+          self.messiness.change
+        } else {
+          self.messiness.within
+        };
+
+        match self.last_column {
+          Some(col) if self.rng.next_float() >= chance => col,
+          _ => self.reroll(),
+        }
+      })
+      .collect()
+  }
+
+  /// Advances past lines that entered the board, keeping the columns the game actually used.
+  /// `queued` holds the amounts of the attacks they came from, oldest first, so the generator
+  /// draws once per attack like `generate` does. Lines past the queue count as one more attack.
+  pub fn sync(&mut self, queued: &[u16], entered: &[Garbage]) {
+    let holes: Vec<u8> = entered
+      .iter()
+      .flat_map(|gb| std::iter::repeat_n(gb.col, gb.amt as usize))
+      .collect();
+
+    let mut done = 0;
+    for &amt in queued.iter().chain(std::iter::once(&u16::MAX)) {
+      if done == holes.len() {
+        break;
+      }
+
+      let amt = (amt as usize).min(holes.len() - done);
+      self.columns(amt as u16);
+      done += amt;
+      self.last_column = Some(holes[done - 1]);
+    }
+  }
+
+  /// Splits an attack into clean garbage blocks, one per run of lines sharing a hole.
+  pub fn generate(&mut self, amount: u16, time: u8, phase: u8) -> Vec<Garbage> {
+    let mut res: Vec<Garbage> = Vec::new();
+
+    for col in self.columns(amount) {
+      match res.last_mut() {
+        Some(last) if last.col == col => last.amt += 1,
+        _ => res.push(Garbage {
+          col,
+          amt: 1,
+          time,
+          phase,
+        }),
       }
-    } else {
-      let t = combo_table.data();
-      damage + t[(combo - 1).clamp(0, t.len() as i16 - 1) as usize] as f32
     }
-  } else {
-    damage
-  };
 
-  damage
+    res
+  }
 }
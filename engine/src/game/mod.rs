@@ -1,7 +1,6 @@
 use serde::de::Error;
 pub mod data;
-use garbage::damage_calc;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use triangle::{
   engine::{queue::Mino, utils::KickTable},
   types::game::{ComboTable, Spin, SpinBonuses},
@@ -9,12 +8,18 @@ use triangle::{
 
 use crate::game::{
   data::{KickTableData, MinoData},
+  garbage::{AttackTable, B2bBonus, GarbageEntry, Messiness, Passthrough},
   queue::Bag,
+  timing::Timing,
 };
+use crate::game2;
 
-mod garbage;
+pub mod fumen;
+pub mod garbage;
 pub mod queue;
 pub mod rng;
+pub mod timing;
+pub mod zobrist;
 
 macro_rules! board_width_macro {
   () => {
@@ -26,11 +31,14 @@ pub const BOARD_WIDTH: usize = board_width_macro!();
 pub const BOARD_HEIGHT: usize = 40;
 pub const BOARD_BUFFER: usize = 20;
 
-pub const FULL_WIDTH: std::ops::Range<usize> = 0..BOARD_WIDTH;
+// custom rooms can resize the board, but columns have to fit in the fixed arrays and a u64
+pub const MIN_WIDTH: usize = 4;
+pub const MAX_WIDTH: usize = 16;
+pub const MAX_HEIGHT: usize = 64;
 
 pub fn print_board(board: Vec<u64>, garbage_height: u8, highlight: (Mino, Vec<(u8, u8)>)) {
   let mut start_row = 0;
-  for y in (0..BOARD_HEIGHT).rev() {
+  for y in (0..MAX_HEIGHT).rev() {
     let mut empty_row = true;
     for col in board.iter() {
       if (col & (1 << y)) != 0 {
@@ -75,16 +83,20 @@ pub fn print_board(board: Vec<u64>, garbage_height: u8, highlight: (Mino, Vec<(u
 
 #[derive(Clone, Copy, Debug)]
 pub struct CollisionMap {
-  pub states: [[u64; const { BOARD_WIDTH + 2 }]; 4],
+  pub states: [[u64; const { MAX_WIDTH + 2 }]; 4],
+  pub width: u8,
+  pub height: u8,
 }
 
 impl CollisionMap {
   #[inline(always)]
-  pub fn new(board: &[u64; BOARD_WIDTH], piece: &Falling) -> CollisionMap {
-    let mut states = [[0u64; const { BOARD_WIDTH + 2 }]; 4];
+  pub fn new(board: &Board, piece: &Falling) -> CollisionMap {
+    let width = board.width as usize;
+    let mut states = [[0u64; const { MAX_WIDTH + 2 }]; 4];
 
+    // everything outside the playfield, including unused columns, is a wall
     let mut padded = [!0u64; 32];
-    padded[8..8 + BOARD_WIDTH].copy_from_slice(board);
+    padded[8..8 + width].copy_from_slice(&board.cols[..width]);
 
     for rot in 0..4 {
       let blocks = piece.mino.rot(rot as u8);
@@ -115,22 +127,24 @@ impl CollisionMap {
         };
       }
 
-      for x in 0..BOARD_WIDTH + 2 {
+      for x in 0..width + 2 {
         column!(x);
       }
     }
 
-    CollisionMap { states }
+    CollisionMap {
+      states,
+      width: board.width,
+      height: board.height,
+    }
   }
 
   #[inline(always)]
   pub fn test(&self, x: u8, y: u8, rot: u8) -> bool {
-    let x = x as usize;
-    let y = y as usize;
-    if x >= BOARD_WIDTH + 2 || y >= BOARD_HEIGHT {
+    if x >= self.width + 2 || y >= self.height {
       return true;
     }
-    (self.states[rot as usize][x] >> y) & 1 != 0
+    (self.states[rot as usize][x as usize] >> y) & 1 != 0
   }
 }
 
@@ -144,8 +158,50 @@ pub struct HoleData<T> {
 
 #[derive(Clone, Copy, Debug)]
 pub struct Board {
-  pub cols: [u64; BOARD_WIDTH],
+  // only the first `width` columns are used, the rest stay empty
+  pub cols: [u64; MAX_WIDTH],
   pub garbage: u8,
+  pub width: u8,
+  pub height: u8,
+}
+
+// readable formats store a row-major bool matrix, compact formats store the raw column bits
+#[derive(Serialize, Deserialize)]
+struct RawBoard {
+  garbage: u8,
+  board: Vec<Vec<bool>>,
+}
+
+impl Serialize for Board {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: serde::Serializer,
+  {
+    if !serializer.is_human_readable() {
+      return (
+        self.width,
+        self.height,
+        &self.cols[..self.width as usize],
+        self.garbage,
+      )
+        .serialize(serializer);
+    }
+
+    let board = (0..self.height)
+      .map(|y| {
+        self.cols[..self.width as usize]
+          .iter()
+          .map(|col| col & (1u64 << y) != 0)
+          .collect()
+      })
+      .collect();
+
+    RawBoard {
+      garbage: self.garbage,
+      board,
+    }
+    .serialize(serializer)
+  }
 }
 
 impl<'de> Deserialize<'de> for Board {
@@ -153,58 +209,117 @@ impl<'de> Deserialize<'de> for Board {
   where
     D: serde::Deserializer<'de>,
   {
-    #[derive(Deserialize)]
-    struct RawBoard {
-      garbage: u8,
-      board: Vec<[bool; BOARD_WIDTH]>,
+    if !deserializer.is_human_readable() {
+      let (width, height, cols, garbage) = <(u8, u8, Vec<u64>, u8)>::deserialize(deserializer)?;
+      Board::check_size(width as usize, height as usize).map_err(D::Error::custom)?;
+      if cols.len() != width as usize {
+        return Err(D::Error::custom(
+          "Board column count does not match its width",
+        ));
+      }
+
+      let mut board = Board::with_size(width as usize, height as usize);
+      board.cols[..cols.len()].copy_from_slice(&cols);
+      board.garbage = garbage;
+      return Ok(board);
     }
 
     // Deserialize into the helper struct
     let raw = RawBoard::deserialize(deserializer)?;
 
-    // Enforce safety limits for the u64 bitmask size
-    if raw.board.len() != BOARD_HEIGHT {
-      return Err(D::Error::custom(format!(
-        "Board height does not match expected height of {}",
-        BOARD_HEIGHT
-      )));
+    // Every row has to be as wide as the first one
+    let width = raw.board.first().map_or(BOARD_WIDTH, |row| row.len());
+    if raw.board.iter().any(|row| row.len() != width) {
+      return Err(D::Error::custom("Board rows have different widths"));
     }
 
-    // Initialize empty bitmasks for each column
-    let mut cols = [0u64; BOARD_WIDTH];
+    // Enforce safety limits for the u64 bitmask size
+    Board::check_size(width, raw.board.len()).map_err(D::Error::custom)?;
+
+    let mut board = Board::with_size(width, raw.board.len());
+    board.garbage = raw.garbage;
 
     // Transpose row-major booleans into column-major u64 bitmasks
     for (row_idx, row) in raw.board.iter().enumerate() {
       for (col_idx, &cell) in row.iter().enumerate() {
         if cell {
-          cols[col_idx] |= 1 << row_idx;
+          board.cols[col_idx] |= 1 << row_idx;
         }
       }
     }
 
-    Ok(Board {
-      cols,
-      garbage: raw.garbage,
-    })
+    Ok(board)
   }
 }
 
 impl Board {
   pub fn new() -> Self {
+    Self::with_size(BOARD_WIDTH, BOARD_HEIGHT)
+  }
+
+  // height includes the buffer above the visible playfield
+  pub fn with_size(width: usize, height: usize) -> Self {
+    if let Err(err) = Self::check_size(width, height) {
+      panic!("{}", err);
+    }
+
     Board {
-      cols: [0; BOARD_WIDTH],
+      cols: [0; MAX_WIDTH],
       garbage: 0,
+      width: width as u8,
+      height: height as u8,
+    }
+  }
+
+  pub fn check_size(width: usize, height: usize) -> Result<(), String> {
+    if !(MIN_WIDTH..=MAX_WIDTH).contains(&width) {
+      return Err(format!(
+        "Board width of {} is outside of [{}, {}]",
+        width, MIN_WIDTH, MAX_WIDTH
+      ));
+    }
+    if height <= BOARD_BUFFER || height > MAX_HEIGHT {
+      return Err(format!(
+        "Board height of {} is outside of ({}, {}]",
+        height, BOARD_BUFFER, MAX_HEIGHT
+      ));
     }
+    Ok(())
+  }
+
+  #[inline(always)]
+  pub fn width(&self) -> usize {
+    self.width as usize
+  }
+
+  #[inline(always)]
+  pub fn height(&self) -> usize {
+    self.height as usize
+  }
+
+  #[inline(always)]
+  fn height_mask(&self) -> u64 {
+    u64::MAX >> (64 - self.height)
+  }
+
+  // x and y of a freshly spawned piece
+  #[inline(always)]
+  pub fn spawn(&self, mino: Mino) -> (u8, u8) {
+    let tetromino = mino.data();
+    (
+      ((self.width() + tetromino.w as usize) / 2) as u8 - 1,
+      (self.height() - BOARD_BUFFER) as u8 + 2,
+    )
   }
 
   #[inline(always)]
   pub fn set(&mut self, x: usize, y: usize) {
-    debug_assert!(x < BOARD_WIDTH && y < BOARD_HEIGHT);
+    debug_assert!(x < self.width() && y < self.height());
     self.cols[x] |= 1 << y;
   }
 
   pub fn is_occupied(&self, x: i8, y: i8) -> bool {
-    if x < 0 || x >= BOARD_WIDTH as i8 || y < 0 || y >= BOARD_HEIGHT as i8 {
+    if x < 0 || x >= self.width as i8 || y < 0 || y >= self.height as i8 {
       return true;
     }
     let x = x as usize;
@@ -245,9 +360,9 @@ impl Board {
 
   #[inline(always)]
   fn clear_setup(&mut self) -> (u64, bool) {
-    let clear_mask = self
-      .cols
-      .into_iter()
+    let clear_mask = self.cols[..self.width()]
+      .iter()
+      .copied()
       .reduce(|acc, col| acc & col)
       .unwrap_or(0);
 
@@ -271,8 +386,8 @@ impl Board {
       return (0, false);
     }
 
-    self
-      .cols
+    let width = self.width();
+    self.cols[..width]
       .iter_mut()
       .for_each(|col| unsafe { *col = _pext_u64(*col, !clear_mask) });
 
@@ -289,7 +404,7 @@ impl Board {
 
     while clear_mask != 0 {
       let y = 63 - clear_mask.leading_zeros();
-      for x in FULL_WIDTH {
+      for x in 0..self.width() {
         let low_mask = (1u64 << y) - 1;
         let low = self.cols[x] & low_mask;
         let high = self.cols[x] >> (y + 1);
@@ -344,22 +459,22 @@ impl Board {
   // }
 
   pub fn is_pc(&self) -> bool {
-    self.cols.iter().all(|&col| col == 0)
+    self.cols[..self.width()].iter().all(|&col| col == 0)
   }
 
   pub fn insert_garbage(&mut self, amount: u16, column: u8) {
-    assert!((column as usize) < BOARD_WIDTH, "hole-column out of bounds");
+    assert!(column < self.width, "hole-column out of bounds");
 
     if amount == 0 {
       return;
     }
 
-    self.garbage = (self.garbage.saturating_add(amount as u8)).min(BOARD_HEIGHT as u8);
+    self.garbage = (self.garbage.saturating_add(amount as u8)).min(self.height);
 
-    let all_mask = (1u64 << BOARD_HEIGHT) - 1;
+    let all_mask = self.height_mask();
     let bottom_mask = (1u64 << amount) - 1;
 
-    for x in 0..BOARD_WIDTH {
+    for x in 0..self.width() {
       let shifted = (self.cols[x] << amount) & all_mask;
 
       self.cols[x] = if x == column as usize {
@@ -371,30 +486,89 @@ impl Board {
   }
 
   pub fn print(&self) {
-    print_board(Vec::from(self.cols), self.garbage, (Mino::I, Vec::new()));
+    print_board(
+      Vec::from(&self.cols[..self.width()]),
+      self.garbage,
+      (Mino::I, Vec::new()),
+    );
   }
 
   #[inline(always)]
   pub fn collision_map(&self, piece: &Falling) -> CollisionMap {
-    CollisionMap::new(&self.cols, piece)
+    CollisionMap::new(self, piece)
   }
 
   // BOARD STATS
 
   #[inline(always)]
-  pub fn column_heights(&self) -> [u32; BOARD_WIDTH] {
-    std::array::from_fn(|i| 64 - self.cols[i].leading_zeros())
+  pub fn columns(&self) -> Columns<'_> {
+    Columns(&self.cols[..self.width()])
+  }
+
+  #[inline(always)]
+  pub fn column_heights(&self) -> [u32; MAX_WIDTH] {
+    self.columns().column_heights()
+  }
+
+  #[inline(always)]
+  pub fn heights(&self) -> (u32, u32) {
+    self.columns().heights()
+  }
+
+  #[inline(always)]
+  pub fn well(&self, heights: &[u32; MAX_WIDTH]) -> Option<usize> {
+    self.columns().well(heights)
+  }
+
+  #[inline(always)]
+  pub fn well_depth(&self, heights: &[u32; MAX_WIDTH], well_idx: usize) -> u32 {
+    self.columns().well_depth(heights, well_idx)
+  }
+
+  #[inline(always)]
+  pub fn count_holes(&self, heights: &[u32; MAX_WIDTH]) -> u32 {
+    self.columns().count_holes(heights)
+  }
+
+  #[inline(always)]
+  pub fn holes(&self, heights: &[u32; MAX_WIDTH]) -> HoleData<u32> {
+    self.columns().holes(heights)
+  }
+
+  #[inline(always)]
+  pub fn unevenness(&self, heights: &[u32; MAX_WIDTH], well: Option<usize>) -> i32 {
+    self.columns().unevenness(heights, well)
+  }
+}
+
+// the used columns of a board, so boards of both engines share the evaluation stats
+#[derive(Clone, Copy)]
+pub struct Columns<'a>(pub &'a [u64]);
+
+impl Columns<'_> {
+  #[inline(always)]
+  pub fn width(&self) -> usize {
+    self.0.len()
   }
 
+  // heights of every column, entries past the width are 0
+  #[inline(always)]
+  pub fn column_heights(&self) -> [u32; MAX_WIDTH] {
+    std::array::from_fn(|i| self.0.get(i).map_or(0, |col| 64 - col.leading_zeros()))
+  }
+
+  // (outer, inner) where inner is the 4 center columns
   #[inline(always)]
   pub fn heights(&self) -> (u32, u32) {
+    let width = self.0.len();
+    let center = (width - 4) / 2..(width - 4) / 2 + 4;
     (
-      64 - self.cols[..(BOARD_WIDTH - 4) / 2]
+      64 - self.0[..center.start]
         .iter()
-        .chain(self.cols[(BOARD_WIDTH - 3)..].iter())
+        .chain(self.0[center.end..width].iter())
         .fold(0, |acc, &val| acc | val)
         .leading_zeros(),
-      64 - self.cols[(BOARD_WIDTH - 4) / 2..(BOARD_WIDTH - 3)]
+      64 - self.0[center]
         .iter()
         .fold(0, |acc, &val| acc | val)
         .leading_zeros(),
@@ -402,12 +576,12 @@ impl Board {
   }
 
   #[inline(always)]
-  pub fn well(&self, heights: &[u32; BOARD_WIDTH]) -> Option<usize> {
+  pub fn well(&self, heights: &[u32; MAX_WIDTH]) -> Option<usize> {
     let mut min1_val = heights[0];
     let mut min1_idx = 0;
     let mut min2_val = u32::MAX;
 
-    for i in 1..BOARD_WIDTH {
+    for i in 1..self.0.len() {
       let h = heights[i];
 
       if h < min1_val {
@@ -427,19 +601,20 @@ impl Board {
   }
 
   #[inline(always)]
-  pub fn well_depth(&self, heights: &[u32; BOARD_WIDTH], well_idx: usize) -> u32 {
+  pub fn well_depth(&self, heights: &[u32; MAX_WIDTH], well_idx: usize) -> u32 {
     heights[well_idx]
-      - heights[0..well_idx]
-        .iter()
-        .max()
-        .unwrap_or(&0)
-        .max(heights[well_idx + 1..].iter().max().unwrap_or(&0))
+      - heights[0..well_idx].iter().max().unwrap_or(&0).max(
+        heights[well_idx + 1..self.0.len()]
+          .iter()
+          .max()
+          .unwrap_or(&0),
+      )
   }
 
   #[inline(always)]
-  pub fn count_holes(&self, heights: &[u32; BOARD_WIDTH]) -> u32 {
+  pub fn count_holes(&self, heights: &[u32; MAX_WIDTH]) -> u32 {
     self
-      .cols
+      .0
       .iter()
       .zip(heights.iter())
       .map(|(&col, &height)| (!col & ((1 << height) - 1)).count_ones())
@@ -447,14 +622,15 @@ impl Board {
   }
 
   #[inline(always)]
-  pub fn holes(&self, heights: &[u32; BOARD_WIDTH]) -> HoleData<u32> {
+  pub fn holes(&self, heights: &[u32; MAX_WIDTH]) -> HoleData<u32> {
+    let width = self.0.len();
     let mut total_holes = 0;
     let mut summed_depth = 0;
     let mut accessible = 0;
     let mut inaccessible = 0;
 
-    for x in 0..BOARD_WIDTH {
-      let col = self.cols[x];
+    for x in 0..width {
+      let col = self.0[x];
       let hole_mask = !col & ((1 << heights[x]) - 1);
 
       if hole_mask == 0 {
@@ -469,7 +645,7 @@ impl Board {
         + (col & (hole_mask << 2)).count_ones()
         + (col & (hole_mask << 3)).count_ones();
 
-      let activation_y = (if x < BOARD_WIDTH - 2 {
+      let activation_y = (if x < width - 2 {
         std::cmp::max(heights[x + 1], heights[x + 2].saturating_sub(2))
       } else {
         u32::MAX
@@ -497,11 +673,11 @@ impl Board {
   }
 
   #[inline(always)]
-  pub fn unevenness(&self, heights: &[u32; BOARD_WIDTH], well: Option<usize>) -> i32 {
+  pub fn unevenness(&self, heights: &[u32; MAX_WIDTH], well: Option<usize>) -> i32 {
     let mut unevenness = 0;
     let mut last = heights[0] as i32;
 
-    for (i, &h) in heights.iter().skip(1).enumerate() {
+    for (i, &h) in heights[..self.0.len()].iter().skip(1).enumerate() {
       if well.map_or(false, |w| w == i) {
         continue;
       }
@@ -513,7 +689,7 @@ impl Board {
   }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Falling {
   pub x: u8,
   pub y: u8,
@@ -525,41 +701,329 @@ impl Falling {
   pub fn blocks(&self) -> &[(u8, u8); 4] {
     self.mino.rot(self.rot)
   }
+
+  // position of the srs rotation center
+  pub fn srs_position(&self) -> (i8, i8) {
+    let blocks = self.blocks();
+    let srs = self.mino.srs_blocks(self.rot);
+
+    (
+      self.x as i8
+        - blocks.iter().map(|b| b.0).max().unwrap() as i8
+        - srs.iter().map(|b| b.0).min().unwrap(),
+      self.y as i8
+        - blocks.iter().map(|b| b.1).max().unwrap() as i8
+        - srs.iter().map(|b| b.1).min().unwrap(),
+    )
+  }
+
+  pub fn set_srs_position(&mut self, x: i8, y: i8) {
+    let blocks = self.blocks();
+    let srs = self.mino.srs_blocks(self.rot);
+
+    self.x = (x
+      + srs.iter().map(|b| b.0).max().unwrap()
+      + blocks.iter().map(|b| b.0).min().unwrap() as i8) as u8;
+    self.y = (y
+      + srs.iter().map(|b| b.1).max().unwrap()
+      + blocks.iter().map(|b| b.1).min().unwrap() as i8) as u8;
+  }
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct GameConfig {
   pub kicks: KickTable,
+  #[serde(default)]
+  pub rotation: Option<game2::data::RotationSystem>,
+  // the room can forbid 180s even when the kicks have them
+  #[serde(default = "default_allow_180")]
+  pub allow_180: bool,
   pub spins: SpinBonuses,
+  pub attack: AttackTable,
   pub b2b_charging: bool,
   pub b2b_charge_at: i16,
   pub b2b_charge_base: i16,
-  pub b2b_chaining: bool,
-  pub combo_table: ComboTable,
   pub garbage_multiplier: f32,
   pub garbage_cap: u16,
   pub pc_b2b: u16,
-  pub pc_send: u8,
   pub garbage_special_bonus: bool,
+  #[serde(default)]
+  pub messiness: Messiness,
+  // pieces after arriving during which garbage can't be cancelled
+  #[serde(default)]
+  pub garbage_phase: u8,
+  // queued garbage keeps entering on line clears instead of waiting for a non-clearing piece
+  #[serde(default)]
+  pub garbage_queue: bool,
+  #[serde(default)]
+  pub garbage_entry: GarbageEntry,
+  #[serde(default)]
+  pub passthrough: Passthrough,
+  #[serde(default = "default_board_width")]
+  pub board_width: u8,
+  // including the buffer above the visible playfield
+  #[serde(default = "default_board_height")]
+  pub board_height: u8,
+  #[serde(default)]
+  pub spawn: SpawnRules,
+  #[serde(default)]
+  pub timing: Timing,
   pub bag: Bag,
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SpawnRules {
+  // a piece blocked at spawn is pushed up a row before checking for block-out
+  pub raise: bool,
+  // the game ends when a new piece overlaps the stack
+  pub block_out: bool,
+  // the game ends when a piece locks entirely above the visible playfield
+  pub lock_out: bool,
+}
+
+impl Default for SpawnRules {
+  fn default() -> Self {
+    SpawnRules {
+      raise: true,
+      block_out: true,
+      lock_out: false,
+    }
+  }
+}
+
+impl GameConfig {
+  // kicks as the legacy game applies them, its I piece doesn't use true rotation
+  #[inline(always)]
+  pub fn legacy_kicks(&self, mino: Mino, from: u8, to: u8) -> &[(i8, i8)] {
+    match &self.rotation {
+      Some(rotation) => {
+        let data = rotation.legacy_data(mino.into());
+        &data.data(mino.into(), from, to)[..data.real_size]
+      }
+      None => self.kicks.data_fast(mino, from, to),
+    }
+  }
+
+  // kicks for game2, with the I piece converted for true rotation, None when it has no data for them
+  #[inline(always)]
+  pub fn kick_data(&self, mino: game2::data::Mino) -> Option<&game2::data::KickData> {
+    match &self.rotation {
+      Some(rotation) => Some(rotation.data(mino)),
+      None => game2::data::KickTable::try_from(self.kicks)
+        .ok()
+        .map(|table| table.raw()),
+    }
+  }
+
+  // None when the bitboard movegen has no specialization for the kicks, such as custom ones
+  pub fn kick_table(&self) -> Option<game2::data::KickTable> {
+    match &self.rotation {
+      Some(_) => None,
+      None => game2::data::KickTable::try_from(self.kicks).ok(),
+    }
+  }
+
+  // the (±1, -2) kicks that upgrade a mini t-spin only mean something in the srs tables
+  #[inline(always)]
+  pub fn srs_kicks(&self) -> bool {
+    self.rotation.is_none()
+  }
+
+  // rotation and columns right of the srs spawn a piece enters with
+  #[inline(always)]
+  pub fn spawn_state(&self, mino: game2::data::Mino) -> (u8, i8) {
+    self
+      .rotation
+      .as_ref()
+      .map_or((0, 0), |rotation| rotation.spawn(mino))
+  }
+
+  #[inline(always)]
+  pub fn center_column(&self, mino: game2::data::Mino) -> bool {
+    self
+      .rotation
+      .as_ref()
+      .is_some_and(|rotation| rotation.center_column(mino))
+  }
+
+  #[inline(always)]
+  pub fn allows_180(&self) -> bool {
+    self.allow_180
+      && self
+        .rotation
+        .as_ref()
+        .is_none_or(|rotation| rotation.allows_180())
+  }
+
+  // updates b2b and combo for a placement and returns its attack, shared by both engines
+  pub fn attack(
+    &self,
+    cleared: u8,
+    garbage_cleared: bool,
+    spin: Spin,
+    pc: bool,
+    b2b: &mut i16,
+    combo: &mut i16,
+  ) -> u16 {
+    let mut broke_b2b = Option::from(*b2b);
+    if cleared > 0 {
+      *combo += 1;
+      if (spin != Spin::None || cleared >= 4) && !(pc && self.pc_b2b > 0) {
+        *b2b += 1;
+        broke_b2b = None;
+      }
+      if pc && self.pc_b2b > 0 {
+        *b2b += self.pc_b2b as i16;
+        broke_b2b = None;
+      }
+
+      if broke_b2b.is_some() {
+        *b2b = -1;
+      }
+    } else {
+      *combo = -1;
+      broke_b2b = None;
+    }
+
+    let garbage_special_bonus =
+      if self.garbage_special_bonus && garbage_cleared && (spin != Spin::None || cleared >= 4) {
+        1
+      } else {
+        0
+      } as f32;
+
+    let mut sent = (self.attack.damage(cleared, spin, *b2b, *combo) * self.garbage_multiplier
+      + garbage_special_bonus) as u16;
+
+    if pc {
+      sent += self.attack.pc;
+    }
+
+    if let Some(b2b) = broke_b2b {
+      if self.b2b_charging && b2b + 1 > self.b2b_charge_at {
+        sent += ((b2b - self.b2b_charge_at + self.b2b_charge_base + 1) as f32
+          * self.garbage_multiplier) as u16;
+      }
+    }
+
+    sent
+  }
+
+  // cancels the queue with the attack, then tanks what can enter through `insert`
+  // returns the garbage actually sent, shared by both engines
+  pub fn exchange_garbage(
+    &self,
+    state: &StartState,
+    cursor: &mut (usize, u16),
+    mut sent: u16,
+    cleared: u8,
+    timer: u8,
+    mut insert: impl FnMut(u16, u8),
+  ) -> u16 {
+    if cleared > 0 {
+      sent = self
+        .passthrough
+        .cancel(state.garbage, cursor, sent, timer);
+    }
+
+    if cleared == 0 || self.garbage_queue || self.garbage_entry == GarbageEntry::Continuous {
+      let delay = (self.garbage_entry == GarbageEntry::Delayed) as u8;
+      let mut tanked = 0;
+      while cursor.0 < state.garbage.len()
+        && tanked < self.garbage_cap
+        && state.garbage[cursor.0].time + delay <= timer
+      {
+        let amt = (self.garbage_cap - tanked).min(state.garbage[cursor.0].amt - cursor.1);
+        tanked += amt;
+        insert(amt, state.garbage[cursor.0].col);
+
+        if amt == state.garbage[cursor.0].amt - cursor.1 {
+          cursor.0 += 1;
+          cursor.1 = 0;
+        } else {
+          cursor.1 += amt;
+        }
+      }
+    }
+
+    sent
+  }
+}
+
+// damage options of configs written before attack tables, on top of the tetra league table
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LegacyAttack {
+  b2b_chaining: bool,
+  combo_table: ComboTable,
+  pc_send: u8,
+}
+
+impl LegacyAttack {
+  fn table(&self) -> AttackTable {
+    AttackTable {
+      pc: self.pc_send as u16,
+      b2b: if self.b2b_chaining {
+        B2bBonus::Chaining
+      } else {
+        B2bBonus::Flat(1.0)
+      },
+      combo: self.combo_table,
+      ..AttackTable::tetra_league()
+    }
+  }
+}
+
+impl GameConfig {
+  // also reads configs that still set `b2bChaining`, `comboTable` and `pcSend` instead of `attack`
+  pub fn from_json(mut value: serde_json::Value) -> serde_json::Result<Self> {
+    if value.get("attack").is_none() && value.get("b2bChaining").is_some() {
+      let table = LegacyAttack::deserialize(&value)?.table();
+      value["attack"] = serde_json::to_value(table)?;
+    }
+    serde_json::from_value(value)
+  }
+
+  // for `deserialize_with` on messages that carry a json config
+  pub fn deserialize_json<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    Self::from_json(serde_json::Value::deserialize(deserializer)?).map_err(D::Error::custom)
+  }
+}
+
+fn default_allow_180() -> bool {
+  true
+}
+
+fn default_board_width() -> u8 {
+  BOARD_WIDTH as u8
+}
+
+fn default_board_height() -> u8 {
+  BOARD_HEIGHT as u8
+}
+
 pub struct StartState<'a> {
   pub queue: &'a [Mino; 32],
   pub garbage: &'a [Garbage],
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Garbage {
   pub col: u8,
   pub amt: u16,
+  // pieces until it can enter the board
   pub time: u8,
+  // pieces until it can be cancelled
+  #[serde(default)]
+  pub phase: u8,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Game {
   pub board: Board,
   pub queue_ptr: usize,
@@ -570,20 +1034,86 @@ pub struct Game {
   // index, tanked
   pub garbage: (usize, u16),
   pub spin: Spin,
+  // blocked out or locked out
+  #[serde(default)]
+  pub dead: bool,
+  // kept up to date by the moves, a state edited through its fields needs a `rehash`
+  #[serde(skip)]
+  hash: u64,
 }
 
 impl Game {
   pub fn new(piece: Mino) -> Self {
-    let tetromino = piece.data();
-    let board = Board::new();
+    Self::with_size(piece, BOARD_WIDTH, BOARD_HEIGHT)
+  }
+
+  // equal for states that play out the same from here, the falling piece follows from the queue
+  #[inline(always)]
+  pub fn hash(&self) -> u64 {
+    self.hash
+  }
+
+  // the hash worked out from scratch, what `hash` is after a `rehash`
+  pub fn full_hash(&self) -> u64 {
+    zobrist::board(self.cols()) ^ self.parts().hash()
+  }
+
+  pub fn rehash(&mut self) {
+    self.hash = self.full_hash();
+  }
+
+  #[inline(always)]
+  fn parts(&self) -> zobrist::Parts {
+    zobrist::Parts {
+      hold: self.hold.map(|mino| mino as u8),
+      queue_ptr: self.queue_ptr,
+      b2b: self.b2b,
+      combo: self.combo,
+      garbage: self.garbage,
+    }
+  }
+
+  #[inline(always)]
+  fn cols(&self) -> &[u64] {
+    &self.board.cols[..self.board.width()]
+  }
+
+  pub fn set_queue_ptr(&mut self, ptr: usize) {
+    let parts = self.parts();
+    self.queue_ptr = ptr;
+    self.hash ^= parts.delta(&self.parts());
+  }
+
+  // the same state seen from a root `pieces` further into the queue and `garbage` further into
+  // the garbage queue
+  pub fn rebase(&mut self, pieces: usize, (index, tanked): (usize, u16)) {
+    let parts = self.parts();
+    self.queue_ptr = self.queue_ptr.saturating_sub(pieces);
+    self.garbage = if self.garbage.0 == index {
+      (0, self.garbage.1.saturating_sub(tanked))
+    } else {
+      (self.garbage.0.saturating_sub(index), self.garbage.1)
+    };
+    self.hash ^= parts.delta(&self.parts());
+  }
+
+  pub fn insert_garbage(&mut self, amount: u16, column: u8) {
+    let cols = self.board.cols;
+    self.board.insert_garbage(amount, column);
+    self.hash ^= zobrist::board_delta(&cols, self.cols());
+  }
+
+  pub fn with_size(piece: Mino, width: usize, height: usize) -> Self {
+    let board = Board::with_size(width, height);
+    let (x, y) = board.spawn(piece);
     let piece = Falling {
-      x: ((BOARD_WIDTH + tetromino.w as usize) / 2) as u8 - 1,
-      y: (BOARD_HEIGHT - BOARD_BUFFER) as u8 + 2,
+      x,
+      y,
       rot: 0,
       mino: piece,
     };
 
-    Game {
+    let mut game = Game {
       b2b: -1,
       combo: -1,
       board,
@@ -592,7 +1122,22 @@ impl Game {
       piece,
       garbage: (0, 0),
       spin: Spin::None,
-    }
+      dead: false,
+      hash: 0,
+    };
+    game.rehash();
+    game
+  }
+
+  // a game on the config's board, its first piece spawned the way the config spawns pieces
+  pub fn with_config(piece: Mino, config: &GameConfig) -> Self {
+    let mut game = Self::with_size(
+      piece,
+      config.board_width as usize,
+      config.board_height as usize,
+    );
+    game.set_falling(piece, config);
+    game
   }
 
   pub fn print(&self) {
@@ -607,7 +1152,7 @@ impl Game {
     }
 
     print_board(
-      Vec::from(b.cols),
+      Vec::from(&b.cols[..b.width()]),
       b.garbage,
       (self.piece.mino, falling_target),
     );
@@ -627,33 +1172,43 @@ impl Game {
     config: &GameConfig,
     collision_map: &CollisionMap,
   ) -> (bool, bool) {
-    let to = (self.piece.rot + amount) % 4;
+    let from = self.piece.rot;
+    let to = (from + amount) % 4;
+
+    if amount == 2 && !config.allows_180() {
+      return (false, false);
+    }
 
     let mut res = (false, false, false);
 
-    if !collision_map.test(self.piece.x, self.piece.y, to) {
-      self.piece.rot = to;
-      res = (true, false, false);
-    }
+    // the first test isn't a kick, custom tables may start with an offset
+    let kickset = config.legacy_kicks(self.piece.mino, from, to);
+
+    for (i, &(dx, dy)) in kickset.iter().enumerate() {
+      if !collision_map.test(
+        (self.piece.x as i8 + dx) as u8,
+        (self.piece.y as i8 - dy) as u8,
+        to,
+      ) {
+        let is_tst_or_fin = config.srs_kicks()
+          && ((((from == 2 && to == 3) || (from == 0 && to == 3)) && dx == 1 && dy == -2)
+            || (((from == 2 && to == 1) || (from == 0 && to == 1)) && dx == -1 && dy == -2));
+        self.piece.x = (self.piece.x as i8 + dx) as u8;
+        self.piece.y = (self.piece.y as i8 - dy) as u8;
+        self.piece.rot = to;
+        res = (true, i > 0, is_tst_or_fin);
+        break;
+      }
 
-    if res.0 == false {
-      let from = self.piece.rot;
-
-      let kickset = config.kicks.data_fast(self.piece.mino, from, to);
-
-      for &(dx, dy) in kickset.iter() {
-        if !collision_map.test(
-          (self.piece.x as i8 + dx) as u8,
-          (self.piece.y as i8 - dy) as u8,
-          to,
-        ) {
-          let is_tst_or_fin =
-            (((from == 2 && to == 3) || (from == 0 && to == 3)) && dx == 1 && dy == -2)
-              || (((from == 2 && to == 1) || (from == 0 && to == 1)) && dx == -1 && dy == -2);
-          self.piece.x = (self.piece.x as i8 + dx) as u8;
-          self.piece.y = (self.piece.y as i8 - dy) as u8;
-          self.piece.rot = to;
-          res = (true, true, is_tst_or_fin);
+      // ars only kicks a j, l or t when its center column isn't the first place it's blocked
+      if i == 0 && config.center_column(self.piece.mino.into()) {
+        let rotated = Falling {
+          x: (self.piece.x as i8 + dx) as u8,
+          y: (self.piece.y as i8 - dy) as u8,
+          rot: to,
+          ..self.piece
+        };
+        if self.center_column_blocked(&rotated) {
           break;
         }
       }
@@ -666,6 +1221,21 @@ impl Game {
     (res.0, res.1)
   }
 
+  // reads the piece's cells row by row from the top left, true when the first blocked one is in
+  // the column of its rotation center
+  fn center_column_blocked(&self, piece: &Falling) -> bool {
+    let (center, _) = piece.srs_position();
+    let mut cells = piece
+      .blocks()
+      .map(|(x, y)| (piece.x as i8 - x as i8, piece.y as i8 - y as i8));
+    cells.sort_by_key(|&(x, y)| (-y, x));
+
+    cells
+      .iter()
+      .find(|&&(x, y)| self.board.is_occupied(x, y))
+      .is_some_and(|&(x, _)| x == center)
+  }
+
   #[inline(always)]
   pub fn update_spin(
     &mut self,
@@ -876,39 +1446,58 @@ impl Game {
     moved
   }
 
-  pub fn hold(&mut self, start_state: &StartState) -> bool {
+  pub fn hold(&mut self, config: &GameConfig, start_state: &StartState) -> bool {
+    let parts = self.parts();
+
     if let Some(hold) = self.hold {
       self.hold = Some(self.piece.mino);
-      self.piece.mino = hold;
-      let tetromino = self.piece.mino.data();
-      self.piece.x = ((BOARD_WIDTH + tetromino.w as usize) / 2) as u8 - 1;
-      self.piece.y = (BOARD_HEIGHT - BOARD_BUFFER) as u8 + 2;
+      self.set_falling(hold, config);
     } else {
       assert!(self.queue_ptr < start_state.queue.len(), "Queue is empty");
       self.hold = Some(self.piece.mino);
-      self.next_piece(start_state);
+      self.next_piece(config, start_state);
     }
+    self.hash ^= parts.delta(&self.parts());
+
+    self.apply_spawn_rules(&config.spawn);
 
     true
   }
 
-  pub fn next_piece(&mut self, start_state: &StartState) {
+  pub fn next_piece(&mut self, config: &GameConfig, start_state: &StartState) {
     assert!(self.queue_ptr < start_state.queue.len(), "Queue is empty");
     let next = start_state.queue[self.queue_ptr];
     self.queue_ptr += 1;
 
-    self.set_falling(next);
+    self.set_falling(next, config);
   }
 
   #[inline(always)]
-  pub fn set_falling(&mut self, mino: Mino) {
+  pub fn set_falling(&mut self, mino: Mino, config: &GameConfig) {
     self.piece.mino = mino;
 
-    let tetromino = mino.data();
-
-    self.piece.x = ((BOARD_WIDTH + tetromino.w as usize) / 2) as u8 - 1;
-    self.piece.y = (BOARD_HEIGHT - BOARD_BUFFER) as u8 + 2;
+    (self.piece.x, self.piece.y) = self.board.spawn(mino);
     self.piece.rot = 0;
+
+    // ars and nrs spawn some pieces pointing down or further right, around the same srs center
+    let (rot, dx) = config.spawn_state(mino.into());
+    if (rot, dx) != (0, 0) {
+      let (x, y) = self.piece.srs_position();
+      self.piece.rot = rot;
+      self.piece.set_srs_position(x + dx, y);
+    }
+  }
+
+  // called after a piece spawns, marks the game as dead on block-out
+  #[inline(always)]
+  pub fn apply_spawn_rules(&mut self, rules: &SpawnRules) {
+    if rules.raise && self.topped_out_raw() {
+      self.piece.y += 1;
+    }
+
+    if rules.block_out && self.topped_out_raw() {
+      self.dead = true;
+    }
   }
 
   pub fn topped_out(&self, collision_map: &CollisionMap) -> bool {
@@ -924,6 +1513,12 @@ impl Game {
     })
   }
 
+  // the search drops topped-out states even when the spawn rules let the game go on
+  #[inline(always)]
+  pub fn lost(&self) -> bool {
+    self.dead || self.topped_out_raw()
+  }
+
   pub fn collision_map(&self) -> CollisionMap {
     self.board.collision_map(&self.piece)
   }
@@ -939,11 +1534,17 @@ impl Game {
     // println!("HARD DROP {} {} {} {}", self.piece.mino.str(), self.piece.x, self.piece.y, self.piece.rot);
     self.soft_drop(collision_map);
 
+    let cols = self.board.cols;
+    let parts = self.parts();
+
     let blocks = self.piece.blocks();
 
     let mut max_y = blocks[0].1;
     let mut min_y = blocks[0].1;
 
+    let visible_top = (self.board.height() - BOARD_BUFFER) as u8;
+    let mut locked_out = true;
+
     for &(x, y) in blocks {
       if !(self.piece.x >= x) {
         println!(
@@ -974,6 +1575,7 @@ impl Game {
       self
         .board
         .set((self.piece.x - x) as usize, (self.piece.y - y) as usize);
+      locked_out &= self.piece.y - y >= visible_top;
 
       if y > max_y {
         max_y = y;
@@ -984,104 +1586,37 @@ impl Game {
     }
 
     let (cleared, garbage_cleared) = self.board.clear();
+    // garbage that comes in below keeps the hash up to date itself
+    self.hash ^= zobrist::board_delta(&cols, self.cols());
 
     let pc = self.board.is_pc();
 
-    let mut broke_b2b = Option::from(self.b2b);
-    if cleared > 0 {
-      self.combo += 1;
-      if (self.spin != Spin::None || cleared >= 4) && !(pc && config.pc_b2b > 0) {
-        self.b2b += 1;
-        broke_b2b = None;
-      }
-      if pc && config.pc_b2b > 0 {
-        self.b2b += config.pc_b2b as i16;
-        broke_b2b = None;
-      }
-
-      if broke_b2b.is_some() {
-        self.b2b = -1;
-      }
-    } else {
-      self.combo = -1;
-      broke_b2b = None;
-    }
-
-    let garbage_special_bonus = if config.garbage_special_bonus
-      && garbage_cleared
-      && (self.spin != Spin::None || cleared >= 4)
-    {
-      1
-    } else {
-      0
-    } as f32;
-
-    let mut sent = (damage_calc(
+    let attack = config.attack(
       cleared,
+      garbage_cleared,
       self.spin,
-      self.b2b,
-      self.combo,
-      config.combo_table,
-      config.b2b_chaining,
-    ) * config.garbage_multiplier
-      + garbage_special_bonus) as u16;
-
-    if pc {
-      sent += config.pc_send as u16;
-    }
-
-    if let Some(b2b) = broke_b2b {
-      if config.b2b_charging && b2b + 1 > config.b2b_charge_at {
-        sent += ((b2b - config.b2b_charge_at + config.b2b_charge_base + 1) as f32
-          * config.garbage_multiplier) as u16;
-      }
-    }
-
-    let attack = sent;
-
-    let gb_len = state.garbage.len();
-
-    if cleared > 0 {
-      while sent > 0 && self.garbage.0 < gb_len {
-        let amt = state.garbage[self.garbage.0].amt;
-
-        if amt > sent {
-          self.garbage.1 = amt - sent;
-          sent = 0;
-          break;
-        } else {
-          sent -= amt;
-          self.garbage.0 += 1;
-          self.garbage.1 = 0;
-        }
-      }
-    } else {
-      let mut tanked = 0;
-      while self.garbage.0 < gb_len
-        && tanked < config.garbage_cap
-        && state.garbage[self.garbage.0].time <= timer
-      {
-        let amt =
-          (tanked - config.garbage_cap).min(state.garbage[self.garbage.0].amt - self.garbage.1);
-        tanked += amt;
-        self
-          .board
-          .insert_garbage(amt, state.garbage[self.garbage.0].col);
+      pc,
+      &mut self.b2b,
+      &mut self.combo,
+    );
 
-        if amt == state.garbage[self.garbage.0].amt - self.garbage.1 {
-          self.garbage.0 += 1;
-          self.garbage.1 = 0;
-        } else {
-          self.garbage.1 += amt;
-        }
-      }
-    }
+    let mut cursor = self.garbage;
+    let sent = config.exchange_garbage(state, &mut cursor, attack, cleared, timer, |amt, col| {
+      self.insert_garbage(amt, col)
+    });
+    self.garbage = cursor;
 
     let clear_type = self.spin;
 
     self.spin = Spin::None;
 
-    self.next_piece(state);
+    self.next_piece(config, state);
+    self.hash ^= parts.delta(&self.parts());
+
+    if config.spawn.lock_out && locked_out {
+      self.dead = true;
+    }
+    self.apply_spawn_rules(&config.spawn);
 
     (attack, sent, (clear_type, cleared))
   }
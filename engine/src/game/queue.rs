@@ -1,42 +1,104 @@
 use std::collections::VecDeque;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use triangle::engine::queue::Mino;
 
 use super::rng::RNG;
 
-#[derive(Deserialize, Clone, Copy, Debug)]
+const MINOS: [Mino; 7] = [
+  Mino::Z,
+  Mino::L,
+  Mino::O,
+  Mino::S,
+  Mino::I,
+  Mino::J,
+  Mino::T,
+];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Bag {
   #[serde(rename = "7-bag")]
   Bag7,
+  #[serde(rename = "14-bag")]
+  Bag14,
+  #[serde(rename = "classic")]
+  Classic,
+  #[serde(rename = "pairs")]
+  Pairs,
+  #[serde(rename = "total mayhem")]
+  TotalMayhem,
+  #[serde(rename = "7+1-bag")]
+  Bag7Plus1,
+  #[serde(rename = "7+2-bag")]
+  Bag7Plus2,
+  #[serde(rename = "7+x-bag")]
+  Bag7PlusX,
+}
+
+// randomizer state carried between bags
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct BagState {
+  pub count: usize,
+  pub last: Option<usize>,
+  pub extra: Vec<Mino>,
 }
 
 impl Bag {
-  pub fn get_cycle(&self) -> Vec<Mino> {
+  pub fn next(&self, rng: &mut RNG, state: &mut BagState) -> Vec<Mino> {
+    state.count += 1;
+
     match self {
-      Bag::Bag7 => Vec::from([
-        Mino::Z,
-        Mino::L,
-        Mino::O,
-        Mino::S,
-        Mino::I,
-        Mino::J,
-        Mino::T,
-      ]),
+      Bag::Bag7 => rng.shuffle(MINOS.to_vec()),
+      Bag::Bag14 => rng.shuffle([MINOS, MINOS].concat()),
+      Bag::Classic => {
+        let mut index = (rng.next_float() * (MINOS.len() + 1) as f64) as usize;
+        if state.last == Some(index) || index >= MINOS.len() {
+          index = (rng.next_float() * MINOS.len() as f64) as usize;
+        }
+        state.last = Some(index);
+        vec![MINOS[index]]
+      }
+      Bag::Pairs => {
+        let s = rng.shuffle(MINOS.to_vec());
+        rng.shuffle(vec![s[0], s[0], s[0], s[1], s[1], s[1]])
+      }
+      Bag::TotalMayhem => vec![MINOS[(rng.next_float() * MINOS.len() as f64) as usize]],
+      Bag::Bag7Plus1 => {
+        let mut bag = MINOS.to_vec();
+        bag.push(MINOS[(rng.next_float() * MINOS.len() as f64) as usize]);
+        rng.shuffle(bag)
+      }
+      Bag::Bag7Plus2 => {
+        let mut bag = MINOS.to_vec();
+        bag.push(MINOS[(rng.next_float() * MINOS.len() as f64) as usize]);
+        bag.push(MINOS[(rng.next_float() * MINOS.len() as f64) as usize]);
+        rng.shuffle(bag)
+      }
+      Bag::Bag7PlusX => {
+        let extra = [3, 2, 1, 1].get(state.count - 1).copied().unwrap_or(0);
+        if state.extra.len() < extra {
+          state.extra = rng.shuffle(MINOS.to_vec());
+        }
+        let mut bag = MINOS.to_vec();
+        bag.extend(state.extra.drain(..extra));
+        rng.shuffle(bag)
+      }
     }
   }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Queue<const N: usize> {
   pub bag: Bag,
   pub rng: RNG,
+  pub state: BagState,
   pub queue: VecDeque<Mino>,
 }
 
 impl<const N: usize> Queue<N> {
   pub fn new(bag: Bag, seed: u64, initial: Vec<Mino>) -> Self {
     let mut rng = RNG::new(seed);
+    let mut state = BagState::default();
 
     let mut queue: VecDeque<Mino> = VecDeque::with_capacity(N + 7);
 
@@ -45,12 +107,15 @@ impl<const N: usize> Queue<N> {
     }
 
     while queue.len() < N {
-      for mino in rng.shuffle(bag.get_cycle()) {
-        queue.push_back(mino);
-      }
+      queue.extend(bag.next(&mut rng, &mut state));
     }
 
-    Queue { bag, rng, queue }
+    Queue {
+      bag,
+      rng,
+      state,
+      queue,
+    }
   }
 
   pub fn shift(&mut self) -> Mino {
@@ -60,9 +125,9 @@ impl<const N: usize> Queue<N> {
       .unwrap_or_else(|| unreachable!("Queue is empty!"));
 
     while self.queue.len() < N {
-      for mino in self.rng.shuffle(self.bag.get_cycle()) {
-        self.queue.push_back(mino);
-      }
+      self
+        .queue
+        .extend(self.bag.next(&mut self.rng, &mut self.state));
     }
 
     res
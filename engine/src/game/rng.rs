@@ -1,8 +1,10 @@
+use serde::{Deserialize, Serialize};
+
 const MODULUS: u64 = 2147483647;
 const MULTIPLIER: u64 = 16807;
 const MAX_FLOAT: u64 = 2147483646;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct RNG {
   pub seed: u64,
   pub index: usize,
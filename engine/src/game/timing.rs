@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use triangle::types::game::Spin;
+
+use super::{CollisionMap, Game, GameConfig, StartState, data::Move};
+
+// frames between two presses of a tap or rotation
+pub const KEY_FRAMES: f64 = 1.0;
+// tetr.io's soft drop speed never drops below its base gravity
+const BASE_GRAVITY: f64 = 0.02;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Handling {
+  pub das: f64,
+  pub arr: f64,
+  // soft drop factor, 41 and above drops instantly
+  pub sdf: f64,
+}
+
+impl Default for Handling {
+  fn default() -> Self {
+    Handling {
+      das: 10.0,
+      arr: 2.0,
+      sdf: 6.0,
+    }
+  }
+}
+
+// all times are in frames
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Timing {
+  // rows per frame, 0 gives unlimited time to move the piece
+  pub gravity: f64,
+  pub lock_delay: f64,
+  // moves that restart the lock delay before the piece locks regardless
+  pub lock_resets: u8,
+  pub handling: Handling,
+  // entry delay before every piece, and on top of it after a line clear
+  #[serde(default)]
+  pub are: f64,
+  #[serde(default)]
+  pub line_clear_are: f64,
+}
+
+impl Default for Timing {
+  fn default() -> Self {
+    Timing {
+      gravity: 0.0,
+      lock_delay: 30.0,
+      lock_resets: 15,
+      handling: Handling::default(),
+      are: 0.0,
+      line_clear_are: 0.0,
+    }
+  }
+}
+
+// time spent on the current piece
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Clock {
+  // frames since the piece spawned
+  pub frame: f64,
+  // progress towards the next row
+  pub fall: f64,
+  // frames on the ground since the last reset
+  pub lock: f64,
+  pub resets: u8,
+}
+
+// the clocks a search reached each spot with, keyed by the compressed position
+#[derive(Default)]
+pub struct Arrivals(HashMap<u16, Vec<Clock>>);
+
+impl Arrivals {
+  // false if an earlier arrival had at least as much time left on the ground
+  pub fn insert(&mut self, spot: u16, clock: Clock) -> bool {
+    let clocks = self.0.entry(spot).or_default();
+    if clocks
+      .iter()
+      .any(|c| c.resets <= clock.resets && c.lock <= clock.lock)
+    {
+      return false;
+    }
+
+    clocks.push(clock);
+    true
+  }
+}
+
+impl Timing {
+  #[inline(always)]
+  pub fn enabled(&self) -> bool {
+    self.gravity > 0.0
+  }
+
+  // frames between a piece locking and the next one spawning
+  pub fn entry_delay(&self, cleared: bool) -> f64 {
+    if cleared {
+      self.are + self.line_clear_are
+    } else {
+      self.are
+    }
+  }
+
+  // garbage timings are counted in pieces, at `pps` no piece takes less than its entry delay
+  pub fn frames_to_pieces(&self, frames: f64, pps: f64) -> u8 {
+    let per_piece = (60.0 / pps).max(self.are + KEY_FRAMES);
+    (frames / per_piece).ceil() as u8
+  }
+
+  // frames a move takes, given how many cells the piece travelled
+  pub fn duration(&self, mv: Move, cells: u8) -> f64 {
+    match mv {
+      Move::DasLeft | Move::DasRight => {
+        self.handling.das + self.handling.arr * cells.saturating_sub(1) as f64
+      }
+      Move::SoftDrop if self.handling.sdf >= 41.0 => 0.0,
+      Move::SoftDrop => cells as f64 / (self.gravity.max(BASE_GRAVITY) * self.handling.sdf),
+      Move::HardDrop | Move::None => 0.0,
+      _ => KEY_FRAMES,
+    }
+  }
+
+  // lets `frames` pass, the first `airborne` of them before a move brought the piece down, returns
+  // false if the piece locked in the meantime
+  pub fn advance(
+    &self,
+    game: &mut Game,
+    map: &CollisionMap,
+    clock: &mut Clock,
+    frames: f64,
+    airborne: f64,
+    reset: bool,
+  ) -> bool {
+    clock.frame += frames;
+    // only frames on the ground count towards the lock delay
+    let mut grounded = frames - airborne;
+
+    if self.gravity >= 20.0 {
+      if game.soft_drop(map) {
+        game.spin = Spin::None;
+      }
+    } else {
+      let progress = clock.fall;
+      let mut rows = 0;
+      clock.fall += frames * self.gravity;
+      while clock.fall >= 1.0 {
+        if game.piece.y == 0 || map.test(game.piece.x, game.piece.y - 1, game.piece.rot) {
+          clock.fall = 0.0;
+          break;
+        }
+        clock.fall -= 1.0;
+        game.piece.y -= 1;
+        game.spin = Spin::None;
+        rows += 1;
+      }
+
+      // the piece touched down when it fell its last row
+      if rows > 0 {
+        let landed = (rows as f64 - progress) / self.gravity;
+        grounded = grounded.min((frames - landed).max(0.0));
+      }
+    }
+
+    let on_ground = game.piece.y == 0 || map.test(game.piece.x, game.piece.y - 1, game.piece.rot);
+    if !on_ground {
+      clock.lock = 0.0;
+      return true;
+    }
+
+    if reset && clock.resets < self.lock_resets {
+      clock.resets += 1;
+      clock.lock = 0.0;
+    }
+    clock.lock += grounded;
+
+    clock.lock <= self.lock_delay
+  }
+
+  // lets the time a move took pass once it ran, returns false if the piece locked in the meantime
+  pub fn after_move(
+    &self,
+    mv: Move,
+    moved: bool,
+    cells: u8,
+    game: &mut Game,
+    map: &CollisionMap,
+    clock: &mut Clock,
+  ) -> bool {
+    let frames = self.duration(mv, cells);
+    // a soft drop only touches down once it is over
+    let airborne = if mv == Move::SoftDrop { frames } else { 0.0 };
+    self.advance(
+      game,
+      map,
+      clock,
+      frames,
+      airborne,
+      moved && mv != Move::SoftDrop,
+    )
+  }
+
+  // runs a move and lets the time it takes pass, None if the piece locked before it finished
+  pub fn run(
+    &self,
+    mv: Move,
+    game: &mut Game,
+    config: &GameConfig,
+    map: &CollisionMap,
+    start: &StartState,
+    clock: &mut Clock,
+  ) -> Option<bool> {
+    let (x, y) = (game.piece.x, game.piece.y);
+    let moved = mv.run(game, config, map, start);
+    let cells = x.abs_diff(game.piece.x).max(y.abs_diff(game.piece.y));
+
+    if self.after_move(mv, moved, cells, game, map, clock) {
+      Some(moved)
+    } else {
+      None
+    }
+  }
+}
@@ -0,0 +1,132 @@
+// zobrist style hashing of search states. a state's hash is the xor of one key per part, so when
+// a part changes its old key can be xored out and the new one in without rehashing the rest.
+// both engines keep their hash up to date that way through `delta` and `board_delta`
+
+use crate::game::MAX_WIDTH;
+
+const fn splitmix64(x: u64) -> u64 {
+  let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+  z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+  z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+  z ^ (z >> 31)
+}
+
+const fn keys<const N: usize>(seed: u64) -> [u64; N] {
+  let mut keys = [0; N];
+  let mut i = 0;
+  while i < N {
+    keys[i] = splitmix64(seed ^ splitmix64(i as u64));
+    i += 1;
+  }
+  keys
+}
+
+const COLUMN_KEYS: [u64; MAX_WIDTH] = keys(0x636f_6c75_6d6e);
+const STATE_KEYS: [u64; 5] = keys(0x7374_6174_65);
+const HOLD_KEY: u64 = STATE_KEYS[0];
+const QUEUE_KEY: u64 = STATE_KEYS[1];
+const B2B_KEY: u64 = STATE_KEYS[2];
+const COMBO_KEY: u64 = STATE_KEYS[3];
+const GARBAGE_KEY: u64 = STATE_KEYS[4];
+
+#[inline(always)]
+pub const fn column(idx: usize, col: u64) -> u64 {
+  splitmix64(COLUMN_KEYS[idx] ^ col)
+}
+
+// `mino` is the piece's discriminant
+#[inline(always)]
+pub const fn hold(mino: Option<u8>) -> u64 {
+  splitmix64(
+    HOLD_KEY
+      ^ match mino {
+        Some(mino) => mino as u64 + 1,
+        None => 0,
+      },
+  )
+}
+
+#[inline(always)]
+pub const fn queue_ptr(ptr: usize) -> u64 {
+  splitmix64(QUEUE_KEY ^ ptr as u64)
+}
+
+#[inline(always)]
+pub const fn b2b(b2b: i16) -> u64 {
+  splitmix64(B2B_KEY ^ b2b as u16 as u64)
+}
+
+#[inline(always)]
+pub const fn combo(combo: i16) -> u64 {
+  splitmix64(COMBO_KEY ^ combo as u16 as u64)
+}
+
+// garbage entries already taken from the queue, and lines already gone from the next one
+#[inline(always)]
+pub const fn garbage((index, tanked): (usize, u16)) -> u64 {
+  splitmix64(GARBAGE_KEY ^ index as u64 ^ ((tanked as u64) << 48))
+}
+
+#[inline(always)]
+pub fn board(cols: &[u64]) -> u64 {
+  cols
+    .iter()
+    .enumerate()
+    .fold(0, |hash, (idx, &col)| hash ^ column(idx, col))
+}
+
+// xoring this into a hash of a board with columns `before` makes it a hash of `after`
+#[inline(always)]
+pub fn board_delta(before: &[u64], after: &[u64]) -> u64 {
+  before
+    .iter()
+    .zip(after)
+    .enumerate()
+    .filter(|(_, (old, new))| old != new)
+    .fold(0, |delta, (idx, (&old, &new))| {
+      delta ^ column(idx, old) ^ column(idx, new)
+    })
+}
+
+// everything besides the board that goes into a state's hash
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Parts {
+  pub hold: Option<u8>,
+  pub queue_ptr: usize,
+  pub b2b: i16,
+  pub combo: i16,
+  pub garbage: (usize, u16),
+}
+
+impl Parts {
+  pub const fn hash(&self) -> u64 {
+    hold(self.hold)
+      ^ queue_ptr(self.queue_ptr)
+      ^ b2b(self.b2b)
+      ^ combo(self.combo)
+      ^ garbage(self.garbage)
+  }
+
+  // xoring this into a hash with `self` makes it one with `new`, only the parts that changed
+  // get rehashed
+  #[inline(always)]
+  pub fn delta(&self, new: &Self) -> u64 {
+    let mut delta = 0;
+    if self.hold != new.hold {
+      delta ^= hold(self.hold) ^ hold(new.hold);
+    }
+    if self.queue_ptr != new.queue_ptr {
+      delta ^= queue_ptr(self.queue_ptr) ^ queue_ptr(new.queue_ptr);
+    }
+    if self.b2b != new.b2b {
+      delta ^= b2b(self.b2b) ^ b2b(new.b2b);
+    }
+    if self.combo != new.combo {
+      delta ^= combo(self.combo) ^ combo(new.combo);
+    }
+    if self.garbage != new.garbage {
+      delta ^= garbage(self.garbage) ^ garbage(new.garbage);
+    }
+    delta
+  }
+}
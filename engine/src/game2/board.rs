@@ -1,31 +1,80 @@
+use crate::game::{BOARD_BUFFER, BOARD_HEIGHT, BOARD_WIDTH, MAX_HEIGHT, MAX_WIDTH, MIN_WIDTH};
+
+// columns past the width are always empty
 #[derive(Debug, Copy, Clone)]
 pub struct Board {
-  pub data: [u64; Self::WIDTH],
+  pub data: [u64; MAX_WIDTH],
+  width: u8,
+  height: u8,
 }
 
 impl Board {
-  pub const WIDTH: usize = 10;
-  pub const HEIGHT: u32 = 40;
-  pub const BUFFER: u32 = 20;
+  pub const MAX_WIDTH: usize = MAX_WIDTH;
+  pub const BUFFER: u32 = BOARD_BUFFER as u32;
 
   pub const fn new() -> Self {
+    Self::with_size(BOARD_WIDTH, BOARD_HEIGHT)
+  }
+
+  // same limits as the legacy board, height includes the buffer
+  pub const fn with_size(width: usize, height: usize) -> Self {
+    assert!(width >= MIN_WIDTH, "board is too narrow");
+    assert!(width <= MAX_WIDTH, "board is too wide");
+    assert!(height > BOARD_BUFFER, "board is too short");
+    assert!(height <= MAX_HEIGHT, "board is too tall");
+
+    Self {
+      data: [0; MAX_WIDTH],
+      width: width as u8,
+      height: height as u8,
+    }
+  }
+
+  // an empty board of the same size
+  #[inline(always)]
+  pub const fn empty(&self) -> Self {
     Self {
-      data: [0; Self::WIDTH],
+      data: [0; MAX_WIDTH],
+      ..*self
+    }
+  }
+
+  #[inline(always)]
+  pub const fn width(&self) -> usize {
+    self.width as usize
+  }
+
+  #[inline(always)]
+  pub const fn height(&self) -> u32 {
+    self.height as u32
+  }
+
+  #[inline(always)]
+  pub const fn height_mask(&self) -> u64 {
+    u64::MAX >> (64 - self.height)
+  }
+
+  #[inline(always)]
+  const fn clear_outside(&mut self) {
+    let mut x = self.width();
+    while x < MAX_WIDTH {
+      self.data[x] = 0;
+      x += 1;
     }
   }
 
   pub fn set(&mut self, x: usize, y: u8) {
     assert!(
-      x < Self::WIDTH,
+      x < self.width(),
       "x value of {} is out of bounds [0, {})",
       x,
-      Self::WIDTH
+      self.width()
     );
     assert!(
-      (y as u32) < Self::HEIGHT,
+      (y as u32) < self.height(),
       "y value of {} is out of bounds [0, {})",
       y,
-      Self::HEIGHT
+      self.height()
     );
     self.data[x] |= 1u64 << y;
   }
@@ -41,7 +90,7 @@ impl Board {
   pub fn clear_lines(&mut self, mut lines: u64) {
     loop {
       let mask = !(((lines as i64 & -(lines as i64)) - 1) as u64);
-      for x in 0..Self::WIDTH {
+      for x in 0..self.width() {
         unsafe {
           let col = *self.data.get_unchecked(x);
           *self.data.get_unchecked_mut(x) = col ^ ((col ^ (col >> 1)) & mask);
@@ -56,11 +105,11 @@ impl Board {
 
   #[inline(always)]
   pub const fn shift_const<const DX: i8, const DY: i8>(&self) -> Self {
-    let mut board = Self::new();
-    if DX >= Self::WIDTH as i8
-      || DX <= -(Self::WIDTH as i8)
-      || DY >= Self::HEIGHT as i8
-      || DY <= -(Self::HEIGHT as i8)
+    let mut board = self.empty();
+    if DX >= self.width as i8
+      || DX <= -(self.width as i8)
+      || DY >= self.height as i8
+      || DY <= -(self.height as i8)
     {
       return board;
     }
@@ -71,7 +120,7 @@ impl Board {
 
     if DX >= 0 {
       let adx = DX as usize;
-      let limit = Self::WIDTH - adx;
+      let limit = self.width() - adx;
       let mut i = 0;
       while i < limit {
         let val = self.data[i];
@@ -86,7 +135,7 @@ impl Board {
       }
     } else {
       let adx = -DX as usize;
-      let limit = Self::WIDTH - adx;
+      let limit = self.width() - adx;
       let mut i = 0;
       while i < limit {
         let val = self.data[i + adx];
@@ -113,12 +162,13 @@ impl Board {
   pub fn shift_left(&self) -> Self {
     Self {
       data: std::array::from_fn(|i| {
-        if i < Board::WIDTH - 1 {
+        if i < MAX_WIDTH - 1 {
           unsafe { *self.data.get_unchecked(i + 1) }
         } else {
           0
         }
       }),
+      ..*self
     }
   }
 
@@ -126,12 +176,13 @@ impl Board {
   pub fn shift_right(&self) -> Self {
     Self {
       data: std::array::from_fn(|i| {
-        if i == 0 {
+        if i == 0 || i >= self.width() {
           0
         } else {
           unsafe { *self.data.get_unchecked(i - 1) }
         }
       }),
+      ..*self
     }
   }
 
@@ -139,6 +190,7 @@ impl Board {
   pub fn shift_down(&self) -> Self {
     Self {
       data: self.data.map(|c| c >> 1),
+      ..*self
     }
   }
 
@@ -146,18 +198,19 @@ impl Board {
   pub fn shift_up(&self) -> Self {
     Self {
       data: self.data.map(|c| c << 1),
+      ..*self
     }
   }
 
   #[inline(always)]
   pub fn shift(&self, dx: i8, dy: i8) -> Self {
-    let mut board = Self::new();
+    let mut board = self.empty();
     let adx = dx.unsigned_abs() as usize;
-    if adx >= Self::WIDTH {
+    if adx >= self.width() {
       return board;
     }
 
-    let limit = Self::WIDTH - adx;
+    let limit = self.width() - adx;
 
     for i in 0..limit {
       let src_idx = if dx < 0 { i + adx } else { i };
@@ -186,7 +239,7 @@ impl Board {
   #[inline(always)]
   pub fn real_height(&self) -> u32 {
     let mut tmp = 0;
-    for x in 0..Self::WIDTH {
+    for x in 0..self.width() {
       tmp |= self.data[x];
     }
     64 - tmp.leading_zeros()
@@ -198,7 +251,7 @@ impl Board {
       .map(|h| {
         let mut str = String::new();
         str += &format!("{:2}|", h + 1);
-        for (_x, col) in self.data.iter().enumerate() {
+        for (_x, col) in self.data[..self.width()].iter().enumerate() {
           if (col & (1u64 << h)) != 0 {
             str += &format!("\x1B[100m  \x1B[49m");
           } else {
@@ -221,7 +274,7 @@ impl Board {
   #[inline(always)]
   pub fn count_ones(&self) -> u32 {
     let mut sum = 0;
-    for x in 0..Self::WIDTH {
+    for x in 0..self.width() {
       sum += self.data[x].count_ones();
     }
     sum
@@ -230,12 +283,37 @@ impl Board {
   #[inline(always)]
   pub fn line_clears(&self) -> u64 {
     let mut acc = !0u64;
-    for x in 0..Self::WIDTH {
+    for x in 0..self.width() {
       acc &= self.data[x];
     }
     acc
   }
 
+  // pushes the stack up, filling the bottom rows except for the hole column
+  pub fn insert_garbage(&mut self, amount: u16, column: u8) {
+    assert!(
+      (column as usize) < self.width(),
+      "hole-column out of bounds"
+    );
+
+    if amount == 0 {
+      return;
+    }
+
+    let all_mask = self.height_mask();
+    let bottom_mask = (1u64 << amount) - 1;
+
+    for x in 0..self.width() {
+      let shifted = (self.data[x] << amount) & all_mask;
+
+      self.data[x] = if x == column as usize {
+        shifted
+      } else {
+        shifted | bottom_mask
+      };
+    }
+  }
+
   #[inline(always)]
   fn clear_setup(&mut self, garbage_level: u8) -> (u64, u8) {
     let clear_mask = self.line_clears();
@@ -283,7 +361,7 @@ impl Board {
 
     loop {
       let mask = !(((clear_mask as i64 & -(clear_mask as i64)) - 1) as u64);
-      for x in 0..Self::WIDTH {
+      for x in 0..self.width() {
         let col = self.data[x];
         self.data[x] = col ^ ((col ^ (col >> 1)) & mask);
       }
@@ -363,6 +441,7 @@ impl Not for Board {
     for val in self.data.iter_mut() {
       *val = !*val;
     }
+    self.clear_outside();
     self
   }
 }
@@ -405,7 +484,7 @@ impl Shr<usize> for Board {
 
 impl PartialEq for Board {
   fn eq(&self, other: &Self) -> bool {
-    self.data == other.data
+    self.width == other.width && self.height == other.height && self.data == other.data
   }
 }
 
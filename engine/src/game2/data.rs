@@ -1,8 +1,10 @@
 use const_for::const_for;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::marker::ConstParamTy;
 
-// use super::{Game, GameConfig};
+use super::Game;
+use crate::game::{GameConfig, StartState};
 
 #[derive(Debug)]
 pub struct TetrominoMatrix {
@@ -148,6 +150,37 @@ impl Mino {
   }
 }
 
+impl From<triangle::engine::queue::Mino> for Mino {
+  fn from(mino: triangle::engine::queue::Mino) -> Self {
+    use triangle::engine::queue::Mino as T;
+    match mino {
+      T::I => Mino::I,
+      T::J => Mino::J,
+      T::L => Mino::L,
+      T::O => Mino::O,
+      T::S => Mino::S,
+      T::T => Mino::T,
+      T::Z => Mino::Z,
+      _ => panic!("Invalid mino type: {:?}", mino),
+    }
+  }
+}
+
+impl From<Mino> for triangle::engine::queue::Mino {
+  fn from(mino: Mino) -> Self {
+    use triangle::engine::queue::Mino as T;
+    match mino {
+      Mino::I => T::I,
+      Mino::J => T::J,
+      Mino::L => T::L,
+      Mino::O => T::O,
+      Mino::S => T::S,
+      Mino::T => T::T,
+      Mino::Z => T::Z,
+    }
+  }
+}
+
 const fn make_matrix(size: u8, initial: [(i8, i8); 4]) -> TetrominoMatrix {
   let mut rots = [initial; 4];
 
@@ -337,6 +370,21 @@ pub enum KickTable {
   SRSX,
 }
 
+// gives back the table when game2 has no data for it
+impl TryFrom<triangle::engine::utils::KickTable> for KickTable {
+  type Error = triangle::engine::utils::KickTable;
+
+  fn try_from(kicks: triangle::engine::utils::KickTable) -> Result<Self, Self::Error> {
+    use triangle::engine::utils::KickTable as T;
+    match kicks {
+      T::SRS => Ok(KickTable::SRS),
+      T::SRSPlus => Ok(KickTable::SRSPlus),
+      T::SRSX => Ok(KickTable::SRSX),
+      _ => Err(kicks),
+    }
+  }
+}
+
 impl KickTable {
   #[inline(always)]
   pub const fn get_index(from: u8, to: u8) -> usize {
@@ -355,15 +403,13 @@ impl KickTable {
 
   #[inline(always)]
   pub const fn data(&self, mino: Mino, from: u8, to: u8) -> &[(i8, i8); KICKTABLE_SIZE] {
-    match mino {
-      Mino::I => &self.raw().i[KickTable::get_index(from, to)],
-      _ => &self.raw().standard[KickTable::get_index(from, to)],
-    }
+    self.raw().data(mino, from, to)
   }
 }
 
 const KICKTABLE_SIZE: usize = 11;
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct KickData {
   pub real_size: usize,
   pub standard: [[(i8, i8); KICKTABLE_SIZE]; 12],
@@ -373,6 +419,14 @@ pub struct KickData {
 const I_OFFSET_TABLE: [(i8, i8); 4] = [(0, 0), (-1, 0), (-1, -1), (0, -1)];
 
 impl KickData {
+  #[inline(always)]
+  pub const fn data(&self, mino: Mino, from: u8, to: u8) -> &[(i8, i8); KICKTABLE_SIZE] {
+    match mino {
+      Mino::I => &self.i[KickTable::get_index(from, to)],
+      _ => &self.standard[KickTable::get_index(from, to)],
+    }
+  }
+
   /// modify i kicks to work with true rotation
   pub const fn convert_i_kicks(&self) -> Self {
     let mut i = self.i;
@@ -1371,6 +1425,361 @@ pub const SRS_X_KICKS: KickData = KickData {
 }
 .convert_i_kicks();
 
+// repeats the last kick, tests past `real_size` are never reached
+const fn pad_kicks(kicks: &[(i8, i8)]) -> [(i8, i8); KICKTABLE_SIZE] {
+  let mut res = [kicks[kicks.len() - 1]; KICKTABLE_SIZE];
+  const_for!(i in 0..kicks.len() => {
+    res[i] = kicks[i];
+  });
+  res
+}
+
+// cells of every srs rotation as a rotation system draws them in its box (x right, y down), so
+// the piece moves between rotations to land on them. indexed by `Mino`, then srs rotation
+type RotationStates = [[[(i8, i8); 4]; 4]; 7];
+
+const MINOS: [Mino; 7] = [
+  Mino::I,
+  Mino::J,
+  Mino::L,
+  Mino::O,
+  Mino::S,
+  Mino::T,
+  Mino::Z,
+];
+
+// arika's tgm rotation, horizontal pieces sit on the bottom of their box and vertical ones use its
+// center column. t, j and l spawn pointing down, which is srs rotation 2
+const ARS_STATES: RotationStates = [
+  [
+    [(0, 1), (1, 1), (2, 1), (3, 1)],
+    [(2, 0), (2, 1), (2, 2), (2, 3)],
+    [(0, 1), (1, 1), (2, 1), (3, 1)],
+    [(2, 0), (2, 1), (2, 2), (2, 3)],
+  ],
+  [
+    [(0, 1), (0, 2), (1, 2), (2, 2)],
+    [(1, 0), (2, 0), (1, 1), (1, 2)],
+    [(0, 1), (1, 1), (2, 1), (2, 2)],
+    [(1, 0), (1, 1), (0, 2), (1, 2)],
+  ],
+  [
+    [(2, 1), (0, 2), (1, 2), (2, 2)],
+    [(1, 0), (1, 1), (1, 2), (2, 2)],
+    [(0, 1), (1, 1), (2, 1), (0, 2)],
+    [(0, 0), (1, 0), (1, 1), (1, 2)],
+  ],
+  [[(1, 1), (2, 1), (1, 2), (2, 2)]; 4],
+  [
+    [(1, 1), (2, 1), (0, 2), (1, 2)],
+    [(0, 0), (0, 1), (1, 1), (1, 2)],
+    [(1, 1), (2, 1), (0, 2), (1, 2)],
+    [(0, 0), (0, 1), (1, 1), (1, 2)],
+  ],
+  [
+    [(1, 1), (0, 2), (1, 2), (2, 2)],
+    [(1, 0), (1, 1), (2, 1), (1, 2)],
+    [(0, 1), (1, 1), (2, 1), (1, 2)],
+    [(1, 0), (0, 1), (1, 1), (1, 2)],
+  ],
+  [
+    [(0, 1), (1, 1), (1, 2), (2, 2)],
+    [(2, 0), (1, 1), (2, 1), (1, 2)],
+    [(0, 1), (1, 1), (1, 2), (2, 2)],
+    [(2, 0), (1, 1), (2, 1), (1, 2)],
+  ],
+];
+
+// the nes rotation, around the piece's rotation cell. t, j and l turn in place and spawn pointing
+// down, i, s and z only have two states
+const NRS_STATES: RotationStates = [
+  [
+    [(-2, 0), (-1, 0), (0, 0), (1, 0)],
+    [(0, -2), (0, -1), (0, 0), (0, 1)],
+    [(-2, 0), (-1, 0), (0, 0), (1, 0)],
+    [(0, -2), (0, -1), (0, 0), (0, 1)],
+  ],
+  [
+    [(-1, -1), (-1, 0), (0, 0), (1, 0)],
+    [(0, -1), (1, -1), (0, 0), (0, 1)],
+    [(-1, 0), (0, 0), (1, 0), (1, 1)],
+    [(0, -1), (0, 0), (-1, 1), (0, 1)],
+  ],
+  [
+    [(1, -1), (-1, 0), (0, 0), (1, 0)],
+    [(0, -1), (0, 0), (0, 1), (1, 1)],
+    [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+    [(-1, -1), (0, -1), (0, 0), (0, 1)],
+  ],
+  [[(-1, 0), (0, 0), (-1, 1), (0, 1)]; 4],
+  [
+    [(0, 0), (1, 0), (-1, 1), (0, 1)],
+    [(0, -1), (0, 0), (1, 0), (1, 1)],
+    [(0, 0), (1, 0), (-1, 1), (0, 1)],
+    [(0, -1), (0, 0), (1, 0), (1, 1)],
+  ],
+  [
+    [(0, -1), (-1, 0), (0, 0), (1, 0)],
+    [(0, -1), (0, 0), (1, 0), (0, 1)],
+    [(-1, 0), (0, 0), (1, 0), (0, 1)],
+    [(0, -1), (-1, 0), (0, 0), (0, 1)],
+  ],
+  [
+    [(-1, 0), (0, 0), (0, 1), (1, 1)],
+    [(1, -1), (0, 0), (1, 0), (0, 1)],
+    [(-1, 0), (0, 0), (0, 1), (1, 1)],
+    [(1, -1), (0, 0), (1, 0), (0, 1)],
+  ],
+];
+
+// where the piece's center sits in the box for every rotation (x right, y down)
+const fn state_offsets(mino: Mino, states: &[[(i8, i8); 4]; 4]) -> [(i8, i8); 4] {
+  let mut res = [(0, 0); 4];
+  const_for!(rot in 0..4 => {
+    let blocks = mino.rot(rot as u8);
+    let mut block_min = (i8::MAX, i8::MAX);
+    let mut cell_min = (i8::MAX, i8::MAX);
+    const_for!(i in 0..4 => {
+      // blocks point y up
+      if blocks[i].0 < block_min.0 {
+        block_min.0 = blocks[i].0;
+      }
+      if -blocks[i].1 < block_min.1 {
+        block_min.1 = -blocks[i].1;
+      }
+      if states[rot][i].0 < cell_min.0 {
+        cell_min.0 = states[rot][i].0;
+      }
+      if states[rot][i].1 < cell_min.1 {
+        cell_min.1 = states[rot][i].1;
+      }
+    });
+    let offset = (cell_min.0 - block_min.0, cell_min.1 - block_min.1);
+
+    // catches states that aren't the srs rotation they're listed under
+    const_for!(i in 0..4 => {
+      let cell = (blocks[i].0 + offset.0, offset.1 - blocks[i].1);
+      let mut found = false;
+      const_for!(j in 0..4 => {
+        if states[rot][j].0 == cell.0 && states[rot][j].1 == cell.1 {
+          found = true;
+        }
+      });
+      assert!(found, "rotation state doesn't match the piece");
+    });
+    res[rot] = offset;
+  });
+  res
+}
+
+// every kick moves the piece from its box offset in one rotation to the other before the test
+const fn state_kicks(
+  offsets: &[(i8, i8); 4],
+  tests: &[(i8, i8)],
+) -> [[(i8, i8); KICKTABLE_SIZE]; 12] {
+  let mut res = [[(0, 0); KICKTABLE_SIZE]; 12];
+  const_for!(from in 0..4 => {
+    const_for!(to in 0..4 => {
+      if from != to {
+        let mut kicks = pad_kicks(tests);
+        const_for!(i in 0..KICKTABLE_SIZE => {
+          kicks[i].0 += offsets[to].0 - offsets[from].0;
+          kicks[i].1 += offsets[to].1 - offsets[from].1;
+        });
+        res[KickTable::get_index(from as u8, to as u8)] = kicks;
+      }
+    });
+  });
+  res
+}
+
+// one table per piece. the legacy game turns the I piece around the corner of its srs box instead
+// of its center, so the same offsets as `convert_i_kicks` undo that
+const fn system_kicks(
+  states: &RotationStates,
+  tests: &[(i8, i8)],
+  i_tests: &[(i8, i8)],
+  legacy: bool,
+) -> [KickData; 7] {
+  let mut res = [NONE_KICKS; 7];
+  const_for!(m in 0..7 => {
+    let is_i = matches!(MINOS[m], Mino::I);
+    let mut offsets = state_offsets(MINOS[m], &states[m]);
+    if legacy && is_i {
+      const_for!(rot in 0..4 => {
+        offsets[rot].0 += I_OFFSET_TABLE[rot].0;
+        offsets[rot].1 += I_OFFSET_TABLE[rot].1;
+      });
+    }
+
+    let tests = if is_i { i_tests } else { tests };
+    let kicks = state_kicks(&offsets, tests);
+    res[m] = KickData {
+      real_size: tests.len(),
+      standard: kicks,
+      i: kicks,
+    };
+  });
+  res
+}
+
+// tgm2 kicks one column right, then left, and never kicks the I piece
+const ARS_TESTS: [(i8, i8); 3] = [(0, 0), (1, 0), (-1, 0)];
+
+static ARS_KICKS: [KickData; 7] = system_kicks(&ARS_STATES, &ARS_TESTS, &[(0, 0)], false);
+static ARS_LEGACY_KICKS: [KickData; 7] = system_kicks(&ARS_STATES, &ARS_TESTS, &[(0, 0)], true);
+static NRS_KICKS: [KickData; 7] = system_kicks(&NRS_STATES, &[(0, 0)], &[(0, 0)], false);
+static NRS_LEGACY_KICKS: [KickData; 7] = system_kicks(&NRS_STATES, &[(0, 0)], &[(0, 0)], true);
+
+/// Rotation systems tetr.io's tables don't cover, overrides `GameConfig::kicks`. Pieces keep the
+/// SRS rotation indices, `Ars` and `Nrs` move them between their own rotation states through the
+/// kicks. `Custom` tables keep the SRS states, so only the kick tests can differ.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RotationSystem {
+  /// Arika's TGM2 rotation: bottom-aligned states, one column kicks and the center column rule
+  Ars,
+  /// The NES rotation: no kicks, two state I, S and Z
+  Nrs,
+  Custom(CustomKicks),
+}
+
+impl RotationSystem {
+  /// kicks as written, for the legacy game which doesn't use true rotation for the I piece
+  pub fn legacy_data(&self, mino: Mino) -> &KickData {
+    match self {
+      RotationSystem::Ars => &ARS_LEGACY_KICKS[mino as usize],
+      RotationSystem::Nrs => &NRS_LEGACY_KICKS[mino as usize],
+      RotationSystem::Custom(custom) => &custom.raw,
+    }
+  }
+
+  /// kicks with the I piece converted for true rotation
+  pub fn data(&self, mino: Mino) -> &KickData {
+    match self {
+      RotationSystem::Ars => &ARS_KICKS[mino as usize],
+      RotationSystem::Nrs => &NRS_KICKS[mino as usize],
+      RotationSystem::Custom(custom) => &custom.converted,
+    }
+  }
+
+  pub fn allows_180(&self) -> bool {
+    match self {
+      RotationSystem::Ars | RotationSystem::Nrs => false,
+      RotationSystem::Custom(custom) => custom.allow_180,
+    }
+  }
+
+  /// The rotation a piece spawns in and how many columns right of the SRS spawn
+  pub fn spawn(&self, mino: Mino) -> (u8, i8) {
+    match (self, mino) {
+      (RotationSystem::Ars, Mino::J | Mino::L | Mino::T) => (2, 0),
+      (RotationSystem::Nrs, Mino::J | Mino::L | Mino::T) => (2, 1),
+      // the nes centers its 3 wide pieces on the 6th column
+      (RotationSystem::Nrs, Mino::S | Mino::Z) => (0, 1),
+      _ => (0, 0),
+    }
+  }
+
+  /// Whether a blocked center column stops the piece from kicking, see `Game::rotate`
+  pub fn center_column(&self, mino: Mino) -> bool {
+    matches!(
+      (self, mino),
+      (RotationSystem::Ars, Mino::J | Mino::L | Mino::T)
+    )
+  }
+}
+
+/// Kicks keyed by transition ("01" is spawn to clockwise), in the same (x, y down) convention
+/// as the built in tables. Missing transitions only rotate in place, and 180s are only allowed
+/// when at least one of them is given. `i` defaults to the `standard` kicks.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KickLists {
+  pub standard: BTreeMap<String, Vec<(i8, i8)>>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub i: Option<BTreeMap<String, Vec<(i8, i8)>>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "KickLists", into = "KickLists")]
+pub struct CustomKicks {
+  lists: KickLists,
+  raw: Box<KickData>,
+  converted: Box<KickData>,
+  allow_180: bool,
+}
+
+fn parse_transition(key: &str) -> Result<usize, String> {
+  match key.as_bytes() {
+    &[from @ b'0'..=b'3', to @ b'0'..=b'3'] if from != to => {
+      Ok(KickTable::get_index(from - b'0', to - b'0'))
+    }
+    _ => Err(format!(
+      "invalid kick transition \"{}\", expected two different rotations like \"01\"",
+      key
+    )),
+  }
+}
+
+fn parse_kicks(
+  lists: &BTreeMap<String, Vec<(i8, i8)>>,
+  real_size: &mut usize,
+) -> Result<[[(i8, i8); KICKTABLE_SIZE]; 12], String> {
+  let mut res = [[(0, 0); KICKTABLE_SIZE]; 12];
+  for (key, kicks) in lists {
+    if kicks.is_empty() || kicks.len() > KICKTABLE_SIZE {
+      return Err(format!(
+        "transition \"{}\" has {} kicks, expected 1 to {}",
+        key,
+        kicks.len(),
+        KICKTABLE_SIZE
+      ));
+    }
+    res[parse_transition(key)?] = pad_kicks(kicks);
+    *real_size = (*real_size).max(kicks.len());
+  }
+  Ok(res)
+}
+
+impl TryFrom<KickLists> for CustomKicks {
+  type Error = String;
+
+  fn try_from(lists: KickLists) -> Result<Self, Self::Error> {
+    let mut real_size = 1;
+    let standard = parse_kicks(&lists.standard, &mut real_size)?;
+    let i = match &lists.i {
+      Some(i) => parse_kicks(i, &mut real_size)?,
+      None => standard,
+    };
+
+    let allow_180 = lists
+      .standard
+      .keys()
+      .chain(lists.i.iter().flat_map(|i| i.keys()))
+      .any(|key| matches!(key.as_str(), "02" | "13" | "20" | "31"));
+
+    let raw = KickData {
+      real_size,
+      standard,
+      i,
+    };
+    let converted = raw.convert_i_kicks();
+
+    Ok(CustomKicks {
+      lists,
+      raw: Box::new(raw),
+      converted: Box::new(converted),
+      allow_180,
+    })
+  }
+}
+
+impl From<CustomKicks> for KickLists {
+  fn from(custom: CustomKicks) -> Self {
+    custom.lists
+  }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum Move {
@@ -1398,32 +1807,46 @@ pub enum Move {
   HardDrop,
 }
 
+// both engines press the same keys
+impl From<Move> for crate::game::data::Move {
+  fn from(mv: Move) -> Self {
+    use crate::game::data::Move as M;
+    match mv {
+      Move::None => M::None,
+      Move::Left => M::Left,
+      Move::Right => M::Right,
+      Move::SoftDrop => M::SoftDrop,
+      Move::CCW => M::CCW,
+      Move::CW => M::CW,
+      Move::Flip => M::Flip,
+      Move::DasLeft => M::DasLeft,
+      Move::DasRight => M::DasRight,
+      Move::Hold => M::Hold,
+      Move::HardDrop => M::HardDrop,
+    }
+  }
+}
+
 impl Move {
   #[inline(always)]
-  // pub fn run(
-  //   &self,
-  //   game: &mut Game,
-  //   config: &GameConfig,
-  //   map: &CollisionMap,
-  //   start: &StartState,
-  // ) -> bool {
-  //   match self {
-  //     Move::Left => game.move_left(&map),
-  //     Move::Right => game.move_right(&map),
-  //     Move::SoftDrop => game.soft_drop(&map),
-  //     Move::CCW => game.rotate(3, config, &map).0,
-  //     Move::CW => game.rotate(1, config, &map).0,
-  //     Move::Flip => game.rotate(2, config, &map).0,
-  //     Move::None => panic!("None move called...cf"),
-  //     Move::DasLeft => game.das_left(&map),
-  //     Move::DasRight => game.das_right(&map),
-  //     Move::Hold => game.hold(start),
-  //     Move::HardDrop => {
-  //       game.soft_drop(&map);
-  //       true
-  //     }
-  //   }
-  // }
+  pub fn run(&self, game: &mut Game, config: &GameConfig, start: &StartState) -> bool {
+    match self {
+      Move::Left => game.move_left(),
+      Move::Right => game.move_right(),
+      Move::SoftDrop => game.soft_drop(),
+      Move::CCW => game.rotate(3, config).0,
+      Move::CW => game.rotate(1, config).0,
+      Move::Flip => game.rotate(2, config).0,
+      Move::None => panic!("None move called...cf"),
+      Move::DasLeft => game.das_left(),
+      Move::DasRight => game.das_right(),
+      Move::Hold => game.hold(config, start),
+      Move::HardDrop => {
+        game.soft_drop();
+        true
+      }
+    }
+  }
 
   pub fn str(&self) -> &str {
     match self {
@@ -0,0 +1,76 @@
+use triangle::types::game::SpinBonuses;
+
+use crate::game2::{
+  board::Board,
+  config::ConstConfig,
+  data::{KickTable, Mino},
+  map::CollisionMap,
+  movegen,
+};
+
+pub type ExpandFn = fn(&Board, (usize, u8), SpinBonuses) -> [CollisionMap; 3];
+pub type UsableFn = fn(&Board) -> CollisionMap;
+
+fn expand_board<const PIECE: Mino, const CONFIG: ConstConfig>(
+  board: &Board,
+  initial_pos: (usize, u8),
+  spins: SpinBonuses,
+) -> [CollisionMap; 3] {
+  movegen::expand::<PIECE, CONFIG>(
+    board,
+    &CollisionMap::usable::<PIECE>(board),
+    initial_pos,
+    spins,
+  )
+}
+
+// indexed by the discriminants of `Mino` and `KickTable`, keep both lists in declaration order
+macro_rules! expand_table {
+  ($($mino:ident),*; $kicks:tt) => {
+    [$(expand_table!(@mino $mino; $kicks)),*]
+  };
+  (@mino $mino:ident; [$($kicks:ident),*]) => {
+    [$([
+      expand_board::<{ Mino::$mino }, { ConstConfig { kicktable: KickTable::$kicks, enable_180: false } }> as ExpandFn,
+      expand_board::<{ Mino::$mino }, { ConstConfig { kicktable: KickTable::$kicks, enable_180: true } }> as ExpandFn,
+    ]),*]
+  };
+}
+
+macro_rules! usable_table {
+  ($($mino:ident),*) => {
+    [$(CollisionMap::usable::<{ Mino::$mino }> as UsableFn),*]
+  };
+}
+
+static EXPAND: [[[ExpandFn; 2]; 4]; 7] =
+  expand_table!(I, J, L, O, S, T, Z; [None, SRS, SRSPlus, SRSX]);
+
+static USABLE: [UsableFn; 7] = usable_table!(I, J, L, O, S, T, Z);
+
+// the monomorphized movegen for a piece and config only known at runtime
+#[inline(always)]
+pub fn expand_fn(mino: Mino, kicks: KickTable, enable_180: bool) -> ExpandFn {
+  EXPAND[mino as usize][kicks as usize][enable_180 as usize]
+}
+
+#[inline(always)]
+pub fn usable_fn(mino: Mino) -> UsableFn {
+  USABLE[mino as usize]
+}
+
+// every placement of `mino` on `board` as [all, full spins, mini spins]
+pub fn expand(
+  mino: Mino,
+  kicks: KickTable,
+  enable_180: bool,
+  board: &Board,
+  initial_pos: (usize, u8),
+  spins: SpinBonuses,
+) -> [CollisionMap; 3] {
+  expand_fn(mino, kicks, enable_180)(board, initial_pos, spins)
+}
+
+pub fn usable(mino: Mino, board: &Board) -> CollisionMap {
+  usable_fn(mino)(board)
+}
@@ -0,0 +1,603 @@
+use triangle::types::game::{Spin, SpinBonuses};
+
+use crate::game::{self, GameConfig, SpawnRules, StartState, zobrist};
+
+use super::{board::Board, data::Mino};
+
+// kicks that upgrade a mini t-spin to a full one
+#[inline(always)]
+pub const fn is_tst_or_fin(from: u8, to: u8, kick: (i8, i8)) -> bool {
+  (((from == 2 && to == 3) || (from == 0 && to == 3)) && kick.0 == 1 && kick.1 == -2)
+    || (((from == 2 && to == 1) || (from == 0 && to == 1)) && kick.0 == -1 && kick.1 == -2)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Piece {
+  pub mino: Mino,
+  // rotation center, y up
+  pub x: i8,
+  pub y: i8,
+  pub rot: u8,
+}
+
+impl Piece {
+  // centered on the board the way the legacy engine spawns it
+  pub const fn spawn(mino: Mino, board: &Board) -> Self {
+    let blocks = mino.rot(0);
+    let mut min_x = 0;
+    let mut max_x = 0;
+    let mut i = 0;
+    while i < blocks.len() {
+      if blocks[i].0 < min_x {
+        min_x = blocks[i].0;
+      }
+      if blocks[i].0 > max_x {
+        max_x = blocks[i].0;
+      }
+      i += 1;
+    }
+    let w = (max_x - min_x + 1) as usize;
+
+    Piece {
+      mino,
+      x: ((board.width() + w) / 2 - w) as i8 - min_x,
+      y: (board.height() - Board::BUFFER) as i8 + 1,
+      rot: 0,
+    }
+  }
+
+  #[inline(always)]
+  pub fn cells(&self) -> [(i8, i8); 4] {
+    self
+      .mino
+      .rot(self.rot)
+      .map(|(dx, dy)| (self.x + dx, self.y + dy))
+  }
+
+  #[inline(always)]
+  fn shifted(&self, dx: i8, dy: i8) -> Self {
+    Piece {
+      x: self.x + dx,
+      y: self.y + dy,
+      ..*self
+    }
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct Game {
+  pub board: Board,
+  // rows of garbage at the bottom of the board
+  pub garbage_level: u8,
+  pub queue_ptr: usize,
+  pub b2b: i16,
+  pub combo: i16,
+  pub hold: Option<Mino>,
+  pub piece: Piece,
+  // index, tanked
+  pub garbage: (usize, u16),
+  pub spin: Spin,
+  // blocked out or locked out
+  pub dead: bool,
+  // kept up to date by the moves, a state edited through its fields needs a `rehash`
+  hash: u64,
+}
+
+impl Game {
+  pub fn new(mino: Mino) -> Self {
+    Self::with_board(mino, Board::new())
+  }
+
+  // height includes the buffer above the visible playfield
+  pub fn with_size(mino: Mino, width: usize, height: usize) -> Self {
+    Self::with_board(mino, Board::with_size(width, height))
+  }
+
+  fn with_board(mino: Mino, board: Board) -> Self {
+    let mut game = Game {
+      board,
+      garbage_level: 0,
+      queue_ptr: 0,
+      b2b: -1,
+      combo: -1,
+      hold: None,
+      piece: Piece::spawn(mino, &board),
+      garbage: (0, 0),
+      spin: Spin::None,
+      dead: false,
+      hash: 0,
+    };
+    game.rehash();
+    game
+  }
+
+  pub fn from_legacy(legacy: &game::Game) -> Self {
+    let width = legacy.board.width();
+    let mut board = Board::with_size(width, legacy.board.height());
+    board.data[..width].copy_from_slice(&legacy.board.cols[..width]);
+
+    // both engines agree on the cells, not on where the piece is anchored
+    let mino = Mino::from(legacy.piece.mino);
+    let cells = legacy.piece.blocks().map(|(bx, by)| {
+      (
+        legacy.piece.x as i8 - bx as i8,
+        legacy.piece.y as i8 - by as i8,
+      )
+    });
+    let blocks = mino.rot(legacy.piece.rot);
+
+    let piece = Piece {
+      mino,
+      x: cells.iter().map(|c| c.0).min().unwrap() - blocks.iter().map(|b| b.0).min().unwrap(),
+      y: cells.iter().map(|c| c.1).min().unwrap() - blocks.iter().map(|b| b.1).min().unwrap(),
+      rot: legacy.piece.rot,
+    };
+
+    let mut game = Game {
+      board,
+      garbage_level: legacy.board.garbage,
+      queue_ptr: legacy.queue_ptr,
+      b2b: legacy.b2b,
+      combo: legacy.combo,
+      hold: legacy.hold.map(Mino::from),
+      piece,
+      garbage: legacy.garbage,
+      spin: legacy.spin,
+      dead: legacy.dead,
+      hash: 0,
+    };
+    game.rehash();
+    game
+  }
+
+  // same parts as `game::Game::hash`, the two engines' hashes aren't comparable
+  #[inline(always)]
+  pub fn hash(&self) -> u64 {
+    self.hash
+  }
+
+  pub fn full_hash(&self) -> u64 {
+    zobrist::board(&self.board.data[..self.board.width()]) ^ self.parts().hash()
+  }
+
+  pub fn rehash(&mut self) {
+    self.hash = self.full_hash();
+  }
+
+  #[inline(always)]
+  fn parts(&self) -> zobrist::Parts {
+    zobrist::Parts {
+      hold: self.hold.map(|mino| mino as u8),
+      queue_ptr: self.queue_ptr,
+      b2b: self.b2b,
+      combo: self.combo,
+      garbage: self.garbage,
+    }
+  }
+
+  pub fn set_queue_ptr(&mut self, ptr: usize) {
+    let parts = self.parts();
+    self.queue_ptr = ptr;
+    self.hash ^= parts.delta(&self.parts());
+  }
+
+  // the same state seen from a root `pieces` further into the queue and `garbage` further into
+  // the garbage queue
+  pub fn rebase(&mut self, pieces: usize, (index, tanked): (usize, u16)) {
+    let parts = self.parts();
+    self.queue_ptr = self.queue_ptr.saturating_sub(pieces);
+    self.garbage = if self.garbage.0 == index {
+      (0, self.garbage.1.saturating_sub(tanked))
+    } else {
+      (self.garbage.0.saturating_sub(index), self.garbage.1)
+    };
+    self.hash ^= parts.delta(&self.parts());
+  }
+
+  pub fn print(&self) {
+    let mut board = self.board;
+    for (x, y) in self.piece.cells() {
+      if x >= 0 && y >= 0 && (x as usize) < board.width() && (y as u32) < board.height() {
+        board.set(x as usize, y as u8);
+      }
+    }
+    board.print();
+  }
+
+  // out of bounds counts as occupied
+  #[inline(always)]
+  pub fn is_occupied(&self, x: i8, y: i8) -> bool {
+    x < 0
+      || y < 0
+      || x as usize >= self.board.width()
+      || y as u32 >= self.board.height()
+      || self.board.get(x as usize, y as u8)
+  }
+
+  #[inline(always)]
+  pub fn collides(&self, piece: &Piece) -> bool {
+    piece.cells().iter().any(|&(x, y)| self.is_occupied(x, y))
+  }
+
+  #[inline(always)]
+  fn test(&self, dx: i8, dy: i8) -> bool {
+    self.collides(&self.piece.shifted(dx, dy))
+  }
+
+  #[inline(always)]
+  fn try_shift(&mut self, dx: i8, dy: i8) -> bool {
+    if self.test(dx, dy) {
+      return false;
+    }
+
+    self.piece = self.piece.shifted(dx, dy);
+    true
+  }
+
+  pub fn is_immobile(&self) -> bool {
+    self.test(0, 1) && self.test(1, 0) && self.test(0, -1) && self.test(-1, 0)
+  }
+
+  // Returns (success, kicked)
+  pub fn rotate(&mut self, amount: u8, config: &GameConfig) -> (bool, bool) {
+    let from = self.piece.rot;
+    let to = (from + amount) % 4;
+
+    if amount == 2 && !config.allows_180() {
+      return (false, false);
+    }
+
+    let kicks = config
+      .kick_data(self.piece.mino)
+      .expect("unsupported kicks, check `supports` first");
+    let kickset = &kicks.data(self.piece.mino, from, to)[..kicks.real_size];
+
+    for (i, &(dx, dy)) in kickset.iter().enumerate() {
+      let rotated = Piece {
+        x: self.piece.x + dx,
+        y: self.piece.y - dy,
+        rot: to,
+        ..self.piece
+      };
+
+      if !self.collides(&rotated) {
+        self.piece = rotated;
+        self.update_spin(
+          config.srs_kicks() && is_tst_or_fin(from, to, (dx, dy)),
+          config,
+        );
+        return (true, i > 0);
+      }
+
+      // ars only kicks a j, l or t when its center column isn't the first place it's blocked
+      if i == 0 && config.center_column(self.piece.mino) && self.center_column_blocked(&rotated) {
+        break;
+      }
+    }
+
+    (false, false)
+  }
+
+  // reads the piece's cells row by row from the top left, true when the first blocked one is in
+  // the column of its rotation center
+  fn center_column_blocked(&self, piece: &Piece) -> bool {
+    let mut cells = piece.cells();
+    cells.sort_by_key(|&(x, y)| (-y, x));
+
+    cells
+      .iter()
+      .find(|&&(x, y)| self.is_occupied(x, y))
+      .is_some_and(|&(x, _)| x == piece.x)
+  }
+
+  #[inline(always)]
+  pub fn update_spin(&mut self, is_tst_or_fin: bool, config: &GameConfig) {
+    if config.spins == SpinBonuses::None {
+      return;
+    }
+
+    self.spin = self.spin_for(is_tst_or_fin, config.spins);
+  }
+
+  // the spin the piece gets when a rotation leaves it where it is
+  #[inline(always)]
+  pub fn spin_for(&self, is_tst_or_fin: bool, spins: SpinBonuses) -> Spin {
+    let t_status = if self.piece.mino == Mino::T {
+      self.detect_spin(is_tst_or_fin)
+    } else {
+      Spin::None
+    };
+
+    let immobile = match spins {
+      SpinBonuses::All
+      | SpinBonuses::AllPlus
+      | SpinBonuses::AllMini
+      | SpinBonuses::AllMiniPlus
+      | SpinBonuses::MiniOnly => self.is_immobile(),
+      _ => false,
+    };
+
+    match spins {
+      SpinBonuses::None => Spin::None,
+      SpinBonuses::Stupid => {
+        if self.test(0, -1) {
+          Spin::Normal
+        } else {
+          Spin::None
+        }
+      }
+      SpinBonuses::TSpins => t_status,
+      SpinBonuses::TSpinsPlus => {
+        if t_status != Spin::None {
+          t_status
+        } else if immobile && self.piece.mino == Mino::T {
+          Spin::Mini
+        } else {
+          Spin::None
+        }
+      }
+      SpinBonuses::All => {
+        if self.piece.mino == Mino::T {
+          t_status
+        } else if immobile {
+          Spin::Normal
+        } else {
+          Spin::None
+        }
+      }
+      SpinBonuses::AllMini => {
+        if self.piece.mino == Mino::T {
+          t_status
+        } else if immobile {
+          Spin::Mini
+        } else {
+          Spin::None
+        }
+      }
+      SpinBonuses::AllPlus => {
+        if self.piece.mino == Mino::T {
+          if t_status != Spin::None {
+            t_status
+          } else if immobile {
+            Spin::Mini
+          } else {
+            Spin::None
+          }
+        } else {
+          if immobile { Spin::Normal } else { Spin::None }
+        }
+      }
+      SpinBonuses::AllMiniPlus => {
+        if self.piece.mino == Mino::T {
+          if t_status != Spin::None {
+            t_status
+          } else if immobile {
+            Spin::Mini
+          } else {
+            Spin::None
+          }
+        } else {
+          if immobile { Spin::Mini } else { Spin::None }
+        }
+      }
+      SpinBonuses::MiniOnly => {
+        if t_status != Spin::None {
+          Spin::Mini
+        } else if immobile {
+          Spin::Mini
+        } else {
+          Spin::None
+        }
+      }
+      SpinBonuses::Handheld => self.detect_spin(is_tst_or_fin),
+    }
+  }
+
+  #[inline(always)]
+  pub fn detect_spin(&self, is_tst_or_fin: bool) -> Spin {
+    let Some(table) = self.piece.mino.corner_table(self.piece.rot) else {
+      return Spin::None;
+    };
+
+    if !self.test(0, -1) {
+      return Spin::None;
+    }
+
+    let mut corners = 0u8;
+    let mut front_corners = 0u8;
+
+    let table = table[self.piece.rot as usize];
+
+    for &((cx, cy), front) in table.iter() {
+      // corner tables are laid out from the top right of the piece's 3x3 box
+      if self.is_occupied(self.piece.x + 2 - cx, self.piece.y - cy) {
+        corners += 1;
+        if let Some((r1, r2)) = front
+          && (self.piece.rot == r1 || self.piece.rot == r2)
+        {
+          front_corners += 1;
+        }
+      }
+    }
+
+    if corners < 3 {
+      return Spin::None;
+    }
+
+    let mut spin = Spin::Normal;
+    if self.piece.mino == Mino::T && front_corners != 2 {
+      spin = Spin::Mini;
+    }
+    if is_tst_or_fin {
+      spin = Spin::Normal;
+    }
+
+    spin
+  }
+
+  pub fn move_left(&mut self) -> bool {
+    self.try_shift(-1, 0)
+  }
+
+  pub fn move_right(&mut self) -> bool {
+    self.try_shift(1, 0)
+  }
+
+  pub fn das_left(&mut self) -> bool {
+    let mut moved = false;
+    while self.try_shift(-1, 0) {
+      moved = true;
+    }
+
+    moved
+  }
+
+  pub fn das_right(&mut self) -> bool {
+    let mut moved = false;
+    while self.try_shift(1, 0) {
+      moved = true;
+    }
+
+    moved
+  }
+
+  pub fn soft_drop(&mut self) -> bool {
+    let mut moved = false;
+    while self.try_shift(0, -1) {
+      moved = true;
+    }
+
+    moved
+  }
+
+  pub fn hold(&mut self, config: &GameConfig, start_state: &StartState) -> bool {
+    let parts = self.parts();
+
+    if let Some(hold) = self.hold {
+      self.hold = Some(self.piece.mino);
+      self.set_falling(hold, config);
+    } else {
+      self.hold = Some(self.piece.mino);
+      self.next_piece(config, start_state);
+    }
+    self.hash ^= parts.delta(&self.parts());
+
+    self.apply_spawn_rules(&config.spawn);
+
+    true
+  }
+
+  pub fn next_piece(&mut self, config: &GameConfig, start_state: &StartState) {
+    assert!(self.queue_ptr < start_state.queue.len(), "Queue is empty");
+    let next = start_state.queue[self.queue_ptr];
+    self.queue_ptr += 1;
+
+    self.set_falling(next.into(), config);
+  }
+
+  // ars and nrs spawn some pieces pointing down or further right, around the same center
+  #[inline(always)]
+  pub fn set_falling(&mut self, mino: Mino, config: &GameConfig) {
+    let (rot, dx) = config.spawn_state(mino);
+    let piece = Piece::spawn(mino, &self.board);
+    self.piece = Piece {
+      x: piece.x + dx,
+      rot,
+      ..piece
+    };
+  }
+
+  // called after a piece spawns, marks the game as dead on block-out
+  #[inline(always)]
+  pub fn apply_spawn_rules(&mut self, rules: &SpawnRules) {
+    if rules.raise && self.topped_out() {
+      self.piece.y += 1;
+    }
+
+    if rules.block_out && self.topped_out() {
+      self.dead = true;
+    }
+  }
+
+  #[inline(always)]
+  pub fn topped_out(&self) -> bool {
+    self.collides(&self.piece)
+  }
+
+  // the search drops topped-out states even when the spawn rules let the game go on
+  #[inline(always)]
+  pub fn lost(&self) -> bool {
+    self.dead || self.topped_out()
+  }
+
+  pub fn insert_garbage(&mut self, amount: u16, column: u8) {
+    let cols = self.board.data;
+    self.board.insert_garbage(amount, column);
+    self.hash ^= zobrist::board_delta(&cols, &self.board.data);
+    self.garbage_level = (self.garbage_level as u32 + amount as u32).min(self.board.height()) as u8;
+  }
+
+  // Returns (attack, actual sent garbage, (clear type + lines cleared))
+  pub fn hard_drop(
+    &mut self,
+    config: &GameConfig,
+    state: &StartState,
+    timer: u8,
+  ) -> (u16, u16, (Spin, u8)) {
+    self.soft_drop();
+
+    let cols = self.board.data;
+    let parts = self.parts();
+
+    let visible_top = (self.board.height() - Board::BUFFER) as i8;
+    let mut locked_out = true;
+
+    for (x, y) in self.piece.cells() {
+      assert!(
+        x >= 0 && y >= 0,
+        "piece out of bounds: {} {} {}",
+        x,
+        y,
+        self.piece.mino.str()
+      );
+      self.board.set(x as usize, y as u8);
+      locked_out &= y >= visible_top;
+    }
+
+    let (cleared, garbage_cleared) = self.board.clear(self.garbage_level);
+    self.garbage_level -= garbage_cleared;
+    // garbage that comes in below keeps the hash up to date itself
+    self.hash ^= zobrist::board_delta(&cols, &self.board.data);
+
+    let pc = self.board.is_empty();
+
+    let attack = config.attack(
+      cleared,
+      garbage_cleared > 0,
+      self.spin,
+      pc,
+      &mut self.b2b,
+      &mut self.combo,
+    );
+
+    let mut cursor = self.garbage;
+    let sent = config.exchange_garbage(state, &mut cursor, attack, cleared, timer, |amt, col| {
+      self.insert_garbage(amt, col)
+    });
+    self.garbage = cursor;
+
+    let clear_type = self.spin;
+
+    self.spin = Spin::None;
+
+    self.next_piece(config, state);
+    self.hash ^= parts.delta(&self.parts());
+
+    if config.spawn.lock_out && locked_out {
+      self.dead = true;
+    }
+    self.apply_spawn_rules(&config.spawn);
+
+    (attack, sent, (clear_type, cleared))
+  }
+}
@@ -0,0 +1,112 @@
+use triangle::{engine::queue::Mino as LegacyMino, types::game::Spin};
+
+use crate::game::{GameConfig, StartState};
+
+use super::{Game, Piece, data::Move};
+
+// every key is tried from every state, the search is small enough not to prune
+const MOVES: [Move; 9] = [
+  Move::CW,
+  Move::CCW,
+  Move::Flip,
+  Move::Left,
+  Move::Right,
+  Move::SoftDrop,
+  Move::DasLeft,
+  Move::DasRight,
+  Move::HardDrop,
+];
+
+#[inline(always)]
+pub(crate) fn compress(piece: &Piece, spin: Spin) -> u16 {
+  // centers stay within 4 columns of the board
+  ((piece.x + 4) as u16 & 0b_11111)
+    | ((piece.y as u16 & 0b_111111) << 5)
+    | ((piece.rot as u16 & 0b11) << 11)
+    | ((spin as u16 & 0b11) << 13)
+}
+
+// fewest keys that lock the current piece on the cells of `target` with `spin`, ending in a hard drop.
+// `target` can use the canonical rotation of an expand result map, holding is up to the caller
+pub fn get_keys(
+  game: &Game,
+  config: &GameConfig,
+  target: Piece,
+  spin: Spin,
+) -> Result<Vec<Move>, String> {
+  if target.mino != game.piece.mino {
+    return Err(format!(
+      "Target is a {} but the current piece is a {}",
+      target.mino.str(),
+      game.piece.mino.str()
+    ));
+  }
+
+  let mut target_cells = target.cells();
+  target_cells.sort();
+
+  // none of the tried keys touch the queue
+  let start_state = StartState {
+    queue: &[LegacyMino::I; 32],
+    garbage: &[],
+  };
+
+  let mut state = game.clone();
+  let mut passed = [0u64; 512];
+
+  // piece, spin, parent, key pressed to get there
+  let mut nodes = vec![(game.piece, Spin::None, usize::MAX, Move::None)];
+  let start = compress(&game.piece, Spin::None);
+  passed[start as usize / 64] |= 1 << (start % 64);
+
+  let mut ptr = 0;
+  while ptr < nodes.len() {
+    let (piece, spin_state, _, _) = nodes[ptr];
+
+    for &mv in &MOVES {
+      state.piece = piece;
+      state.spin = spin_state;
+
+      if !mv.run(&mut state, config, &start_state) {
+        continue;
+      }
+
+      if mv == Move::HardDrop {
+        let mut cells = state.piece.cells();
+        cells.sort();
+
+        if cells == target_cells && state.spin == spin {
+          let mut keys = vec![Move::HardDrop];
+          let mut idx = ptr;
+          while idx != 0 {
+            keys.push(nodes[idx].3);
+            idx = nodes[idx].2;
+          }
+          keys.reverse();
+          return Ok(keys);
+        }
+        continue;
+      }
+
+      let compressed = compress(&state.piece, state.spin);
+      let idx = compressed as usize / 64;
+      let bit = 1 << (compressed % 64);
+
+      if passed[idx] & bit != 0 {
+        continue;
+      }
+      passed[idx] |= bit;
+
+      nodes.push((state.piece, state.spin, ptr, mv));
+    }
+
+    ptr += 1;
+  }
+
+  Err(format!(
+    "No keys lock the {} at {:?} with spin {}",
+    target.mino.str(),
+    target_cells,
+    spin.as_str()
+  ))
+}
@@ -60,8 +60,15 @@ impl CollisionMap {
     }
   }
 
+  // a blank map for boards the size of `board`
+  pub const fn blank_for(board: &Board) -> Self {
+    Self {
+      data: [board.empty(); 4],
+    }
+  }
+
   pub const fn usable<const PIECE: Mino>(board: &Board) -> Self {
-    let mut result = Self::blank();
+    let mut result = Self::blank_for(board);
 
     const MAX_SIZE: usize = {
       let minos = [
@@ -88,28 +95,37 @@ impl CollisionMap {
       max_w - 1
     };
 
-    let mut padded = [!0u64; MAX_SIZE + Board::WIDTH + 2];
+    // everything past the board's width stays a wall
+    let mut padded = [!0u64; MAX_SIZE + Board::MAX_WIDTH + 2];
 
-    padded[MAX_SIZE..MAX_SIZE + Board::WIDTH].copy_from_slice(&board.data);
+    let width = board.width();
+    let height_mask = board.height_mask();
+    let mut i = 0;
+    while i < width {
+      padded[MAX_SIZE + i] = board.data[i];
+      i += 1;
+    }
 
     macro_rules! make_rot {
       ($rot:expr) => {{
         let (dx0, dy0, dx1, dy1, dx2, dy2, dx3, dy3, mask0, mask1, mask2, mask3) =
           const { Self::extract_rot_data::<PIECE>($rot) };
 
-        const_for!(x in 0..Board::WIDTH as i8 => {
-          unsafe {
-            let c0 = *padded.get_unchecked((MAX_SIZE as i8 + x + dx0) as usize);
-            let c1 = *padded.get_unchecked((MAX_SIZE as i8 + x + dx1) as usize);
-            let c2 = *padded.get_unchecked((MAX_SIZE as i8 + x + dx2) as usize);
-            let c3 = *padded.get_unchecked((MAX_SIZE as i8 + x + dx3) as usize);
-
-            let val0 = if dy0 >= 0 { c0 >> dy0 as u32 } else { (c0 << (-dy0) as u32) | mask0 };
-            let val1 = if dy1 >= 0 { c1 >> dy1 as u32 } else { (c1 << (-dy1) as u32) | mask1 };
-            let val2 = if dy2 >= 0 { c2 >> dy2 as u32 } else { (c2 << (-dy2) as u32) | mask2 };
-            let val3 = if dy3 >= 0 { c3 >> dy3 as u32 } else { (c3 << (-dy3) as u32) | mask3 };
-
-            result.data[$rot].data[x as usize] = !(val0 | val1 | val2 | val3) & ((1u64 << Board::HEIGHT) - 1);
+        const_for!(x in 0..Board::MAX_WIDTH as i8 => {
+          if (x as usize) < width {
+            unsafe {
+              let c0 = *padded.get_unchecked((MAX_SIZE as i8 + x + dx0) as usize);
+              let c1 = *padded.get_unchecked((MAX_SIZE as i8 + x + dx1) as usize);
+              let c2 = *padded.get_unchecked((MAX_SIZE as i8 + x + dx2) as usize);
+              let c3 = *padded.get_unchecked((MAX_SIZE as i8 + x + dx3) as usize);
+
+              let val0 = if dy0 >= 0 { c0 >> dy0 as u32 } else { (c0 << (-dy0) as u32) | mask0 };
+              let val1 = if dy1 >= 0 { c1 >> dy1 as u32 } else { (c1 << (-dy1) as u32) | mask1 };
+              let val2 = if dy2 >= 0 { c2 >> dy2 as u32 } else { (c2 << (-dy2) as u32) | mask2 };
+              let val3 = if dy3 >= 0 { c3 >> dy3 as u32 } else { (c3 << (-dy3) as u32) | mask3 };
+
+              result.data[$rot].data[x as usize] = !(val0 | val1 | val2 | val3) & height_mask;
+            }
           }
         });
       }};
@@ -145,7 +161,7 @@ impl CollisionMap {
     let max_h = self.data[0..PIECE.real_permutations()]
       .iter()
       .fold(0, |a, b| a.max(b.real_height()))
-      .min(Board::HEIGHT)
+      .min(self.data[0].height())
       .min(crop_at);
 
     let boards = self.data[0..PIECE.real_permutations()]
@@ -177,7 +193,7 @@ impl CollisionMap {
   {
     let num_rots = piece.real_permutations();
     for rot in 0..num_rots {
-      for x in 0..Board::WIDTH {
+      for x in 0..self.data[rot].width() {
         let mut col = unsafe { *self.data.get_unchecked(rot).data.get_unchecked(x) };
         while col != 0 {
           let y = col.trailing_zeros() as u8;
@@ -205,7 +221,7 @@ pub struct FilledIter<'a> {
 impl<'a> FilledIter<'a> {
   #[inline(always)]
   pub fn new(map: &'a CollisionMap, num_rots: usize) -> Self {
-    let current_col = if num_rots > 0 && Board::WIDTH > 0 {
+    let current_col = if num_rots > 0 {
       unsafe { *map.data.get_unchecked(0).data.get_unchecked(0) }
     } else {
       0
@@ -234,7 +250,7 @@ impl<'a> Iterator for FilledIter<'a> {
       }
 
       self.x += 1;
-      if self.x >= Board::WIDTH {
+      if self.x >= self.map.data[self.rot].width() {
         self.x = 0;
         self.rot += 1;
         if self.rot >= self.num_rots {
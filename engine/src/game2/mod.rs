@@ -1,6 +1,11 @@
 pub mod board;
 pub mod config;
 pub mod data;
+pub mod dispatch;
+pub mod game;
+pub mod keyfinder;
 pub mod map;
 pub mod movegen;
 pub mod util;
+
+pub use game::{Game, Piece};
@@ -1,5 +1,19 @@
+use triangle::{
+  engine::queue::Mino as LegacyMino,
+  types::game::{Spin, SpinBonuses},
+};
+
 use super::util::const_for_dynamic;
-use crate::game2::{board::Board, config::ConstConfig, data::Mino, map::CollisionMap};
+use crate::game::{GameConfig, StartState};
+use crate::game2::{
+  Game, Piece,
+  board::Board,
+  config::ConstConfig,
+  data::{Mino, Move},
+  game::is_tst_or_fin,
+  keyfinder::compress,
+  map::CollisionMap,
+};
 
 const fn kick_table_data<
   const CONFIG: ConstConfig,
@@ -140,29 +154,44 @@ impl Bitset {
   }
 }
 
+// placements of the piece as [all, full spins, mini spins], spin maps are only filled when spins score
 pub fn expand<const PIECE: Mino, const CONFIG: ConstConfig>(
+  board: &Board,
   cmap: &CollisionMap,
   initial_pos: (usize, u8),
+  spins: SpinBonuses,
 ) -> [CollisionMap; 3] {
-  let mut res = [CollisionMap::blank(); 3];
+  let mut res = [CollisionMap::blank_for(board); 3];
+
+  // finding every spin needs every rotation source, so the search can't stop early
+  // for pieces that can spin under the rules
+  let track_spins = PIECE != Mino::O
+    && match spins {
+      SpinBonuses::None => false,
+      SpinBonuses::TSpins | SpinBonuses::TSpinsPlus => PIECE == Mino::T,
+      _ => true,
+    };
 
   let canidates = cmap.landable();
 
-  let mut search = [Board::new(); 4];
+  let mut search = [board.empty(); 4];
 
   let mut remaining = Bitset::new(const { PIECE.real_permutations() });
   let mut complete = Bitset::new(const { PIECE.search_size() as usize });
 
-  let spawn_y = 21u8;
+  let spawn_piece = Piece::spawn(PIECE, board);
+  let spawn_x = spawn_piece.x as usize;
+  let spawn_y = spawn_piece.y as u8;
   let h_spawn = PIECE.h_spawn();
   let board_y = initial_pos.1;
+  let height_mask = board.height_mask();
 
-  let is_slow_init = Board::HEIGHT > spawn_y as u32 && board_y > spawn_y.saturating_sub(h_spawn);
+  let is_slow_init = board.height() > spawn_y as u32 && board_y > spawn_y.saturating_sub(h_spawn);
 
   if is_slow_init {
-    let threshold = std::cmp::min(spawn_y + 1, Board::HEIGHT as u8);
+    let threshold = std::cmp::min(spawn_y + 1, board.height() as u8);
     let mut spawn = spawn_y;
-    while spawn < threshold && !cmap[0].get(4, spawn) {
+    while spawn < threshold && !cmap[0].get(spawn_x, spawn) {
       spawn += 1;
     }
 
@@ -170,16 +199,17 @@ pub fn expand<const PIECE: Mino, const CONFIG: ConstConfig>(
       return res;
     }
 
-    search[0].set(4, spawn);
+    search[0].set(spawn_x, spawn);
     remaining.set_all(true);
     complete.set_all(true);
     complete.off(0);
   } else {
     for rot in 0..PIECE.real_permutations() {
-      for x in 0..Board::WIDTH {
-        let blocked = ((1u64 << Board::HEIGHT) - 1) & !cmap[rot][x];
-        let fill = (1u64 << (u64::BITS - blocked.leading_zeros())) - 1;
-        search[rot][x] = ((1u64 << Board::HEIGHT) - 1) ^ fill;
+      for x in 0..board.width() {
+        let blocked = height_mask & !cmap[rot][x];
+        // everything up to the highest blocked row, boards can be a full 64 rows tall
+        let fill = u64::MAX.checked_shr(blocked.leading_zeros()).unwrap_or(0);
+        search[rot][x] = height_mask ^ fill;
       }
 
       search[rot] |= (search[rot].shift_left() | search[rot].shift_right()) & cmap[rot];
@@ -201,7 +231,7 @@ pub fn expand<const PIECE: Mino, const CONFIG: ConstConfig>(
       remaining.set(rot, res[0][rot] != canidates[rot]);
     }
 
-    if remaining.none() {
+    if remaining.none() && !track_spins {
       return res;
     }
 
@@ -245,7 +275,7 @@ pub fn expand<const PIECE: Mino, const CONFIG: ConstConfig>(
         res[0][rot_c] |= search[ROT] & canidates[rot_c];
         remaining.set(rot_c, res[0][rot_c] != canidates[rot_c]);
 
-        if remaining.none() {
+        if remaining.none() && !track_spins {
           complete.set_all(true);
           break 'rot_block;
         }
@@ -271,7 +301,7 @@ pub fn expand<const PIECE: Mino, const CONFIG: ConstConfig>(
             let off_y = canonical_offset_y(PIECE, ROT) - canonical_offset_y(PIECE, rotated);
 
             let mut tmp = search[ROT];
-            let mut result = Board::new();
+            let mut result = board.empty();
 
             const_for_dynamic!(I_UNCHECKED in 0..KickTableSize::<CONFIG>::VALUE => {
               const I: usize = if I_UNCHECKED > 10 { 10 } else { I_UNCHECKED };
@@ -306,7 +336,7 @@ pub fn expand<const PIECE: Mino, const CONFIG: ConstConfig>(
           }
           rotate!(3u8);
 
-          if remaining.none() {
+          if remaining.none() && !track_spins {
             complete.set_all(true);
             break 'rot_block;
           }
@@ -315,5 +345,162 @@ pub fn expand<const PIECE: Mino, const CONFIG: ConstConfig>(
     });
   }
 
+  if track_spins {
+    tag_spins::<PIECE, CONFIG>(board, cmap, &search, &canidates, &mut res, spins);
+  }
+
+  res
+}
+
+// marks the landings reachable through a final rotation that counts as a spin
+fn tag_spins<const PIECE: Mino, const CONFIG: ConstConfig>(
+  board: &Board,
+  cmap: &CollisionMap,
+  search: &[Board; 4],
+  canidates: &CollisionMap,
+  res: &mut [CollisionMap; 3],
+  spins: SpinBonuses,
+) {
+  let mut scratch = Game::new(PIECE);
+  scratch.board = *board;
+
+  const_for_dynamic!(R in 0..PieceSearchSize::<PIECE>::VALUE => {
+    const ROT: usize = if R >= 4 {0} else {R};
+
+    macro_rules! rotate {
+      ($amt:expr) => {
+        let rotated = const { (ROT + $amt as usize) & 3 };
+        let rotated_c = Mino::canonical_rot::<PIECE>(rotated);
+
+        let off_x = canonical_offset_x(PIECE, ROT) - canonical_offset_x(PIECE, rotated);
+        let off_y = canonical_offset_y(PIECE, ROT) - canonical_offset_y(PIECE, rotated);
+
+        let mut tmp = search[ROT];
+
+        const_for_dynamic!(I_UNCHECKED in 0..KickTableSize::<CONFIG>::VALUE => {
+          const I: usize = if I_UNCHECKED > 10 { 10 } else { I_UNCHECKED };
+          let kick = kick_table_data::<CONFIG, PIECE, ROT, $amt, I>();
+          let dx = kick.0 + off_x;
+          let dy = -kick.1 + off_y;
+
+          let landed = tmp.shift(dx, dy) & canidates[rotated_c];
+          tmp &= !(cmap[rotated_c].shift(-dx, -dy));
+
+          let tst_or_fin = is_tst_or_fin(ROT as u8, rotated as u8, kick);
+
+          for x in 0..board.width() {
+            let mut col = landed[x];
+            while col != 0 {
+              let y = col.trailing_zeros() as u8;
+              col &= col - 1;
+
+              // search positions are canonical, spins are judged on the real rotation
+              scratch.piece = Piece {
+                mino: PIECE,
+                x: x as i8 + canonical_offset_x(PIECE, rotated),
+                y: y as i8 + canonical_offset_y(PIECE, rotated),
+                rot: rotated as u8,
+              };
+
+              match scratch.spin_for(tst_or_fin, spins) {
+                Spin::Normal => res[1][rotated_c].set(x, y),
+                Spin::Mini => res[2][rotated_c].set(x, y),
+                Spin::None => {}
+              }
+            }
+          }
+        });
+      };
+    }
+
+    rotate!(1u8);
+    if CONFIG.enable_180 {
+      rotate!(2u8);
+    }
+    rotate!(3u8);
+  });
+
+  // a landing reachable as both only needs to be tried as the full spin
+  for rot in 0..PIECE.real_permutations() {
+    res[2][rot] &= !res[1][rot];
+  }
+}
+
+// the same maps as `expand` for kicks it isn't specialized for (custom tables, ARS and NRS),
+// found by pressing keys on the game like the key finder. a spin stays on the piece until it
+// locks, the way both games score it
+pub fn expand_keys(game: &Game, config: &GameConfig) -> [CollisionMap; 3] {
+  const MOVES: [Move; 6] = [
+    Move::CW,
+    Move::CCW,
+    Move::Flip,
+    Move::Left,
+    Move::Right,
+    Move::SoftDrop,
+  ];
+
+  // none of the keys touch the queue
+  let start_state = StartState {
+    queue: &[LegacyMino::I; 32],
+    garbage: &[],
+  };
+
+  let mut res = [CollisionMap::blank_for(&game.board); 3];
+  let mut state = game.clone();
+  let mut passed = [0u64; 512];
+
+  let mut nodes = vec![(game.piece, Spin::None)];
+  let start = compress(&game.piece, Spin::None);
+  passed[start as usize / 64] |= 1 << (start % 64);
+
+  let mut ptr = 0;
+  while ptr < nodes.len() {
+    let (piece, spin) = nodes[ptr];
+    ptr += 1;
+
+    state.piece = piece;
+    state.soft_drop();
+
+    // results are indexed by canonical rotation
+    let mino = piece.mino;
+    let rot = piece.rot as usize;
+    let rot_c = match mino.real_permutations() {
+      1 => 0,
+      2 => rot & 1,
+      _ => rot,
+    };
+    let x = (state.piece.x - canonical_offset_x(mino, rot)) as usize;
+    let y = (state.piece.y - canonical_offset_y(mino, rot)) as u8;
+
+    res[0][rot_c].set(x, y);
+    match spin {
+      Spin::Normal => res[1][rot_c].set(x, y),
+      Spin::Mini => res[2][rot_c].set(x, y),
+      Spin::None => {}
+    }
+
+    for &mv in &MOVES {
+      state.piece = piece;
+      state.spin = spin;
+
+      if !mv.run(&mut state, config, &start_state) {
+        continue;
+      }
+
+      let key = compress(&state.piece, state.spin);
+      if passed[key as usize / 64] & (1 << (key % 64)) != 0 {
+        continue;
+      }
+      passed[key as usize / 64] |= 1 << (key % 64);
+
+      nodes.push((state.piece, state.spin));
+    }
+  }
+
+  // a landing reachable as both only needs to be tried as the full spin
+  for rot in 0..game.piece.mino.real_permutations() {
+    res[2][rot] &= !res[1][rot];
+  }
+
   res
 }
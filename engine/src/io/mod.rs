@@ -9,7 +9,9 @@ use crate::game::{
   data::Move,
   queue::{Bag, Queue},
 };
-use crate::search::{beam_search, eval::WEIGHTS_HANDTUNED};
+use crate::search::{SearchLimits, anytime_search, eval::WEIGHTS_HANDTUNED};
+
+pub mod tbp;
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -27,6 +29,7 @@ pub struct InsertGarbage {
 
 #[derive(Deserialize)]
 pub struct Start {
+  #[serde(deserialize_with = "GameConfig::deserialize_json")]
   pub config: GameConfig,
   pub seed: u64,
   pub bag: Bag,
@@ -45,6 +48,9 @@ pub struct OpponentInfo {
 pub struct Step {
   garbage: Vec<Garbage>,
   opponent: OpponentInfo,
+  // seconds to think for, a fixed depth 7 width 1000 search without it
+  #[serde(default)]
+  time: Option<f64>,
 }
 
 #[derive(Serialize)]
@@ -90,13 +96,13 @@ pub async fn start_server() {
     match msg {
       Incoming::Start(start) => {
         queue = Queue::<32>::new(start.bag, start.seed, Vec::new());
+        game = Game::with_config(queue.shift(), &start.config);
         config = Option::from(start.config);
-        game = Game::new(queue.shift());
       }
 
       Incoming::InsertGarbage(garbage) => {
         for gb in garbage.garbage {
-          game.board.insert_garbage(gb.amt, gb.col);
+          game.insert_garbage(gb.amt, gb.col);
         }
       }
 
@@ -130,12 +136,18 @@ pub async fn start_server() {
 
         let start = std::time::Instant::now();
 
-        let choice = beam_search::<7, 1000>(
+        let limits = match cfg.time {
+          Some(time) => SearchLimits::timed(std::time::Duration::from_secs_f64(time)),
+          None => SearchLimits::fixed(7, 1000),
+        };
+
+        let choice = anytime_search(
           game.clone(),
           &(config.clone()).unwrap(),
           &start_state,
           &WEIGHTS_HANDTUNED,
           WEIGHTS_HANDTUNED.eval_opponent(&opponent),
+          &limits,
         );
         let elapsed = start.elapsed().as_secs_f64();
 
@@ -143,7 +155,7 @@ pub async fn start_server() {
           let mut double_shift = false;
           if mv.0.hold {
             double_shift = game.hold.is_none();
-            game.hold(&start_state);
+            game.hold(config.as_ref().unwrap(), &start_state);
           }
 
           let mut keys =
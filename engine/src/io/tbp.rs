@@ -0,0 +1,475 @@
+use std::collections::VecDeque;
+
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use triangle::{
+  engine::{queue::Mino, utils::KickTable},
+  types::game::{Spin, SpinBonuses},
+};
+
+use crate::game::{
+  BOARD_HEIGHT, BOARD_WIDTH, Board, Falling, Game, GameConfig, SpawnRules, StartState,
+  data::MinoData,
+  garbage::{AttackTable, GarbageEntry, Messiness, Passthrough},
+  queue::Bag,
+  timing::Timing,
+};
+use crate::search::{SearchLimits, anytime_search, eval::WEIGHTS_HANDTUNED, movegen::Placement};
+
+// Tetris Bot Protocol frontend
+// https://github.com/tetris-bot-protocol/tbp-spec
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Piece {
+  I,
+  O,
+  T,
+  L,
+  J,
+  S,
+  Z,
+}
+
+impl Piece {
+  pub fn mino(&self) -> Mino {
+    match self {
+      Piece::I => Mino::I,
+      Piece::O => Mino::O,
+      Piece::T => Mino::T,
+      Piece::L => Mino::L,
+      Piece::J => Mino::J,
+      Piece::S => Mino::S,
+      Piece::Z => Mino::Z,
+    }
+  }
+
+  // garbage and empty cells have no piece in the protocol
+  pub fn from_mino(mino: Mino) -> Result<Self, &'static str> {
+    match mino {
+      Mino::I => Ok(Piece::I),
+      Mino::O => Ok(Piece::O),
+      Mino::T => Ok(Piece::T),
+      Mino::L => Ok(Piece::L),
+      Mino::J => Ok(Piece::J),
+      Mino::S => Ok(Piece::S),
+      Mino::Z => Ok(Piece::Z),
+      _ => Err("not a playable piece"),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[repr(u8)]
+pub enum Orientation {
+  North,
+  East,
+  South,
+  West,
+}
+
+impl Orientation {
+  pub fn from_rot(rot: u8) -> Self {
+    match rot & 3 {
+      0 => Orientation::North,
+      1 => Orientation::East,
+      2 => Orientation::South,
+      _ => Orientation::West,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TbpSpin {
+  None,
+  Mini,
+  Full,
+}
+
+impl From<Spin> for TbpSpin {
+  fn from(spin: Spin) -> Self {
+    match spin {
+      Spin::None => TbpSpin::None,
+      Spin::Mini => TbpSpin::Mini,
+      Spin::Normal => TbpSpin::Full,
+    }
+  }
+}
+
+impl From<TbpSpin> for Spin {
+  fn from(spin: TbpSpin) -> Self {
+    match spin {
+      TbpSpin::None => Spin::None,
+      TbpSpin::Mini => Spin::Mini,
+      TbpSpin::Full => Spin::Normal,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Location {
+  #[serde(rename = "type")]
+  pub piece: Piece,
+  pub orientation: Orientation,
+  pub x: i8,
+  pub y: i8,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct TbpMove {
+  pub location: Location,
+  pub spin: TbpSpin,
+}
+
+impl TbpMove {
+  pub fn from_placement(mino: Mino, placement: &Placement) -> Result<Self, &'static str> {
+    let piece = Piece::from_mino(mino)?;
+    let (x, y) = Falling {
+      x: placement.x,
+      y: placement.y,
+      rot: placement.rot,
+      mino,
+    }
+    .srs_position();
+
+    Ok(TbpMove {
+      location: Location {
+        piece,
+        orientation: Orientation::from_rot(placement.rot),
+        x,
+        y,
+      },
+      spin: placement.spin.into(),
+    })
+  }
+
+  // frontends can send anything, the piece has to sit on the board without overlapping it
+  pub fn to_placement(&self, board: &Board) -> Result<Placement, &'static str> {
+    let mino = self.location.piece.mino();
+    let rot = self.location.orientation as u8;
+    let blocks = mino.rot(rot);
+    let srs = mino.srs_blocks(rot);
+
+    // the anchor `Falling::set_srs_position` picks, without wrapping
+    let x = self.location.x as i16
+      + srs.iter().map(|b| b.0).max().unwrap() as i16
+      + blocks.iter().map(|b| b.0).min().unwrap() as i16;
+    let y = self.location.y as i16
+      + srs.iter().map(|b| b.1).max().unwrap() as i16
+      + blocks.iter().map(|b| b.1).min().unwrap() as i16;
+
+    let fits = blocks.iter().all(|&(bx, by)| {
+      match (i8::try_from(x - bx as i16), i8::try_from(y - by as i16)) {
+        (Ok(cx), Ok(cy)) => !board.is_occupied(cx, cy),
+        _ => false,
+      }
+    });
+    if !fits {
+      return Err("move is off the board or overlaps it");
+    }
+
+    Ok(Placement {
+      x: x as u8,
+      y: y as u8,
+      rot,
+      spin: self.spin.into(),
+    })
+  }
+}
+
+#[derive(Deserialize)]
+pub struct Start {
+  pub hold: Option<Piece>,
+  pub queue: Vec<Piece>,
+  pub combo: u32,
+  pub back_to_back: bool,
+  pub board: Vec<Vec<Option<String>>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type")]
+pub enum FrontendMessage {
+  Rules {},
+  Start(Start),
+  Stop,
+  Suggest,
+  Play {
+    #[serde(rename = "move")]
+    mv: TbpMove,
+  },
+  NewPiece {
+    piece: Piece,
+  },
+  Quit,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type")]
+pub enum BotMessage {
+  Info {
+    name: &'static str,
+    version: &'static str,
+    author: &'static str,
+    features: Vec<&'static str>,
+  },
+  Ready,
+  Error {
+    reason: &'static str,
+  },
+  Suggestion {
+    moves: Vec<TbpMove>,
+  },
+}
+
+pub fn default_config() -> GameConfig {
+  GameConfig {
+    // the protocol's standard ruleset
+    kicks: KickTable::SRS,
+    rotation: None,
+    allow_180: false,
+    spins: SpinBonuses::TSpins,
+    attack: AttackTable::guideline(),
+    b2b_charging: false,
+    b2b_charge_at: 0,
+    b2b_charge_base: 0,
+    pc_b2b: 0,
+    garbage_multiplier: 1.0,
+    garbage_cap: 8,
+    garbage_special_bonus: false,
+    messiness: Messiness::default(),
+    garbage_phase: 0,
+    garbage_queue: false,
+    garbage_entry: GarbageEntry::Instant,
+    passthrough: Passthrough::Zero,
+    board_width: BOARD_WIDTH as u8,
+    board_height: BOARD_HEIGHT as u8,
+    spawn: SpawnRules::default(),
+    timing: Timing::default(),
+    bag: Bag::Bag7,
+  }
+}
+
+struct TbpState {
+  game: Game,
+  queue: VecDeque<Mino>,
+  // the current piece hasn't been revealed by the frontend yet
+  awaiting_piece: bool,
+}
+
+impl TbpState {
+  fn new(start: Start) -> Self {
+    let mut queue: VecDeque<Mino> = start.queue.iter().map(|p| p.mino()).collect();
+    let mut game = Game::new(queue.pop_front().unwrap_or(Mino::I));
+
+    let mut board = Board::new();
+    for (y, row) in start.board.iter().take(BOARD_HEIGHT).enumerate() {
+      for (x, cell) in row.iter().take(BOARD_WIDTH).enumerate() {
+        if cell.is_some() {
+          board.set(x, y);
+        }
+      }
+    }
+
+    game.board = board;
+    game.hold = start.hold.map(|p| p.mino());
+    game.combo = start.combo as i16 - 1;
+    game.b2b = if start.back_to_back { 0 } else { -1 };
+
+    TbpState {
+      game,
+      awaiting_piece: start.queue.is_empty(),
+      queue,
+    }
+  }
+
+  fn queue_array(&self) -> [Mino; 32] {
+    std::array::from_fn(|i| *self.queue.get(i).unwrap_or(&Mino::I))
+  }
+
+  fn suggest(
+    &self,
+    config: &GameConfig,
+    limits: &SearchLimits,
+  ) -> Result<Vec<TbpMove>, &'static str> {
+    let queue = self.queue_array();
+    let start_state = StartState {
+      queue: &queue,
+      garbage: &[],
+    };
+
+    let mut game = self.game.clone();
+    game.queue_ptr = 0;
+    game.garbage = (0, 0);
+
+    // past the pieces we were given the queue is padding, so don't search into it
+    let limits = SearchLimits {
+      depth: (self.queue.len() + 1).min(limits.depth as usize) as u8,
+      ..limits.clone()
+    };
+    let choice = anytime_search(
+      game.clone(),
+      config,
+      &start_state,
+      &WEIGHTS_HANDTUNED,
+      0.0,
+      &limits,
+    );
+
+    match choice {
+      Some((action, _)) => {
+        if action.hold {
+          game.hold(config, &start_state);
+        }
+        Ok(vec![TbpMove::from_placement(
+          game.piece.mino,
+          &action.placement,
+        )?])
+      }
+      None => {
+        let map = game.collision_map();
+        game.soft_drop(&map);
+        Ok(vec![TbpMove::from_placement(
+          game.piece.mino,
+          &Placement {
+            x: game.piece.x,
+            y: game.piece.y,
+            rot: game.piece.rot,
+            spin: Spin::None,
+          },
+        )?])
+      }
+    }
+  }
+
+  // leaves the state as it was when the move can't be played
+  fn play(&mut self, config: &GameConfig, mv: &TbpMove) -> Result<(), &'static str> {
+    if self.awaiting_piece {
+      return Err("no piece to play");
+    }
+
+    let queue = self.queue_array();
+    let start_state = StartState {
+      queue: &queue,
+      garbage: &[],
+    };
+
+    let mut game = self.game.clone();
+    game.queue_ptr = 0;
+    game.garbage = (0, 0);
+
+    if mv.location.piece.mino() != game.piece.mino {
+      game.hold(config, &start_state);
+    }
+    if mv.location.piece.mino() != game.piece.mino || game.queue_ptr as usize > self.queue.len() {
+      return Err("move is for a piece that isn't in play");
+    }
+
+    let placement = mv.to_placement(&game.board)?;
+    let map = game.collision_map();
+    game.piece.x = placement.x;
+    game.piece.y = placement.y;
+    game.piece.rot = placement.rot;
+    game.spin = placement.spin;
+    game.hard_drop(config, &map, &start_state, 0);
+    self.game = game;
+
+    for _ in 0..self.game.queue_ptr {
+      if self.queue.pop_front().is_none() {
+        self.awaiting_piece = true;
+      }
+    }
+    self.game.queue_ptr = 0;
+    Ok(())
+  }
+
+  fn new_piece(&mut self, config: &GameConfig, piece: Piece) {
+    if self.awaiting_piece {
+      self.game.set_falling(piece.mino(), config);
+      self.awaiting_piece = false;
+    } else {
+      self.queue.push_back(piece.mino());
+    }
+  }
+}
+
+pub async fn start_server(limits: SearchLimits) {
+  let incoming = futures::stream::repeat_with(|| {
+    let mut line = String::new();
+    if std::io::stdin().read_line(&mut line).unwrap() == 0 {
+      return Some(FrontendMessage::Quit);
+    }
+    serde_json::from_str::<FrontendMessage>(&line).ok()
+  });
+
+  let outgoing = futures::sink::unfold((), |_, msg: BotMessage| {
+    serde_json::to_writer(std::io::stdout(), &msg).unwrap();
+    println!();
+    async { Ok::<(), ()>(()) }
+  });
+
+  futures::pin_mut!(incoming);
+  futures::pin_mut!(outgoing);
+
+  outgoing
+    .send(BotMessage::Info {
+      name: "Falcon",
+      version: "1.0.0-a",
+      author: "halp",
+      features: vec![],
+    })
+    .await
+    .unwrap();
+
+  let config = default_config();
+  let mut state = Option::<TbpState>::None;
+
+  while let Some(msg) = incoming.next().await {
+    // unknown messages must be ignored
+    let Some(msg) = msg else {
+      continue;
+    };
+
+    match msg {
+      FrontendMessage::Rules {} => {
+        outgoing.send(BotMessage::Ready).await.unwrap();
+      }
+
+      FrontendMessage::Start(start) => {
+        state = Some(TbpState::new(start));
+      }
+
+      FrontendMessage::Stop => {
+        state = None;
+      }
+
+      FrontendMessage::Suggest => {
+        if let Some(state) = &state {
+          let msg = match state.suggest(&config, &limits) {
+            Ok(moves) => BotMessage::Suggestion { moves },
+            Err(reason) => BotMessage::Error { reason },
+          };
+          outgoing.send(msg).await.unwrap();
+        }
+      }
+
+      FrontendMessage::Play { mv } => {
+        if let Some(state) = &mut state
+          && let Err(reason) = state.play(&config, &mv)
+        {
+          outgoing.send(BotMessage::Error { reason }).await.unwrap();
+        }
+      }
+
+      FrontendMessage::NewPiece { piece } => {
+        if let Some(state) = &mut state {
+          state.new_piece(&config, piece);
+        }
+      }
+
+      FrontendMessage::Quit => break,
+    }
+  }
+}
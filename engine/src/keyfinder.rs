@@ -1,9 +1,13 @@
 use triangle::{engine::queue::Mino, types::game::Spin};
 
-use crate::game::{StartState, data::MinoData};
+use crate::game::{MAX_HEIGHT, StartState, data::MinoData};
 use crate::search::movegen::Placement;
 
-use super::game::{Game, GameConfig, data::Move};
+use super::game::{
+  Game, GameConfig,
+  data::Move,
+  timing::{Arrivals, Clock},
+};
 
 const MOVES: [[Move; 9]; 9] = [
   // None
@@ -117,8 +121,8 @@ const MOVES: [[Move; 9]; 9] = [
 ];
 
 #[inline(always)]
-pub fn compress_blocks(blocks: &[(u8, u8); 4]) -> [u64; 7] {
-  let mut res = [0u64; 7];
+pub fn compress_blocks(blocks: &[(u8, u8); 4]) -> [u64; MAX_HEIGHT / 4] {
+  let mut res = [0u64; MAX_HEIGHT / 4];
 
   for &(x, y) in blocks {
     let idx = (y / 4) as usize;
@@ -131,11 +135,8 @@ pub fn compress_blocks(blocks: &[(u8, u8); 4]) -> [u64; 7] {
 
 pub fn get_keys(mut state: Game, config: &GameConfig, target: Placement) -> Vec<Move> {
   let mut passed = [0u64; 1024];
-
-  let mut queue = [(0, 0, 0, Spin::None, ([Move::None; 16], 0usize)); 2048];
-
-  let mut front_ptr = 0;
-  let mut back_ptr = 1;
+  // under gravity a later arrival at the same spot may have more time left than the first one
+  let mut arrivals = Arrivals::default();
 
   let target_blocks = state
     .piece
@@ -147,25 +148,35 @@ pub fn get_keys(mut state: Game, config: &GameConfig, target: Placement) -> Vec<
 
   let tgt_2 = target.rot % 2;
 
-  queue[0] = (
+  let map = state.collision_map();
+
+  let timed = config.timing.enabled();
+  let mut clock = Clock::default();
+  if timed {
+    config
+      .timing
+      .advance(&mut state, &map, &mut clock, 0.0, 0.0, false);
+  }
+
+  let mut queue = vec![(
     state.piece.x,
     state.piece.y,
     state.piece.rot,
     Spin::None,
     ([Move::None; 16], 0usize),
-  );
+    clock,
+  )];
+  let mut front_ptr = 0;
 
   let game = state.clone();
 
-  let map = state.collision_map();
-
   let start_state = StartState {
     garbage: &[],
     queue: &[Mino::I; 32],
   };
 
-  while front_ptr < back_ptr {
-    let (x, y, rot, spin, moves) = queue[front_ptr];
+  while front_ptr < queue.len() {
+    let (x, y, rot, spin, moves, clock) = queue[front_ptr];
     front_ptr += 1;
 
     for &mv in &MOVES[moves.0[moves.1.max(1) - 1] as usize] {
@@ -178,7 +189,15 @@ pub fn get_keys(mut state: Game, config: &GameConfig, target: Placement) -> Vec<
       state.piece.y = y;
       state.piece.rot = rot;
 
-      let fail = !mv.run(&mut state, config, &map, &start_state);
+      let mut next_clock = clock;
+      let fail = if timed {
+        config
+          .timing
+          .run(mv, &mut state, config, &map, &start_state, &mut next_clock)
+          != Some(true)
+      } else {
+        !mv.run(&mut state, config, &map, &start_state)
+      };
 
       if mv == Move::HardDrop {
         if state.piece.rot % 2 == tgt_2
@@ -196,7 +215,7 @@ pub fn get_keys(mut state: Game, config: &GameConfig, target: Placement) -> Vec<
             .collect();
         }
       } else {
-        if fail || moves.1 >= 64 {
+        if fail || moves.1 >= moves.0.len() {
           continue;
         }
 
@@ -204,28 +223,32 @@ pub fn get_keys(mut state: Game, config: &GameConfig, target: Placement) -> Vec<
         new_moves[moves.1] = mv;
 
         let compressed = 0u16
-          | (state.piece.x as u16 & 0b_1111)
-          | ((state.piece.y as u16 & 0b_111111) << 4)
-          | ((state.piece.rot as u16 & 0b11) << 10)
-          | ((state.spin as u16 & 0b11) << 12);
+          | (state.piece.x as u16 & 0b_11111)
+          | ((state.piece.y as u16 & 0b_111111) << 5)
+          | ((state.piece.rot as u16 & 0b11) << 11)
+          | ((state.spin as u16 & 0b11) << 13);
 
         let idx = compressed as usize / 64;
         let bit = 1 << (compressed % 64);
 
-        if fail || passed[idx] & bit != 0 {
+        if timed {
+          if !arrivals.insert(compressed, next_clock) {
+            continue;
+          }
+        } else if passed[idx] & bit != 0 {
           continue;
         }
 
         passed[idx] |= bit;
 
-        queue[back_ptr] = (
+        queue.push((
           state.piece.x,
           state.piece.y,
           state.piece.rot,
           state.spin,
           (new_moves, moves.1 + 1),
-        );
-        back_ptr += 1;
+          next_clock,
+        ));
       }
     }
   }
@@ -242,3 +265,43 @@ pub fn get_keys(mut state: Game, config: &GameConfig, target: Placement) -> Vec<
 
   panic!("No move found (tgt spin: {})", target.spin.as_str());
 }
+
+// a key press, in frames since the piece spawned
+#[derive(Clone, Copy, Debug)]
+pub struct PlannedKey {
+  pub mv: Move,
+  pub frame: f64,
+  pub duration: f64,
+}
+
+// when to press each key so the piece arrives before locking, replaying the same timing as the search
+pub fn plan_keys(mut state: Game, config: &GameConfig, keys: &[Move]) -> Vec<PlannedKey> {
+  let start_state = StartState {
+    garbage: &[],
+    queue: &[Mino::I; 32],
+  };
+
+  let timing = &config.timing;
+  let map = state.collision_map();
+  let mut clock = Clock::default();
+  timing.advance(&mut state, &map, &mut clock, 0.0, 0.0, false);
+
+  keys
+    .iter()
+    .map(|&mv| {
+      let frame = clock.frame;
+      let (x, y) = (state.piece.x, state.piece.y);
+      let moved = mv.run(&mut state, config, &map, &start_state);
+
+      let cells = x.abs_diff(state.piece.x).max(y.abs_diff(state.piece.y));
+      let duration = timing.duration(mv, cells);
+      timing.after_move(mv, moved, cells, &mut state, &map, &mut clock);
+
+      PlannedKey {
+        mv,
+        frame,
+        duration,
+      }
+    })
+    .collect()
+}
@@ -20,27 +20,230 @@ pub mod search;
 use game::{
   Game, GameConfig, Garbage, StartState,
   data::Move,
+  garbage::{GarbageGenerator, Messiness},
   queue::{Bag, Queue},
+  timing::KEY_FRAMES,
 };
-use keyfinder::get_keys;
-use search::beam_search;
+use keyfinder::{PlannedKey, get_keys, plan_keys};
+use search::{Action, Movegen, SearchLimits, Stop, bitboard, resume_search, tree::Tree};
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, thread::JoinHandle};
+use triangle::engine::queue::Mino;
 
 use crate::search::eval::Weights;
 
 pub struct StepResult {
   pub keys: Vec<Move>,
+  // when to press each key, only meaningful with gravity
+  pub plan: Vec<PlannedKey>,
+  // frames after the hard drop before the next piece spawns
+  pub entry_delay: f64,
   pub time: f64,
 }
 
-pub struct Falcon<const DEPTH: u8, const WIDTH: usize> {
+// everything needed to reproduce a position exactly
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+  pub queue: Queue<32>,
+  pub game: Game,
+  pub config: Option<GameConfig>,
+  pub generator: GarbageGenerator,
+}
+
+impl Snapshot {
+  pub fn to_json(&self) -> String {
+    serde_json::to_string_pretty(self).unwrap()
+  }
+
+  pub fn from_json(data: &str) -> serde_json::Result<Self> {
+    serde_json::from_str(data)
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    bincode::serde::encode_to_vec(self, bincode::config::standard()).unwrap()
+  }
+
+  pub fn from_bytes(data: &[u8]) -> Result<Self, bincode::error::DecodeError> {
+    bincode::serde::decode_from_slice(data, bincode::config::standard())
+      .map(|(snapshot, _)| snapshot)
+  }
+}
+
+// the tree of the last search, in the engine it ran on
+enum Retained {
+  Legacy(Tree<Game>),
+  Bitboard(Tree<game2::Game>),
+}
+
+impl Retained {
+  fn best(&self) -> Option<Action> {
+    match self {
+      Retained::Legacy(tree) => tree.best().map(|(action, _)| action),
+      Retained::Bitboard(tree) => tree.best().map(|(action, _)| action),
+    }
+  }
+
+  // keeps the subtree of the move that led to `played`, the queue moved `shift` pieces since
+  fn reroot(self, played: &Game, shift: usize) -> Option<Self> {
+    let garbage = played.garbage;
+
+    match self {
+      Retained::Legacy(tree) => {
+        let hash = played.full_hash();
+        tree
+          .reroot(
+            |game| game.rebase(shift, garbage),
+            |game| game.hash() == hash,
+          )
+          .map(Retained::Legacy)
+      }
+      Retained::Bitboard(tree) => {
+        let hash = game2::Game::from_legacy(played).hash();
+        tree
+          .reroot(
+            |game| game.rebase(shift, garbage),
+            |game| game.hash() == hash,
+          )
+          .map(Retained::Bitboard)
+      }
+    }
+  }
+
+  // keeps the states that still come out the same under `job`'s garbage
+  fn revalidate(self, job: &Job) -> Option<Self> {
+    let config = &job.config;
+    let start_state = StartState {
+      queue: &job.queue,
+      garbage: &job.garbage,
+    };
+
+    match self {
+      Retained::Legacy(tree) => {
+        let mut root = job.game.clone();
+        root.rehash();
+        tree
+          .revalidate(
+            &root,
+            |game, action, depth| search::replay(game, config, &start_state, action, depth),
+            |a, b| a.hash() == b.hash(),
+          )
+          .map(Retained::Legacy)
+      }
+      Retained::Bitboard(tree) => tree
+        .revalidate(
+          &game2::Game::from_legacy(&job.game),
+          |game, action, depth| search::replay(game, config, &start_state, action, depth),
+          |a, b| a.hash() == b.hash(),
+        )
+        .map(Retained::Bitboard),
+    }
+  }
+}
+
+// a position to search, owned so it can move to the ponder thread
+struct Job {
+  game: Game,
+  queue: [Mino; 32],
+  garbage: Vec<Garbage>,
+  config: GameConfig,
+  weights: Weights,
+  opponent_danger: f64,
+  limits: SearchLimits,
+  bitboard: bool,
+}
+
+// a tree and the garbage it was searched with, none when that isn't known exactly
+type Kept = (Retained, Option<Vec<Garbage>>);
+
+impl Job {
+  // `tree` has to be rooted at this position, one from the other engine is dropped. when it was
+  // searched with other garbage, only the states that still come out the same are kept
+  fn run(&self, tree: Option<Kept>) -> Option<Retained> {
+    let tree = tree.and_then(|(tree, searched)| match searched {
+      Some(searched) if searched == self.garbage => Some(tree),
+      _ => tree.revalidate(self),
+    });
+
+    let start_state = StartState {
+      queue: &self.queue,
+      garbage: &self.garbage,
+    };
+
+    if self.bitboard {
+      let tree = match tree {
+        Some(Retained::Bitboard(tree)) => Some(tree),
+        _ => None,
+      };
+      resume_search(
+        game2::Game::from_legacy(&self.game),
+        &self.config,
+        &start_state,
+        &self.weights,
+        self.opponent_danger,
+        &self.limits,
+        tree,
+      )
+      .map(Retained::Bitboard)
+    } else {
+      let tree = match tree {
+        Some(Retained::Legacy(tree)) => Some(tree),
+        _ => None,
+      };
+      resume_search(
+        self.game.clone(),
+        &self.config,
+        &start_state,
+        &self.weights,
+        self.opponent_danger,
+        &self.limits,
+        tree,
+      )
+      .map(Retained::Legacy)
+    }
+  }
+}
+
+// a search of the position after our move, running until the next piece is due
+struct Ponder {
+  stop: Stop,
+  garbage: Vec<Garbage>,
+  handle: Option<JoinHandle<Option<Retained>>>,
+}
+
+impl Ponder {
+  // the deepest tree it finished, along with the garbage it was searched with
+  fn finish(mut self) -> Option<Kept> {
+    self.stop.stop();
+    let tree = self.handle.take()?.join().ok().flatten()?;
+    Some((tree, Some(std::mem::take(&mut self.garbage))))
+  }
+}
+
+impl Drop for Ponder {
+  fn drop(&mut self) {
+    self.stop.stop();
+  }
+}
+
+pub struct Falcon {
   queue: Queue<32>,
   game: Game,
   config: Option<GameConfig>,
+  generator: GarbageGenerator,
   weights: Weights,
+  movegen: Movegen,
+  limits: SearchLimits,
+  // rayon's global pool when unset
+  pool: Option<Arc<rayon::ThreadPool>>,
+  // a tree rooted at the current position and the garbage it was searched with, dropped whenever
+  // the board changes in a way the search didn't plan for
+  retained: Option<Kept>,
+  ponder: Option<Ponder>,
 }
 
-impl<const DEPTH: u8, const WIDTH: usize> Falcon<DEPTH, WIDTH> {
-  pub fn new(weights: Weights) -> Self {
+impl Falcon {
+  pub fn new(weights: Weights, limits: SearchLimits) -> Self {
     let mut queue = Queue::new(Bag::Bag7, 0, Vec::new());
     let game = Game::new(queue.shift());
 
@@ -48,68 +251,256 @@ impl<const DEPTH: u8, const WIDTH: usize> Falcon<DEPTH, WIDTH> {
       queue,
       game,
       config: None,
+      generator: GarbageGenerator::new(0, Messiness::default(), game::BOARD_WIDTH as u8),
       weights,
+      movegen: Movegen::default(),
+      limits,
+      pool: None,
+      retained: None,
+      ponder: None,
     }
   }
 
+  // results only depend on the thread count through time budgets
+  pub fn set_threads(&mut self, threads: usize) {
+    self.pool = Some(Arc::new(
+      rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("failed to build the search thread pool"),
+    ));
+  }
+
+  // e.g. a time budget that follows the pace of the game
+  pub fn set_limits(&mut self, limits: SearchLimits) {
+    self.limits = limits;
+  }
+
+  pub fn set_weights(&mut self, weights: Weights) {
+    self.weights = weights;
+    self.forget();
+  }
+
+  // configs the bitboard movegen can't handle keep using the legacy one
+  pub fn set_movegen(&mut self, movegen: Movegen) {
+    self.movegen = movegen;
+  }
+
   pub fn start(&mut self, config: GameConfig, seed: u64, bag: Bag) {
     self.queue = Queue::new(bag, seed, Vec::new());
+    self.generator = GarbageGenerator::new(seed, config.messiness, config.board_width);
+    self.game = Game::with_config(self.queue.shift(), &config);
     self.config = Some(config);
-    self.game = Game::new(self.queue.shift());
+    self.forget();
   }
 
-  pub fn insert_garbage(&mut self, garbage: Vec<Garbage>) {
-    for gb in garbage {
-      self.game.board.insert_garbage(gb.amt, gb.col);
+  pub fn snapshot(&self) -> Snapshot {
+    Snapshot {
+      queue: self.queue.clone(),
+      game: self.game.clone(),
+      config: self.config.clone(),
+      generator: self.generator.clone(),
     }
   }
 
-  pub fn step(&mut self, garbage: Vec<Garbage>, opponent: &Game) -> Option<StepResult> {
+  pub fn restore(&mut self, snapshot: Snapshot) {
+    self.queue = snapshot.queue;
+    self.game = snapshot.game;
+    self.config = snapshot.config;
+    self.generator = snapshot.generator;
+    self.forget();
+  }
+
+  // tetr.io's gravity rises over the course of a game
+  pub fn set_gravity(&mut self, gravity: f64) {
+    if let Some(config) = &mut self.config
+      && config.timing.gravity != gravity
+    {
+      config.timing.gravity = gravity;
+      self.forget();
+    }
+  }
+
+  // holes of queued attacks given as (amount, time, phase), assuming they enter the board in order
+  pub fn predict_garbage(&self, attacks: &[(u16, u8, u8)]) -> Vec<Garbage> {
+    let mut generator = self.generator.clone();
+    attacks
+      .iter()
+      .flat_map(|&(amt, time, phase)| generator.generate(amt, time, phase))
+      .collect()
+  }
+
+  // `queued` are the attack amounts in the garbage queue the lines were taken from
+  pub fn insert_garbage(&mut self, garbage: Vec<Garbage>, queued: &[u16]) {
+    for gb in &garbage {
+      self.game.insert_garbage(gb.amt, gb.col);
+    }
+    self.generator.sync(queued, &garbage);
+    self.forget();
+  }
+
+  // whether the ponder thread is still searching
+  pub fn pondering(&self) -> bool {
+    self
+      .ponder
+      .as_ref()
+      .and_then(|ponder| ponder.handle.as_ref())
+      .is_some_and(|handle| !handle.is_finished())
+  }
+
+  // e.g. once the round is over, the thread winds down on its own
+  pub fn stop_pondering(&mut self) {
+    self.ponder = None;
+  }
+
+  // drops every search of a position that no longer exists
+  fn forget(&mut self) {
+    self.ponder = None;
+    self.retained = None;
+  }
+
+  fn job(&self, garbage: Vec<Garbage>, opponent: &Game, limits: SearchLimits) -> Option<Job> {
     let config = self.config.clone()?;
+    let mut game = self.game.clone();
+    game.garbage = (0, 0);
+
+    Some(Job {
+      game,
+      queue: self.queue.as_array(),
+      garbage,
+      bitboard: self.movegen == Movegen::Bitboard && bitboard::supports(&config),
+      config,
+      weights: self.weights.clone(),
+      opponent_danger: self.weights.eval_opponent(opponent),
+      limits,
+    })
+  }
+
+  // the tree of the current position, stops pondering
+  fn take_retained(&mut self) -> Option<Kept> {
+    if let Some(ponder) = self.ponder.take() {
+      self.retained = ponder.finish();
+    }
+
+    self.retained.take()
+  }
+
+  // searches the current position on another thread until the next `step` picks up its tree.
+  // new garbage restarts it, and so does garbage entering the board through `insert_garbage`
+  pub fn ponder(&mut self, garbage: Vec<Garbage>, opponent: &Game) {
+    if self
+      .ponder
+      .as_ref()
+      .is_some_and(|ponder| ponder.garbage == garbage)
+    {
+      return;
+    }
+
+    let tree = self.take_retained();
+    let stop = Stop::new();
+    let limits = SearchLimits {
+      time: None,
+      nodes: None,
+      stop: Some(stop.clone()),
+      ..self.limits.clone()
+    };
+    let Some(job) = self.job(garbage.clone(), opponent, limits) else {
+      return;
+    };
+
+    let pool = self.pool.clone();
+    let handle = std::thread::spawn(move || {
+      let run = || job.run(tree);
+      match pool {
+        Some(pool) => pool.install(run),
+        None => run(),
+      }
+    });
+
+    self.ponder = Some(Ponder {
+      stop,
+      garbage,
+      handle: Some(handle),
+    });
+  }
+
+  // errors when falcon hasn't been started or no keys reach the chosen placement
+  pub fn step(&mut self, garbage: Vec<Garbage>, opponent: &Game) -> Result<StepResult, String> {
+    let tree = self.take_retained();
+    let job = self
+      .job(garbage, opponent, self.limits.clone())
+      .ok_or_else(|| "Falcon hasn't been started".to_string())?;
+    let config = &job.config;
     self.game.garbage = (0, 0);
 
-    let queue_arr = self.queue.as_array();
     let start_state = StartState {
-      queue: &queue_arr,
-      garbage: garbage.as_slice(),
+      queue: &job.queue,
+      garbage: &job.garbage,
     };
 
     let start_time = std::time::Instant::now();
-    let choice = beam_search::<DEPTH, WIDTH>(
-      self.game.clone(),
-      &config,
-      &start_state,
-      &self.weights,
-      self.weights.eval_opponent(opponent),
-    );
+    let run = || job.run(tree);
+    let tree = match &self.pool {
+      Some(pool) => pool.install(run),
+      None => run(),
+    };
+    let choice = tree.as_ref().and_then(Retained::best);
     let elapsed = start_time.elapsed().as_secs_f64();
 
     if let Some(mv) = choice {
+      // the game only changes once the keys are known
+      let mut game = self.game.clone();
       let mut double_shift = false;
-      if mv.0.hold {
-        double_shift = self.game.hold.is_none();
-        self.game.hold(&start_state);
+      if mv.hold {
+        double_shift = game.hold.is_none();
+        game.hold(config, &start_state);
       }
 
-      let map = self.game.collision_map();
+      let mut keys = if job.bitboard {
+        bitboard::get_keys(&game, config, mv.placement)?
+      } else {
+        get_keys(game.clone(), config, mv.placement)
+      };
+      self.game = game;
+      self.generator.tick();
 
-      let mut keys = get_keys(self.game.clone(), &config, mv.0.placement);
+      let map = self.game.collision_map();
+      let mut plan = plan_keys(self.game.clone(), config, &keys);
 
       for key in keys.iter() {
-        key.run(&mut self.game, &config, &map, &start_state);
+        key.run(&mut self.game, config, &map, &start_state);
+      }
+      // game2 judged the spin when it found the keys, the tree's states were scored with it
+      if job.bitboard {
+        self.game.spin = mv.placement.spin;
       }
 
-      if mv.0.hold {
+      if mv.hold {
         keys.insert(0, Move::Hold);
+        for key in plan.iter_mut() {
+          key.frame += KEY_FRAMES;
+        }
+        plan.insert(
+          0,
+          PlannedKey {
+            mv: Move::Hold,
+            frame: 0.0,
+            duration: KEY_FRAMES,
+          },
+        );
       }
 
+      // the tree's state for the move, with the garbage it was searched with
+      let mut played = self.game.clone();
+      played.hard_drop(config, &map, &start_state, 0);
+
       println!("-------------------------");
       self.game.print();
       println!("B2B: {}", self.game.b2b);
       println!("Time: {:.0}μs", elapsed * 1_000_000.0);
 
-      self.game.hard_drop(
-        &config,
+      let (_, _, (_, cleared)) = self.game.hard_drop(
+        config,
         &map,
         &StartState {
           queue: &self.queue.as_array(),
@@ -124,18 +515,32 @@ impl<const DEPTH: u8, const WIDTH: usize> Falcon<DEPTH, WIDTH> {
       self.queue.shift();
       self.game.queue_ptr = 0;
 
-      Some(StepResult {
+      // trees searched with garbage get checked against whatever garbage the next search has
+      self.retained = tree
+        .and_then(|tree| tree.reroot(&played, 1 + double_shift as usize))
+        .map(|tree| (tree, job.garbage.is_empty().then(Vec::new)));
+
+      Ok(StepResult {
         keys,
+        plan,
+        entry_delay: config.timing.entry_delay(cleared > 0),
         time: elapsed,
       })
     } else {
+      self.generator.tick();
       let map = self.game.collision_map();
-      self.game.hard_drop(&config, &map, &start_state, 0);
+      let (_, _, (_, cleared)) = self.game.hard_drop(config, &map, &start_state, 0);
       self.queue.shift();
       self.game.queue_ptr = 0;
 
-      Some(StepResult {
+      Ok(StepResult {
         keys: vec![Move::HardDrop],
+        plan: vec![PlannedKey {
+          mv: Move::HardDrop,
+          frame: 0.0,
+          duration: 0.0,
+        }],
+        entry_delay: config.timing.entry_delay(cleared > 0),
         time: elapsed,
       })
     }
@@ -146,26 +551,41 @@ impl<const DEPTH: u8, const WIDTH: usize> Falcon<DEPTH, WIDTH> {
 pub mod tests {
 
   use super::*;
-  use game::Game;
+  use game::{
+    BOARD_HEIGHT, BOARD_WIDTH, Game, SpawnRules,
+    data::MinoData,
+    garbage::{AttackTable, GarbageEntry, Messiness, Passthrough},
+    timing::Timing,
+  };
+  use game2::data::RotationSystem;
   use triangle::{
     engine::{queue::Mino, utils::KickTable},
-    types::game::{ComboTable, SpinBonuses},
+    types::game::SpinBonuses,
   };
 
   pub fn init() -> (game::GameConfig, Queue<32>, Game) {
     let config = game::GameConfig {
       kicks: KickTable::SRSX,
+      rotation: None,
+      allow_180: true,
       spins: SpinBonuses::Handheld,
-      b2b_chaining: false,
+      attack: AttackTable::tetra_league(),
       b2b_charging: true,
       b2b_charge_at: 0,
       b2b_charge_base: 0,
       pc_b2b: 1,
-      pc_send: 5,
-      combo_table: ComboTable::Multiplier,
       garbage_multiplier: 1.0,
       garbage_cap: 8,
       garbage_special_bonus: true,
+      messiness: Messiness::default(),
+      garbage_phase: 0,
+      garbage_queue: false,
+      garbage_entry: GarbageEntry::Instant,
+      passthrough: Passthrough::Zero,
+      board_width: BOARD_WIDTH as u8,
+      board_height: BOARD_HEIGHT as u8,
+      spawn: SpawnRules::default(),
+      timing: Timing::default(),
       bag: Bag::Bag7,
     };
 
@@ -204,6 +624,1148 @@ pub mod tests {
     game.print();
   }
 
+  #[test]
+  pub fn test_tbp_pieces() {
+    use io::tbp::{Location, Orientation, Piece, TbpMove, TbpSpin};
+
+    for mino in [
+      Mino::I,
+      Mino::O,
+      Mino::T,
+      Mino::L,
+      Mino::J,
+      Mino::S,
+      Mino::Z,
+    ] {
+      assert_eq!(Piece::from_mino(mino).map(|piece| piece.mino()), Ok(mino));
+    }
+
+    // the frontend gets an error message instead of a crashed bot
+    assert!(Piece::from_mino(Mino::Garbage).is_err());
+    let placement = search::movegen::Placement {
+      x: 4,
+      y: 1,
+      rot: 0,
+      spin: triangle::types::game::Spin::None,
+    };
+    assert!(TbpMove::from_placement(Mino::Garbage, &placement).is_err());
+
+    // moves from the frontend are checked against the board before they're played
+    let at = |x: i8, y: i8| TbpMove {
+      location: Location {
+        piece: Piece::T,
+        orientation: Orientation::North,
+        x,
+        y,
+      },
+      spin: TbpSpin::None,
+    };
+    let mut board = game::Board::new();
+    let placement = at(4, 0).to_placement(&board).unwrap();
+    let mv = TbpMove::from_placement(Mino::T, &placement).unwrap();
+    assert_eq!((mv.location.x, mv.location.y), (4, 0));
+    assert!(at(4, -1).to_placement(&board).is_err());
+    assert!(at(0, 0).to_placement(&board).is_err());
+    assert!(at(127, 0).to_placement(&board).is_err());
+    assert!(at(-128, 127).to_placement(&board).is_err());
+    board.set(5, 0);
+    assert!(at(4, 0).to_placement(&board).is_err());
+    assert!(at(4, 1).to_placement(&board).is_ok());
+  }
+
+  #[test]
+  pub fn test_snapshot() {
+    let (config, queue, mut game) = init();
+    game.board.set(0, 0);
+    game.board.set(9, 39);
+    game.board.garbage = 1;
+    game.hold = Some(Mino::T);
+    game.b2b = 3;
+
+    let snapshot = Snapshot {
+      queue,
+      game,
+      generator: GarbageGenerator::new(0, config.messiness, config.board_width),
+      config: Some(config),
+    };
+
+    for restored in [
+      Snapshot::from_json(&snapshot.to_json()).unwrap(),
+      Snapshot::from_bytes(&snapshot.to_bytes()).unwrap(),
+    ] {
+      assert_eq!(restored.game.board.cols, snapshot.game.board.cols);
+      assert_eq!(restored.game.board.garbage, 1);
+      assert_eq!(restored.game.hold, Some(Mino::T));
+      assert_eq!(restored.game.b2b, 3);
+      assert_eq!(restored.queue.rng.seed, snapshot.queue.rng.seed);
+      assert_eq!(restored.queue.rng.index, snapshot.queue.rng.index);
+      assert_eq!(restored.queue.queue, snapshot.queue.queue);
+    }
+  }
+
+  #[test]
+  pub fn test_hold_rotation() {
+    let (config, queue, _) = init();
+    let arr = queue.as_array();
+    let state = StartState {
+      queue: &arr,
+      garbage: &[],
+    };
+
+    let mut game = game::Game::new(Mino::T);
+    game.hold = Some(Mino::I);
+    game.piece.rot = 1;
+
+    // swapping with the hold spawns the held piece flat, like drawing it from the queue
+    game.hold(&config, &state);
+    assert_eq!(game.piece.mino, Mino::I);
+    assert_eq!(game.piece.rot, 0);
+    assert_eq!((game.piece.x, game.piece.y), game.board.spawn(Mino::I));
+
+    game.piece.rot = 3;
+    game.hold(&config, &state);
+    assert_eq!(game.piece.mino, Mino::T);
+    assert_eq!(game.piece.rot, 0);
+  }
+
+  #[test]
+  pub fn test_attack_tables() {
+    use game::garbage::B2bBonus;
+    use triangle::types::game::{ComboTable, Spin};
+
+    let league = AttackTable::tetra_league();
+    assert_eq!(league.damage(4, Spin::None, 0, 0), 4.0);
+    assert_eq!(league.damage(2, Spin::Normal, 0, 0), 4.0);
+    assert_eq!(league.damage(2, Spin::Normal, 1, 0), 5.0);
+    assert_eq!(league.damage(3, Spin::Normal, 0, 0), 6.0);
+    assert_eq!(league.damage(2, Spin::Mini, 0, 0), 1.0);
+    assert_eq!(league.damage(4, Spin::Mini, 0, 0), 10.0);
+    assert_eq!(league.damage(2, Spin::None, 0, 1), 1.25);
+    assert_eq!(league.damage(4, Spin::None, 0, 2), 6.0);
+    // the multiplier table still sends on singles deep into a combo
+    assert_eq!(league.damage(1, Spin::None, 0, 3), (3.0f32 * 1.25).ln_1p());
+    assert_eq!(league.pc, 5);
+
+    let quick_play = AttackTable::quick_play();
+    assert_eq!(quick_play.b2b, B2bBonus::Chaining);
+    assert_eq!(quick_play.damage(4, Spin::None, 1, 0), 5.0);
+    let chained = 2.0 + (1.0 + (4.0f32 * 0.8).ln_1p().fract()) / 3.0;
+    assert_eq!(quick_play.damage(4, Spin::None, 4, 0), 4.0 + chained);
+    assert_eq!(quick_play.pc, 10);
+
+    let guideline = AttackTable::guideline();
+    assert_eq!(guideline.combo, ComboTable::ClassicGuideline);
+    assert_eq!(guideline.damage(4, Spin::None, 1, 0), 5.0);
+    assert_eq!(guideline.damage(3, Spin::Normal, 0, 0), 6.0);
+    assert_eq!(guideline.damage(4, Spin::Normal, 0, 0), 6.0);
+    assert_eq!(guideline.damage(1, Spin::Mini, 0, 0), 0.0);
+    assert_eq!(guideline.damage(2, Spin::Mini, 0, 0), 1.0);
+    assert_eq!(
+      guideline.damage(1, Spin::None, 0, 1),
+      ComboTable::ClassicGuideline.data()[0] as f32
+    );
+  }
+
+  #[test]
+  pub fn test_legacy_config() {
+    use game::garbage::B2bBonus;
+    use triangle::types::game::ComboTable;
+
+    let (config, _, _) = init();
+    let mut value = serde_json::to_value(&config).unwrap();
+    let fields = value.as_object_mut().unwrap();
+    fields.remove("attack");
+    fields.insert("b2bChaining".into(), true.into());
+    fields.insert(
+      "comboTable".into(),
+      serde_json::to_value(ComboTable::ClassicGuideline).unwrap(),
+    );
+    fields.insert("pcSend".into(), 10.into());
+
+    let legacy = GameConfig::from_json(value).unwrap();
+    assert_eq!(legacy.attack.b2b, B2bBonus::Chaining);
+    assert_eq!(legacy.attack.combo, ComboTable::ClassicGuideline);
+    assert_eq!(legacy.attack.pc, 10);
+    assert_eq!(legacy.attack.clears, AttackTable::tetra_league().clears);
+
+    // current configs go through untouched
+    let current = GameConfig::from_json(serde_json::to_value(&config).unwrap()).unwrap();
+    assert_eq!(current.attack.pc, config.attack.pc);
+  }
+
+  #[test]
+  pub fn test_bags() {
+    use game::{queue::BagState, rng::RNG};
+
+    for (bag, len) in [
+      (Bag::Bag7, 7),
+      (Bag::Bag14, 14),
+      (Bag::Classic, 1),
+      (Bag::Pairs, 6),
+      (Bag::TotalMayhem, 1),
+      (Bag::Bag7Plus1, 8),
+      (Bag::Bag7Plus2, 9),
+      (Bag::Bag7PlusX, 10),
+    ] {
+      let mut rng = RNG::new(12345);
+      let mut state = BagState::default();
+      assert_eq!(bag.next(&mut rng, &mut state).len(), len, "{:?}", bag);
+    }
+  }
+
+  // regression snapshots of what the randomizers dealt for these seeds when they were written,
+  // they weren't captured from tetr.io replays and only catch changes to the sequences
+  #[test]
+  pub fn test_bag_snapshots() {
+    let mino = |c: char| match c {
+      'Z' => Mino::Z,
+      'L' => Mino::L,
+      'O' => Mino::O,
+      'S' => Mino::S,
+      'I' => Mino::I,
+      'J' => Mino::J,
+      _ => Mino::T,
+    };
+    for (bag, seed, sequence) in [
+      (Bag::Bag7, 12345, "LOSTIJZLOZTISJOSTZJILJSOZITL"),
+      (Bag::Bag7, 1854217392, "TZSLIOJJLOSZTIJILZOST"),
+      (Bag::Bag7, 0, "ZSIOLJTOSIZJLT"),
+      (Bag::Bag14, 12345, "ZOLTSJITOJZISLSJLOIZTTOZSLJI"),
+      (Bag::Classic, 12345, "ZTZZTIJOLOTLOZOLTLLTOJILOTZT"),
+      (Bag::Pairs, 12345, "LLOOLOOOJOJJJOOJOJLIILILZSZS"),
+      (Bag::TotalMayhem, 12345, "ZJTZZZJITJOLLJTLOZLLTLTLTTOI"),
+      (Bag::Bag7Plus1, 12345, "SLOIJZZTIZTSJLOTJSOOITLZLJIZ"),
+      (Bag::Bag7Plus2, 12345, "JLIOSTZZJILSJLZOTOSZIOTJTLLO"),
+      (Bag::Bag7PlusX, 12345, "ISOTZLOSJLTJISTIZOLSZIOJJTLT"),
+    ] {
+      let mut queue = Queue::<32>::new(bag, seed, Vec::new());
+      let pieces: Vec<Mino> = sequence.chars().map(|_| queue.shift()).collect();
+      assert_eq!(
+        pieces,
+        sequence.chars().map(mino).collect::<Vec<_>>(),
+        "{:?} {}",
+        bag,
+        seed
+      );
+    }
+  }
+
+  #[test]
+  pub fn test_garbage_prediction() {
+    let (mut config, _, _) = init();
+    config.messiness = Messiness {
+      change: 0.5,
+      within: 0.3,
+      nosame: true,
+      timeout: 0,
+    };
+
+    let mut falcon = Falcon::new(
+      crate::search::eval::WEIGHTS_HANDTUNED,
+      SearchLimits::fixed(1, 1),
+    );
+    falcon.start(config.clone(), 42, Bag::Bag7);
+    let holes = |garbage: &[Garbage]| -> Vec<u8> {
+      garbage
+        .iter()
+        .flat_map(|gb| std::iter::repeat_n(gb.col, gb.amt as usize))
+        .collect()
+    };
+
+    // hole columns of seed 42 from a port of tetr.io's generator, the first attack is split
+    let expected: [&[u8]; 5] = [
+      &[0, 0, 0, 4],
+      &[9, 9, 9],
+      &[0, 0, 0, 0, 3],
+      &[2, 8],
+      &[8, 8, 8, 4, 8, 8],
+    ];
+
+    // two attacks enter at once, then one, then two again
+    let mut next = 0;
+    for queued in [&[4, 3][..], &[5], &[2, 6]] {
+      let amounts: Vec<(u16, u8, u8)> = queued.iter().map(|&amt| (amt, 0, 0)).collect();
+      let predicted = falcon.predict_garbage(&amounts);
+      assert_eq!(
+        holes(&predicted),
+        expected[next..next + queued.len()].concat()
+      );
+      next += queued.len();
+      falcon.insert_garbage(predicted, queued);
+    }
+  }
+
+  #[test]
+  pub fn test_garbage_timing() {
+    let (mut config, _, _) = init();
+    let queue = [Mino::I; 32];
+    let garbage = [Garbage {
+      col: 0,
+      amt: 2,
+      time: 0,
+      phase: 1,
+    }];
+    let state = StartState {
+      queue: &queue,
+      garbage: &garbage,
+    };
+
+    // a tetris into the left well, or a flat I that clears nothing
+    let place = |config: &GameConfig, tetris: bool, timer: u8| {
+      let mut game = game::Game::new(Mino::I);
+      for x in 1..10 {
+        for y in 0..4 {
+          game.board.set(x, y);
+        }
+      }
+      let map = game.collision_map();
+      if tetris {
+        game.rotate(1, config, &map);
+        while game.move_left(&map) {}
+      }
+      let (_, sent, _) = game.hard_drop(config, &map, &state, timer);
+      (sent, game.garbage)
+    };
+
+    assert_eq!(place(&config, false, 0).1, (1, 0));
+
+    // still in phase, so the tetris can't cancel it, and it waits for a piece that clears nothing
+    assert_eq!(place(&config, true, 0).1, (0, 0));
+    assert_eq!(place(&config, true, 1).1, (1, 0));
+    assert_eq!(place(&config, true, 1).0 + 2, place(&config, true, 0).0);
+
+    // continuous entry doesn't wait for the line clear to finish
+    config.garbage_entry = GarbageEntry::Continuous;
+    assert_eq!(place(&config, true, 0).1, (1, 0));
+
+    // delayed entry holds it back for one more piece
+    config.garbage_entry = GarbageEntry::Delayed;
+    assert_eq!(place(&config, false, 0).1, (0, 0));
+    assert_eq!(place(&config, false, 1).1, (1, 0));
+
+    // the hole never moves, until enough pieces pass without garbage
+    let messiness = Messiness {
+      change: 0.0,
+      within: 0.0,
+      nosame: false,
+      timeout: 2,
+    };
+    let mut generator = GarbageGenerator::new(3, messiness, 10);
+    assert_eq!(generator.columns(3), [0, 0, 0]);
+    generator.tick();
+    assert_eq!(generator.columns(3), [0, 0, 0]);
+    generator.tick();
+    generator.tick();
+    assert_eq!(generator.columns(3), [1, 1, 1]);
+  }
+
+  #[test]
+  pub fn test_garbage_cap() {
+    let (config, _, _) = init();
+    let queue = [Mino::I; 32];
+    let garbage = [
+      Garbage {
+        col: 0,
+        amt: 5,
+        time: 0,
+        phase: 0,
+      },
+      Garbage {
+        col: 1,
+        amt: 5,
+        time: 0,
+        phase: 0,
+      },
+    ];
+    let state = StartState {
+      queue: &queue,
+      garbage: &garbage,
+    };
+
+    // at most `garbage_cap` lines enter per piece, the rest stays queued
+    let mut game = game::Game::new(Mino::I);
+    let map = game.collision_map();
+    game.hard_drop(&config, &map, &state, 0);
+    assert_eq!(config.garbage_cap, 8);
+    assert_eq!(game.garbage, (1, 3));
+    assert_eq!(game.board.column_heights()[2], 8);
+  }
+
+  #[test]
+  pub fn test_passthrough() {
+    let (mut config, _, _) = init();
+    config.garbage_multiplier = 4.0;
+    let queue = [Mino::I; 32];
+    // one attack that already arrived, one still in transit
+    let garbage = [
+      Garbage {
+        col: 0,
+        amt: 3,
+        time: 0,
+        phase: 0,
+      },
+      Garbage {
+        col: 1,
+        amt: 10,
+        time: 2,
+        phase: 0,
+      },
+    ];
+    let state = StartState {
+      queue: &queue,
+      garbage: &garbage,
+    };
+
+    let tetris = |config: &GameConfig| {
+      let mut game = game::Game::new(Mino::I);
+      for x in 1..10 {
+        for y in 0..4 {
+          game.board.set(x, y);
+        }
+      }
+      let map = game.collision_map();
+      game.rotate(1, config, &map);
+      while game.move_left(&map) {}
+      let (_, sent, _) = game.hard_drop(config, &map, &state, 0);
+      (sent, game.garbage)
+    };
+
+    config.passthrough = Passthrough::Full;
+    let (attack, cursor) = tetris(&config);
+    assert!(attack >= 13);
+    assert_eq!(cursor, (0, 0));
+
+    config.passthrough = Passthrough::Zero;
+    assert_eq!(tetris(&config), (attack - 13, (2, 0)));
+
+    config.passthrough = Passthrough::Consistent;
+    assert_eq!(tetris(&config), (attack - 3, (1, 0)));
+
+    // attacks sent at the same time
+    assert_eq!(Passthrough::Zero.in_transit(10, 12), 10);
+    assert_eq!(Passthrough::Consistent.in_transit(10, 12), 0);
+    assert_eq!(Passthrough::Full.in_transit(10, 12), 0);
+
+    // tetr.io doesn't document how much limited passthrough lets through, so there's no model
+    assert!(serde_json::from_str::<Passthrough>("\"limited\"").is_err());
+  }
+
+  #[test]
+  pub fn test_entry_delay_garbage() {
+    let (mut config, _, _) = init();
+    let mut falcon = Falcon::new(
+      crate::search::eval::WEIGHTS_HANDTUNED,
+      SearchLimits::fixed(1, 1),
+    );
+    falcon.start(config.clone(), 0, Bag::Bag7);
+
+    // an attack that can enter in 60 frames, while placing 3 pieces a second
+    let predicted = |timing: &Timing| {
+      let time = timing.frames_to_pieces(60.0, 3.0);
+      falcon.predict_garbage(&[(4, time, 0)])[0].time
+    };
+    assert_eq!(predicted(&config.timing), 3);
+
+    // no piece spawns faster than the entry delay, so it enters a piece earlier
+    config.timing.are = 29.0;
+    assert_eq!(predicted(&config.timing), 2);
+  }
+
+  #[test]
+  pub fn test_bitboard_search() {
+    let (config, _, _) = init();
+
+    let mut falcon = Falcon::new(
+      crate::search::eval::WEIGHTS_HANDTUNED,
+      SearchLimits::fixed(3, 100),
+    );
+    falcon.set_movegen(Movegen::Bitboard);
+    falcon.start(config, 7, Bag::Bag7);
+    let opponent = game::Game::new(Mino::T);
+
+    // every chosen placement has to be reachable by the key finder
+    for _ in 0..30 {
+      let res = falcon.step(Vec::new(), &opponent).unwrap();
+      assert_eq!(res.keys.last(), Some(&Move::HardDrop));
+      assert!(!falcon.game.dead);
+    }
+
+    // rotation systems without a specialized movegen are searched key by key
+    for rotation in [RotationSystem::Ars, RotationSystem::Nrs] {
+      let config = GameConfig {
+        rotation: Some(rotation),
+        ..init().0
+      };
+      assert!(bitboard::supports(&config));
+      falcon.start(config, 7, Bag::Bag7);
+      for _ in 0..10 {
+        let res = falcon.step(Vec::new(), &opponent).unwrap();
+        assert_eq!(res.keys.last(), Some(&Move::HardDrop));
+        assert!(!falcon.game.dead);
+      }
+    }
+  }
+
+  #[test]
+  pub fn test_search_limits() {
+    let (config, queue, game) = init();
+    let start_state = StartState {
+      queue: &queue.as_array(),
+      garbage: &[],
+    };
+    let weights = crate::search::eval::WEIGHTS_HANDTUNED;
+    let run = |limits: SearchLimits| {
+      search::anytime_search(game.clone(), &config, &start_state, &weights, 0.0, &limits)
+        .map(|(action, _)| (action.hold, action.placement.x, action.placement.rot))
+    };
+
+    // without a budget it's the fixed beam search
+    let fixed = search::beam_search::<3, 50>(game.clone(), &config, &start_state, &weights, 0.0)
+      .map(|(action, _)| (action.hold, action.placement.x, action.placement.rot));
+    assert_eq!(run(SearchLimits::fixed(3, 50)), fixed);
+
+    // the first layer always finishes, so a spent budget still has an answer
+    assert!(
+      run(SearchLimits {
+        nodes: Some(1),
+        ..SearchLimits::fixed(7, 1000)
+      })
+      .is_some()
+    );
+    assert!(
+      run(SearchLimits {
+        time: Some(std::time::Duration::from_millis(20)),
+        ..SearchLimits::fixed(7, 1000)
+      })
+      .is_some()
+    );
+  }
+
+  #[test]
+  pub fn test_search_passes() {
+    use search::{
+      Action, iterate,
+      movegen::Placement,
+      tree::{Node, Tree},
+    };
+    use triangle::types::game::Spin;
+
+    let node = Node {
+      game: (),
+      parent: 0,
+      action: Action {
+        placement: Placement {
+          x: 0,
+          y: 0,
+          rot: 0,
+          spin: Spin::None,
+        },
+        hold: false,
+      },
+      score: 0.0,
+    };
+    let limits = SearchLimits {
+      nodes: Some(u64::MAX),
+      ..SearchLimits::fixed(12, 100)
+    };
+
+    // (width, depth it starts from, depth it grows to) of every pass
+    let mut passes = Vec::new();
+    let best = iterate(&limits, None, |mut tree: Tree<()>, budget| {
+      passes.push((tree.width, tree.depth(), budget.depth()));
+      while tree.depth() < budget.depth() {
+        tree.layers.push(vec![node.clone()]);
+      }
+      tree
+    });
+
+    // each pass is wider and deeper, and once it's as wide as it gets the tree keeps growing
+    assert_eq!(
+      passes,
+      [
+        (32, 0, 4),
+        (64, 0, 6),
+        (100, 0, 8),
+        (100, 8, 10),
+        (100, 10, 12)
+      ]
+    );
+    assert_eq!(best.map(|tree| (tree.width, tree.depth())), Some((100, 12)));
+
+    // without a budget there's a single pass at the limits
+    passes.clear();
+    iterate(
+      &SearchLimits::fixed(12, 100),
+      None,
+      |tree: Tree<()>, budget| {
+        passes.push((tree.width, tree.depth(), budget.depth()));
+        tree
+      },
+    );
+    assert_eq!(passes, [(100, 0, 12)]);
+  }
+
+  #[test]
+  pub fn test_beam() {
+    use game::rng::RNG;
+    use search::tree::{Beam, Rank};
+
+    // few distinct scores and states, so there are plenty of ties and transpositions
+    let mut rng = RNG::new(3);
+    let candidates: Vec<(Rank, u64)> = (0..500)
+      .map(|i| {
+        let rank = Rank {
+          score: (rng.next() % 20) as f64,
+          order: (i / 7, i % 7),
+        };
+        (rank, rng.next() % 60)
+      })
+      .collect();
+
+    // the old way, sort everything and keep the first path to each state
+    let mut sorted = candidates.clone();
+    sorted.sort_by_key(|&(rank, _)| rank);
+    let mut seen = std::collections::HashSet::new();
+    let expected: Vec<usize> = sorted
+      .iter()
+      .filter(|(_, hash)| seen.insert(*hash))
+      .take(25)
+      .map(|(rank, _)| rank.order.0 * 7 + rank.order.1)
+      .collect();
+
+    let beam = |candidates: &[(Rank, u64)]| {
+      let mut beam = Beam::new(25);
+      for &(rank, hash) in candidates {
+        beam.push(rank, rank.order.0 * 7 + rank.order.1, |_| hash);
+      }
+      beam
+    };
+    assert_eq!(beam(&candidates).into_sorted(), expected);
+
+    // however the layer is split between tasks, the merged beam is the same
+    for chunk in [1, 13, 64, 499] {
+      let merged = candidates
+        .chunks(chunk)
+        .map(beam)
+        .reduce(Beam::merge)
+        .unwrap();
+      assert_eq!(merged.into_sorted(), expected);
+    }
+  }
+
+  #[test]
+  pub fn test_parallel_search() {
+    let (config, _, _) = init();
+    let run = |threads: usize| {
+      let mut falcon = Falcon::new(
+        crate::search::eval::WEIGHTS_HANDTUNED,
+        SearchLimits::fixed(3, 50),
+      );
+      falcon.set_threads(threads);
+      falcon.start(config.clone(), 11, Bag::Bag7);
+      let opponent = game::Game::new(Mino::T);
+
+      (0..10)
+        .map(|_| falcon.step(Vec::new(), &opponent).unwrap().keys)
+        .collect::<Vec<_>>()
+    };
+
+    // ties between candidates break the same way however the layers are split
+    let single = run(1);
+    assert_eq!(run(4), single);
+    assert_eq!(run(4), run(4));
+  }
+
+  #[test]
+  pub fn test_state_hash() {
+    let (config, queue, game) = init();
+    let start_state = StartState {
+      queue: &queue.as_array(),
+      garbage: &[],
+    };
+
+    let place = |game: &mut Game, x: u8| {
+      let map = game.collision_map();
+      game.piece.x = x;
+      game.hard_drop(&config, &map, &start_state, 0);
+    };
+
+    // the same two pieces dropped in either order
+    let mut a = game.clone();
+    a.set_queue_ptr(1);
+    a.piece.mino = Mino::O;
+    place(&mut a, 1);
+    a.piece.mino = Mino::O;
+    place(&mut a, 6);
+
+    let mut b = game.clone();
+    b.set_queue_ptr(1);
+    b.piece.mino = Mino::O;
+    place(&mut b, 6);
+    b.piece.mino = Mino::O;
+    place(&mut b, 1);
+
+    assert_eq!(a.board.cols, b.board.cols);
+    assert_eq!(a.hash(), b.hash());
+
+    // the moves keep the hash up to date, edits through the fields need a rehash
+    assert_eq!(a.hash(), a.full_hash());
+    a.hold(&config, &start_state);
+    a.insert_garbage(2, 3);
+    assert_eq!(a.hash(), a.full_hash());
+
+    let mut held = b.clone();
+    held.hold = Some(Mino::T);
+    held.rehash();
+    assert_ne!(held.hash(), b.hash());
+
+    let mut b2b = b.clone();
+    b2b.b2b += 1;
+    b2b.rehash();
+    assert_ne!(b2b.hash(), b.hash());
+
+    let mut combo = b.clone();
+    combo.combo += 1;
+    combo.rehash();
+    assert_ne!(combo.hash(), b.hash());
+
+    let mut ptr = b.clone();
+    ptr.set_queue_ptr(ptr.queue_ptr + 1);
+    assert_ne!(ptr.hash(), b.hash());
+
+    // how much of the next attack was cancelled matters as much as which attack is next
+    let mut tanked = b.clone();
+    tanked.garbage = (0, 1);
+    tanked.rehash();
+    assert_ne!(tanked.hash(), b.hash());
+    let mut next = b.clone();
+    next.garbage = (1, 0);
+    next.rehash();
+    assert_ne!(next.hash(), b.hash());
+    assert_ne!(next.hash(), tanked.hash());
+    tanked.rebase(0, (0, 1));
+    assert_eq!(tanked.hash(), tanked.full_hash());
+    assert_eq!(tanked.hash(), b.hash());
+  }
+
+  #[test]
+  pub fn test_tree_reuse() {
+    let (config, queue, game) = init();
+    let start_state = StartState {
+      queue: &queue.as_array(),
+      garbage: &[],
+    };
+    let weights = crate::search::eval::WEIGHTS_HANDTUNED;
+
+    let tree = resume_search(
+      game.clone(),
+      &config,
+      &start_state,
+      &weights,
+      0.0,
+      &SearchLimits::fixed(4, 50),
+      None,
+    )
+    .unwrap();
+    assert_eq!(tree.depth(), 4);
+
+    // re-rooting on a first layer state keeps its subtree one layer shallower
+    let played = tree.layers[0][0].game.hash();
+    let rerooted = tree
+      .clone()
+      .reroot(|_| {}, |game| game.hash() == played)
+      .unwrap();
+    assert_eq!(rerooted.depth(), 3);
+    for (layer, above) in rerooted.layers.iter().skip(1).zip(&rerooted.layers) {
+      assert!(layer.iter().all(|node| node.parent < above.len()));
+    }
+    assert!(rerooted.layers[0].iter().all(|node| node.parent == 0));
+
+    let nodes = |tree: &Tree<Game>| tree.layers.iter().map(Vec::len).sum::<usize>();
+    let revalidate = |garbage: &[Garbage]| {
+      let start_state = StartState {
+        queue: start_state.queue,
+        garbage,
+      };
+      tree.clone().revalidate(
+        &game,
+        |game, action, depth| search::replay(game, &config, &start_state, action, depth),
+        |a, b| a.hash() == b.hash(),
+      )
+    };
+
+    // nothing on an empty board sends enough to cancel garbage, so garbage that can't enter
+    // within the tree's depth keeps all of it
+    let late = revalidate(&[Garbage {
+      col: 3,
+      amt: 4,
+      time: 9,
+      phase: 0,
+    }])
+    .unwrap();
+    assert_eq!(late.depth(), tree.depth());
+    assert_eq!(nodes(&late), nodes(&tree));
+
+    // garbage entering on the first piece changes every state
+    let now = revalidate(&[Garbage {
+      col: 3,
+      amt: 4,
+      time: 0,
+      phase: 0,
+    }]);
+    assert!(now.is_none());
+    assert!(tree.reroot(|_| {}, |_| false).is_none());
+
+    // reusing trees across steps still only plays placements the key finder reaches
+    for movegen in [Movegen::Legacy, Movegen::Bitboard] {
+      let mut falcon = Falcon::new(weights, SearchLimits::fixed(3, 50));
+      falcon.set_movegen(movegen);
+      falcon.start(config.clone(), 5, Bag::Bag7);
+      let opponent = game::Game::new(Mino::T);
+
+      let depth = |falcon: &Falcon| {
+        falcon.retained.as_ref().map(|(tree, _)| match tree {
+          Retained::Legacy(tree) => tree.depth(),
+          Retained::Bitboard(tree) => tree.depth(),
+        })
+      };
+
+      for _ in 0..20 {
+        let res = falcon.step(Vec::new(), &opponent).unwrap();
+        assert_eq!(res.keys.last(), Some(&Move::HardDrop));
+        // what's below the move carries over a layer shallower
+        assert!(depth(&falcon) == Some(2));
+      }
+
+      // garbage that can't be cancelled or enter within the search leaves every state as it was,
+      // so the tree carries over while it's queued too
+      let queued = vec![Garbage {
+        col: 3,
+        amt: 2,
+        time: 9,
+        phase: 9,
+      }];
+      for _ in 0..3 {
+        falcon.step(queued.clone(), &opponent).unwrap();
+        assert!(depth(&falcon) == Some(2));
+      }
+
+      // the bot reports gravity every tick, only a change drops the tree
+      let gravity = falcon.config.as_ref().unwrap().timing.gravity;
+      falcon.set_gravity(gravity);
+      assert!(depth(&falcon) == Some(2));
+      falcon.set_gravity(gravity + 0.01);
+      assert!(falcon.retained.is_none());
+
+      falcon.insert_garbage(falcon.predict_garbage(&[(2, 0, 0)]), &[2]);
+      assert!(falcon.retained.is_none());
+      falcon.step(Vec::new(), &opponent).unwrap();
+      assert!(!falcon.game.dead);
+    }
+  }
+
+  #[test]
+  pub fn test_ponder() {
+    let (config, _, _) = init();
+    let opponent = game::Game::new(Mino::T);
+    let run = |ponder: bool| {
+      let mut falcon = Falcon::new(
+        crate::search::eval::WEIGHTS_HANDTUNED,
+        SearchLimits::fixed(3, 50),
+      );
+      falcon.start(config.clone(), 3, Bag::Bag7);
+
+      (0..10)
+        .map(|_| {
+          let keys = falcon.step(Vec::new(), &opponent).unwrap().keys;
+          if ponder {
+            falcon.ponder(Vec::new(), &opponent);
+            while falcon.pondering() {
+              std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+          }
+          keys
+        })
+        .collect::<Vec<_>>()
+    };
+
+    // a finished ponder leaves the same tree the step would have grown
+    assert_eq!(run(true), run(false));
+
+    let mut falcon = Falcon::new(
+      crate::search::eval::WEIGHTS_HANDTUNED,
+      SearchLimits::fixed(3, 50),
+    );
+    falcon.start(config.clone(), 3, Bag::Bag7);
+    falcon.step(Vec::new(), &opponent).unwrap();
+
+    // garbage on the board cancels it, garbage in the queue restarts it
+    falcon.ponder(Vec::new(), &opponent);
+    falcon.insert_garbage(falcon.predict_garbage(&[(2, 0, 0)]), &[2]);
+    assert!(falcon.ponder.is_none());
+
+    let incoming = falcon.predict_garbage(&[(3, 1, 0)]);
+    falcon.ponder(Vec::new(), &opponent);
+    falcon.ponder(incoming.clone(), &opponent);
+    assert_eq!(falcon.ponder.as_ref().unwrap().garbage, incoming);
+
+    let res = falcon.step(incoming, &opponent).unwrap();
+    assert_eq!(res.keys.last(), Some(&Move::HardDrop));
+    assert!(falcon.ponder.is_none());
+  }
+
+  #[test]
+  pub fn test_weight_profiles() {
+    use crate::search::eval::{WEIGHTS_HANDTUNED, WEIGHTS_ZERO, WeightProfiles, WeightsFile};
+
+    // a plain weights file is used for every ruleset, spin clears keep the value of what they send
+    let json = serde_json::to_string(&WEIGHTS_HANDTUNED).unwrap();
+    let profiles: WeightProfiles = serde_json::from_str::<WeightsFile>(&json).unwrap().into();
+    for spins in [
+      SpinBonuses::AllMiniPlus,
+      SpinBonuses::TSpins,
+      SpinBonuses::Stupid,
+      SpinBonuses::MiniOnly,
+    ] {
+      assert_eq!(profiles.get(spins).clear, WEIGHTS_HANDTUNED.clear);
+    }
+
+    // profiles given in the file take priority
+    let profiles = WeightProfiles {
+      stupid: Some(WEIGHTS_ZERO),
+      ..WEIGHTS_HANDTUNED.into()
+    };
+    let json = serde_json::to_string(&profiles).unwrap();
+    let profiles: WeightProfiles = serde_json::from_str::<WeightsFile>(&json).unwrap().into();
+    assert_eq!(profiles.get(SpinBonuses::Stupid).clear, WEIGHTS_ZERO.clear);
+    assert_eq!(
+      profiles.get(SpinBonuses::MiniOnly).clear,
+      WEIGHTS_HANDTUNED.clear
+    );
+  }
+
+  #[test]
+  pub fn test_board_size() {
+    let (mut config, _, _) = init();
+    config.board_width = 4;
+    config.board_height = 64;
+
+    let mut queue = Queue::<32>::new(Bag::Bag7, 0, vec![Mino::I]);
+    let game = game::Game::with_size(queue.shift(), 4, 64);
+    assert_eq!((game.piece.x, game.piece.y), (3, 46));
+
+    let start_state = StartState {
+      queue: &queue.as_array(),
+      garbage: &[],
+    };
+    let (_, result) = search::beam_search::<3, 100>(
+      game,
+      &config,
+      &start_state,
+      &crate::search::eval::WEIGHTS_HANDTUNED,
+      0.0,
+    )
+    .unwrap();
+    assert!(result.board.cols[4..].iter().all(|&col| col == 0));
+
+    let restored: game::Board =
+      serde_json::from_str(&serde_json::to_string(&result.board).unwrap()).unwrap();
+    assert_eq!((restored.width, restored.height), (4, 64));
+    assert_eq!(restored.cols, result.board.cols);
+
+    // the bitboard movegen sizes its boards at runtime as well
+    assert!(bitboard::supports(&config));
+    let mut falcon = Falcon::new(
+      crate::search::eval::WEIGHTS_HANDTUNED,
+      SearchLimits::fixed(2, 20),
+    );
+    falcon.set_movegen(Movegen::Bitboard);
+    falcon.start(config, 0, Bag::Bag7);
+    let opponent = game::Game::new(Mino::T);
+    for _ in 0..5 {
+      falcon.step(Vec::new(), &opponent).unwrap();
+      assert!(falcon.game.board.cols[4..].iter().all(|&col| col == 0));
+    }
+  }
+
+  #[test]
+  pub fn test_spawn_rules() {
+    let (mut config, _, _) = init();
+    let queue = [Mino::O; 32];
+    let start_state = StartState {
+      queue: &queue,
+      garbage: &[],
+    };
+
+    // stack up to the top of the visible playfield, the O lands entirely above it
+    let mut game = game::Game::new(Mino::O);
+    for x in 0..9 {
+      for y in 0..20 {
+        game.board.set(x, y);
+      }
+    }
+
+    let mut locked = game.clone();
+    config.spawn.lock_out = true;
+    let map = locked.collision_map();
+    locked.hard_drop(&config, &map, &start_state, 0);
+    assert!(locked.dead);
+
+    // the next O overlaps the last one at spawn and gets pushed up a row
+    config.spawn.lock_out = false;
+    let map = game.collision_map();
+    game.hard_drop(&config, &map, &start_state, 0);
+    assert!(!game.dead);
+    assert_eq!(game.piece.y, 23);
+
+    game.board.set(4, 23);
+    let mut blocked = game.clone();
+    game.apply_spawn_rules(&config.spawn);
+    assert!(game.dead);
+
+    // without block-out the game goes on, but the search still drops the position
+    config.spawn.block_out = false;
+    blocked.apply_spawn_rules(&config.spawn);
+    assert!(!blocked.dead);
+    assert!(blocked.lost());
+    assert!(game2::Game::from_legacy(&blocked).lost());
+  }
+
+  #[test]
+  pub fn test_timed_movegen() {
+    let (mut config, _, _) = init();
+    let queue = [Mino::T; 32];
+    let start_state = StartState {
+      queue: &queue,
+      garbage: &[],
+    };
+
+    // a wall the piece can only get past while it is still in the air
+    let mut game = game::Game::new(Mino::T);
+    for y in 0..10 {
+      game.board.set(7, y);
+    }
+    let map = game.collision_map();
+
+    let mut passed = [0u64; 2048];
+    let mut res = [search::movegen::Placement {
+      x: 0,
+      y: 0,
+      rot: 0,
+      spin: triangle::types::game::Spin::None,
+    }; 512];
+
+    let behind_wall = |res: &[search::movegen::Placement]| {
+      res
+        .iter()
+        .any(|p| Mino::T.rot(p.rot).iter().any(|block| p.x - block.0 > 7))
+    };
+
+    let (count, _) = search::movegen::expand(
+      &mut game.clone(),
+      &config,
+      &map,
+      &start_state,
+      &mut passed,
+      &mut res,
+    );
+    assert!(behind_wall(&res[..count]));
+
+    config.timing.gravity = 20.0;
+    let (count, _) = search::movegen::expand(
+      &mut game.clone(),
+      &config,
+      &map,
+      &start_state,
+      &mut passed,
+      &mut res,
+    );
+    assert!(count > 0);
+    assert!(!behind_wall(&res[..count]));
+
+    // the keyfinder has to agree with the timed movegen
+    for placement in &res[..count] {
+      let keys = get_keys(game.clone(), &config, *placement);
+      assert_eq!(keys.last(), Some(&Move::HardDrop));
+    }
+  }
+
+  #[test]
+  pub fn test_soft_drop_tuck() {
+    let (mut config, _, _) = init();
+    config.timing.gravity = 0.02;
+    let queue = [Mino::T; 32];
+    let start_state = StartState {
+      queue: &queue,
+      garbage: &[],
+    };
+
+    // a floor 10 rows below the spawn and a roof to tuck under
+    let mut game = game::Game::new(Mino::T);
+    for x in 0..9 {
+      for y in 0..11 {
+        game.board.set(x, y);
+      }
+    }
+    for x in 0..3 {
+      game.board.set(x, 13);
+    }
+    let map = game.collision_map();
+    let spawned = game.clone();
+
+    // the soft drop takes longer than the lock delay, but the piece only touches down at its end
+    let timing = config.timing;
+    let mut clock = game::timing::Clock::default();
+    timing.advance(&mut game, &map, &mut clock, 0.0, 0.0, false);
+    let drop = timing.run(
+      Move::SoftDrop,
+      &mut game,
+      &config,
+      &map,
+      &start_state,
+      &mut clock,
+    );
+    assert_eq!(drop, Some(true));
+    assert_eq!(game.piece.y, 12);
+    assert!(clock.frame > timing.lock_delay);
+
+    let tuck = timing.run(
+      Move::Left,
+      &mut game,
+      &config,
+      &map,
+      &start_state,
+      &mut clock,
+    );
+    assert_eq!(tuck, Some(true));
+
+    let tucked = search::movegen::Placement {
+      x: game.piece.x,
+      y: game.piece.y,
+      rot: game.piece.rot,
+      spin: game.spin,
+    };
+    let keys = get_keys(spawned.clone(), &config, tucked);
+    assert!(keys.contains(&Move::SoftDrop));
+    assert_eq!(keys.last(), Some(&Move::HardDrop));
+
+    let mut passed = [0u64; 2048];
+    let mut res = [tucked; 512];
+    let (count, _) = search::movegen::expand(
+      &mut spawned.clone(),
+      &config,
+      &map,
+      &start_state,
+      &mut passed,
+      &mut res,
+    );
+    assert!(
+      res[..count]
+        .iter()
+        .any(|p| (p.x, p.y, p.rot) == (tucked.x, tucked.y, tucked.rot))
+    );
+  }
+
   #[test]
   pub fn test_game() {
     // let (config, _, _) = init();
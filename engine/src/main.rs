@@ -19,5 +19,18 @@ pub mod search;
 
 #[tokio::main]
 async fn main() {
-  io::start_server().await;
+  if std::env::args().any(|arg| arg == "--tbp") {
+    // `--time <seconds>` thinks for that long on every suggestion instead of a fixed beam
+    let limits = match std::env::args()
+      .skip_while(|arg| arg != "--time")
+      .nth(1)
+      .and_then(|time| time.parse().ok())
+    {
+      Some(time) => search::SearchLimits::timed(std::time::Duration::from_secs_f64(time)),
+      None => search::SearchLimits::fixed(7, 1000),
+    };
+    io::tbp::start_server(limits).await;
+  } else {
+    io::start_server().await;
+  }
 }
@@ -0,0 +1,183 @@
+use triangle::types::game::Spin;
+
+use crate::game::{
+  self, GameConfig, StartState,
+  data::{MinoData, Move as LegacyMove},
+};
+use crate::game2::{
+  Game, Piece,
+  data::{KickTable, Mino},
+  dispatch, keyfinder,
+  map::CollisionMap,
+  movegen,
+};
+use crate::search::eval::{MoveInfo, Weights};
+use crate::search::{Action, SearchGame, SearchLimits, anytime_search, movegen::Placement};
+
+// game2 can't account for time spent moving the piece, and only has data for some of
+// tetr.io's kick tables
+pub fn supports(config: &GameConfig) -> bool {
+  !config.timing.enabled() && config.kick_data(Mino::T).is_some()
+}
+
+// every placement of the current piece, [all, spins, minis]. kicks without a specialized movegen
+// fall back to pressing keys on the game
+fn expand_piece(game: &Game, config: &GameConfig, kicks: Option<KickTable>) -> [CollisionMap; 3] {
+  let Some(kicks) = kicks else {
+    return movegen::expand_keys(game, config);
+  };
+
+  let initial_pos = (game.piece.x as usize, game.board.real_height() as u8);
+  dispatch::expand(
+    game.piece.mino,
+    kicks,
+    config.allows_180(),
+    &game.board,
+    initial_pos,
+    config.spins,
+  )
+}
+
+// the key finder works on the legacy game, which anchors pieces at the top right of their blocks
+fn to_placement(piece: &Piece, spin: Spin) -> Placement {
+  let cells = piece.cells();
+  let blocks = triangle::engine::queue::Mino::from(piece.mino).rot(piece.rot);
+
+  Placement {
+    x: (cells.iter().map(|c| c.0).min().unwrap() + blocks.iter().map(|b| b.0).max().unwrap() as i8)
+      as u8,
+    y: (cells.iter().map(|c| c.1).min().unwrap() + blocks.iter().map(|b| b.1).max().unwrap() as i8)
+      as u8,
+    rot: piece.rot,
+    spin,
+  }
+}
+
+fn to_piece(mino: Mino, placement: &Placement) -> Piece {
+  let cells = triangle::engine::queue::Mino::from(mino)
+    .rot(placement.rot)
+    .map(|(bx, by)| (placement.x as i8 - bx as i8, placement.y as i8 - by as i8));
+  let blocks = mino.rot(placement.rot);
+
+  Piece {
+    mino,
+    x: cells.iter().map(|c| c.0).min().unwrap() - blocks.iter().map(|b| b.0).min().unwrap(),
+    y: cells.iter().map(|c| c.1).min().unwrap() - blocks.iter().map(|b| b.1).min().unwrap(),
+    rot: placement.rot,
+  }
+}
+
+// keys for the current piece of the legacy game, both engines move pieces the same way
+pub fn get_keys(
+  state: &game::Game,
+  config: &GameConfig,
+  target: Placement,
+) -> Result<Vec<LegacyMove>, String> {
+  let game = Game::from_legacy(state);
+  let piece = to_piece(game.piece.mino, &target);
+
+  keyfinder::get_keys(&game, config, piece, target.spin)
+    .map(|keys| keys.into_iter().map(LegacyMove::from).collect())
+}
+
+// same search as `search::beam_search`, with children generated from the bitboard movegen
+pub fn beam_search<const DEPTH: u8, const WIDTH: usize>(
+  root_game: Game,
+  config: &GameConfig,
+  start_state: &StartState,
+  weights: &Weights,
+  opponent_danger: f64,
+) -> Option<(Action, Game)> {
+  anytime_search(
+    root_game,
+    config,
+    start_state,
+    weights,
+    opponent_danger,
+    &SearchLimits::fixed(DEPTH, WIDTH),
+  )
+}
+
+impl SearchGame for Game {
+  // the bitboard movegen looks at the board directly
+  type Map = ();
+  // the movegen is specialized per kick table, which only gets looked up once per task
+  type Scratch = Option<KickTable>;
+
+  fn scratch(config: &GameConfig) -> Option<KickTable> {
+    config.kick_table()
+  }
+
+  #[inline(always)]
+  fn hash(&self) -> u64 {
+    Game::hash(self)
+  }
+
+  fn rehash(&mut self) {
+    Game::rehash(self);
+  }
+
+  #[inline(always)]
+  fn lost(&self) -> bool {
+    Game::lost(self)
+  }
+
+  fn hold(&mut self, config: &GameConfig, start_state: &StartState) {
+    Game::hold(self, config, start_state);
+  }
+
+  fn map(&self) {}
+
+  fn expand(
+    &self,
+    config: &GameConfig,
+    _: &(),
+    _: &StartState,
+    kicks: &mut Option<KickTable>,
+    mut f: impl FnMut(Placement, Self),
+  ) {
+    let [all, spins, minis] = expand_piece(self, config, *kicks);
+    let mino = self.piece.mino;
+
+    all.for_each_filled(mino, |rot, x, y| {
+      let mut child = self.clone();
+      child.piece = Piece {
+        mino,
+        x: x as i8,
+        y: y as i8,
+        rot,
+      };
+      child.spin = if spins[rot as usize].get(x as usize, y) {
+        Spin::Normal
+      } else if minis[rot as usize].get(x as usize, y) {
+        Spin::Mini
+      } else {
+        Spin::None
+      };
+
+      f(to_placement(&child.piece, child.spin), child);
+    });
+  }
+
+  #[inline(always)]
+  fn place(&mut self, placement: &Placement) {
+    self.piece = to_piece(self.piece.mino, placement);
+    self.spin = placement.spin;
+  }
+
+  #[inline(always)]
+  fn lock(
+    &mut self,
+    config: &GameConfig,
+    _: &(),
+    start_state: &StartState,
+    depth: u8,
+  ) -> (u16, u16, (Spin, u8)) {
+    self.hard_drop(config, start_state, depth)
+  }
+
+  #[inline(always)]
+  fn eval(&self, weights: &Weights, move_info: &MoveInfo, opponent_danger: f64) -> f64 {
+    weights.eval2(self, move_info, opponent_danger)
+  }
+}
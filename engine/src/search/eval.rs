@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
-use triangle::types::game::Spin;
+use triangle::types::game::{Spin, SpinBonuses};
 
-use crate::game::{BOARD_WIDTH, Game, HoleData};
+use crate::game::{BOARD_WIDTH, Columns, Game, HoleData};
+use crate::game2;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DangerWeights {
@@ -55,6 +56,7 @@ pub struct Weights {
   pub inner_height: f64,
   pub unevenness: f64,
 
+  // tuned on a standard width board, resampled for other widths
   pub wells: [f64; BOARD_WIDTH],
 
   pub holes: HoleData<f64>,
@@ -103,22 +105,46 @@ pub struct MoveInfo {
 }
 
 impl Weights {
+  // weight of the well column `idx`, sampled at the center of the column so both sides stay mirrored
+  #[inline(always)]
+  pub fn well(&self, idx: usize, width: usize) -> f64 {
+    self.wells[(2 * idx + 1) * BOARD_WIDTH / (2 * width)]
+  }
+
   pub fn eval(self: &Self, state: &Game, move_info: &MoveInfo, opponent_danger: f64) -> f64 {
+    self.eval_board(state.board.columns(), state.b2b, state.combo, move_info)
+  }
+
+  pub fn eval2(
+    self: &Self,
+    state: &game2::Game,
+    move_info: &MoveInfo,
+    opponent_danger: f64,
+  ) -> f64 {
+    self.eval_board(
+      Columns(&state.board.data[..state.board.width()]),
+      state.b2b,
+      state.combo,
+      move_info,
+    )
+  }
+
+  fn eval_board(&self, board: Columns, b2b: i16, combo: i16, move_info: &MoveInfo) -> f64 {
     let mut score = 0f64;
 
-    let heights = &state.board.column_heights();
-    let well = state.board.well(heights);
+    let heights = &board.column_heights();
+    let well = board.well(heights);
 
     score += match well {
-      Some(idx) => self.wells[idx],
+      Some(idx) => self.well(idx, board.width()),
       None => 0.0,
     };
 
-    let (outer, inner) = state.board.heights();
+    let (outer, inner) = board.heights();
     score += self.outer_height * outer as f64;
     score += self.inner_height * inner as f64;
 
-    score += self.unevenness * state.board.unevenness(heights, well) as f64;
+    score += self.unevenness * board.unevenness(heights, well) as f64;
 
     score += if move_info.clear.1 == 0 {
       0.0
@@ -126,14 +152,14 @@ impl Weights {
       self.clear[move_info.clear.0 as usize][move_info.clear.1 as usize - 1]
     };
 
-    score += self.holes.eval(&state.board.holes(heights));
+    score += self.holes.eval(&board.holes(heights));
 
     // TODO: waste mino type
 
     score += self.sent * move_info.sent as f64;
 
-    score += self.b2b * (state.b2b + 1) as f64;
-    score += self.combo * (state.combo + 1) as f64;
+    score += self.b2b * (b2b + 1) as f64;
+    score += self.combo * (combo + 1) as f64;
 
     let kill_time_factor = ((self.danger_prophecy - move_info.time as f64).max(0.0)
       / (self.danger_prophecy - 1.0).max(1.0))
@@ -150,6 +176,92 @@ impl Weights {
   }
 }
 
+// spin rulesets that reward different setups
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SpinProfile {
+  All,
+  TSpins,
+  Stupid,
+  Handheld,
+  MiniOnly,
+}
+
+impl From<SpinBonuses> for SpinProfile {
+  fn from(spins: SpinBonuses) -> Self {
+    match spins {
+      SpinBonuses::All | SpinBonuses::AllPlus | SpinBonuses::AllMini | SpinBonuses::AllMiniPlus => {
+        SpinProfile::All
+      }
+      // without spins nothing reads the spin rows
+      SpinBonuses::TSpins | SpinBonuses::TSpinsPlus | SpinBonuses::None => SpinProfile::TSpins,
+      SpinBonuses::Stupid => SpinProfile::Stupid,
+      SpinBonuses::Handheld => SpinProfile::Handheld,
+      SpinBonuses::MiniOnly => SpinProfile::MiniOnly,
+    }
+  }
+}
+
+/// Weights per spin ruleset, profiles left out use `all`. The clear table is keyed by spin type
+/// and every spin type sends the same damage under each ruleset, so `all` values spin clears by
+/// what they send everywhere, only how easily spins come up differs.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeightProfiles {
+  pub all: Weights,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub t_spins: Option<Weights>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub stupid: Option<Weights>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub handheld: Option<Weights>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub mini_only: Option<Weights>,
+}
+
+impl WeightProfiles {
+  pub fn get(&self, spins: SpinBonuses) -> Weights {
+    let profile = SpinProfile::from(spins);
+    let weights = match profile {
+      SpinProfile::All => Some(&self.all),
+      SpinProfile::TSpins => self.t_spins.as_ref(),
+      SpinProfile::Stupid => self.stupid.as_ref(),
+      SpinProfile::Handheld => self.handheld.as_ref(),
+      SpinProfile::MiniOnly => self.mini_only.as_ref(),
+    };
+    weights.cloned().unwrap_or_else(|| self.all.clone())
+  }
+}
+
+impl From<Weights> for WeightProfiles {
+  fn from(all: Weights) -> Self {
+    WeightProfiles {
+      all,
+      t_spins: None,
+      stupid: None,
+      handheld: None,
+      mini_only: None,
+    }
+  }
+}
+
+/// A weights file, either a single set of weights or one per spin ruleset
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum WeightsFile {
+  Profiles(WeightProfiles),
+  Single(Weights),
+}
+
+impl From<WeightsFile> for WeightProfiles {
+  fn from(file: WeightsFile) -> Self {
+    match file {
+      WeightsFile::Profiles(profiles) => profiles,
+      WeightsFile::Single(weights) => weights.into(),
+    }
+  }
+}
+
 impl Into<Vec<f64>> for Weights {
   fn into(self) -> Vec<f64> {
     let mut v = Vec::new();
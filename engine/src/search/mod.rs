@@ -1,17 +1,208 @@
 #![allow(unused_variables)]
 
-use std::{collections::HashSet, time::Instant};
+use std::{
+  collections::HashSet,
+  sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+  },
+  time::{Duration, Instant},
+};
 
 use crate::game::StartState;
-use crate::game::{BOARD_WIDTH, Game, GameConfig};
+use crate::game::{CollisionMap, Game, GameConfig};
 use crate::search::eval::MoveInfo;
 use crate::search::movegen::{Placement, expand};
 
+pub mod bitboard;
 pub mod eval;
 pub mod movegen;
+pub mod tree;
 use eval::Weights;
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use tree::{Beam, Node, Rank, Tree};
 use triangle::types::game::Spin;
 
+// how the search finds the placements of a piece
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Movegen {
+  // move by move bfs on the legacy game, works with every config
+  #[default]
+  Legacy,
+  // set based flood fill on game2, boards without gravity only
+  Bitboard,
+}
+
+// set from another thread to end a search early, e.g. pondering once the real search starts
+#[derive(Clone, Debug, Default)]
+pub struct Stop(Arc<AtomicBool>);
+
+impl Stop {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn stop(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+
+  pub fn stopped(&self) -> bool {
+    self.0.load(Ordering::Relaxed)
+  }
+}
+
+impl PartialEq for Stop {
+  fn eq(&self, other: &Self) -> bool {
+    Arc::ptr_eq(&self.0, &other.0)
+  }
+}
+
+// budget for one search, passes deepen up to `depth` and widen up to `width`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchLimits {
+  pub time: Option<Duration>,
+  pub nodes: Option<u64>,
+  pub depth: u8,
+  pub width: usize,
+  pub stop: Option<Stop>,
+}
+
+impl SearchLimits {
+  // a single pass at full width
+  pub const fn fixed(depth: u8, width: usize) -> Self {
+    Self {
+      time: None,
+      nodes: None,
+      depth,
+      width,
+      stop: None,
+    }
+  }
+
+  // the passes deepen until the time runs out, at most as far as the queue reaches
+  pub const fn timed(time: Duration) -> Self {
+    Self {
+      time: Some(time),
+      nodes: None,
+      depth: MAX_DEPTH,
+      width: 4000,
+      stop: None,
+    }
+  }
+
+  pub fn bounded(&self) -> bool {
+    self.time.is_some() || self.nodes.is_some() || self.stop.is_some()
+  }
+}
+
+pub struct Budget {
+  limits: SearchLimits,
+  start: Instant,
+  nodes: u64,
+  // how deep the current pass grows
+  depth: u8,
+}
+
+impl Budget {
+  pub fn new(limits: SearchLimits) -> Self {
+    Self {
+      depth: limits.depth,
+      limits,
+      start: Instant::now(),
+      nodes: 0,
+    }
+  }
+
+  #[inline(always)]
+  pub fn count(&mut self, nodes: u64) {
+    self.nodes += nodes;
+  }
+
+  pub fn nodes(&self) -> u64 {
+    self.nodes
+  }
+
+  pub fn depth(&self) -> u8 {
+    self.depth
+  }
+
+  pub fn limits(&self) -> &SearchLimits {
+    &self.limits
+  }
+
+  // nodes are only counted once a layer finishes, so node budgets stay deterministic
+  pub fn exhausted(&self) -> bool {
+    self.limits.nodes.is_some_and(|max| self.nodes >= max) || self.out_of_time()
+  }
+
+  // a stopped search is out of time too, the layer it was on gets dropped
+  pub fn out_of_time(&self) -> bool {
+    self.limits.stop.as_ref().is_some_and(Stop::stopped)
+      || self
+        .limits
+        .time
+        .is_some_and(|max| self.start.elapsed() >= max)
+  }
+}
+
+// the queue holds 32 pieces and a hold can use one up without placing it
+pub const MAX_DEPTH: u8 = 31;
+
+// width and depth of the first pass of a bounded search
+const INITIAL_WIDTH: usize = 32;
+const INITIAL_DEPTH: u8 = 4;
+// layers each pass adds on top of the last
+const DEPTH_STEP: u8 = 2;
+
+// grows beam trees that get wider and deeper each pass until the budget or the limits run out,
+// starting with `tree` at its own width when there is one. once the width is maxed out a pass
+// keeps growing the last tree. a wider tree only replaces the answer once it gets as deep
+pub fn iterate<G: Clone>(
+  limits: &SearchLimits,
+  tree: Option<Tree<G>>,
+  mut grow: impl FnMut(Tree<G>, &mut Budget) -> Tree<G>,
+) -> Option<Tree<G>> {
+  let mut budget = Budget::new(limits.clone());
+  let mut best: Option<Tree<G>> = None;
+
+  let mut width = match &tree {
+    Some(tree) => tree.width.min(limits.width),
+    None if limits.bounded() => limits.width.min(INITIAL_WIDTH),
+    None => limits.width,
+  };
+  if limits.bounded() {
+    let start = tree.as_ref().map_or(0, Tree::depth);
+    budget.depth = (start + DEPTH_STEP).max(INITIAL_DEPTH).min(limits.depth);
+  }
+  let mut tree = tree;
+
+  loop {
+    let grown = grow(tree.take().unwrap_or_else(|| Tree::new(width)), &mut budget);
+    let done = (width >= limits.width && budget.depth >= limits.depth) || budget.exhausted();
+    let wider = (width * 2).min(limits.width);
+
+    if !done && wider == width {
+      tree = Some(grown);
+    } else if grown.best().is_some()
+      && best
+        .as_ref()
+        .is_none_or(|best| grown.depth() >= best.depth())
+    {
+      best = Some(grown);
+    }
+
+    if done {
+      break;
+    }
+    width = wider;
+    budget.depth = budget.depth.saturating_add(DEPTH_STEP).min(limits.depth);
+  }
+
+  best
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct Action {
   pub placement: Placement,
@@ -26,18 +217,19 @@ struct SearchState {
 }
 
 pub fn search(
-  state: Game,
+  mut state: Game,
   config: &GameConfig,
   start_state: &StartState,
   max_depth: u8,
   weights: &Weights,
   opponent_danger: f64,
 ) -> Option<(Action, Game)> {
+  state.rehash();
   let mut best_result: Option<(Game, f64, Action)> = None;
 
   let mut queue: Vec<SearchState> = Vec::with_capacity(2usize.pow(19));
 
-  let mut passed: HashSet<[u64; BOARD_WIDTH]> = HashSet::with_capacity(2usize.pow(20));
+  let mut passed: HashSet<u64> = HashSet::with_capacity(2usize.pow(20));
 
   let mut expand_passed = [0u64; 2048];
   let mut expand_res = [Placement {
@@ -83,7 +275,7 @@ pub fn search(
         game_copy.spin = p.spin;
         let (attack, sent, clear) = game_copy.hard_drop(config, &map, &start_state, depth);
         nodes += 1;
-        if game_copy.topped_out(&map) {
+        if game_copy.lost() {
           game_copy = queue[ptr - 1].game.clone();
           continue;
         }
@@ -118,14 +310,14 @@ pub fn search(
         game_copy.piece.rot = p.rot;
         game_copy.spin = p.spin;
         let (attack, sent, clear) = game_copy.hard_drop(config, &map, &start_state, depth);
-        if !passed.insert(game_copy.board.cols.clone()) {
+        if !passed.insert(game_copy.hash()) {
           game_copy = queue[ptr - 1].game.clone();
           continue;
         }
 
         nodes += 1;
 
-        if game_copy.topped_out_raw() {
+        if game_copy.lost() {
           game_copy = queue[ptr - 1].game.clone();
           continue;
         }
@@ -158,159 +350,342 @@ pub fn search(
 
 // beam search 🥶
 
-use std::cmp::Reverse;
-use std::collections::BinaryHeap;
+pub fn beam_search<const DEPTH: u8, const WIDTH: usize>(
+  root_game: Game,
+  config: &GameConfig,
+  start_state: &StartState,
+  weights: &Weights,
+  opponent_danger: f64,
+) -> Option<(Action, Game)> {
+  anytime_search(
+    root_game,
+    config,
+    start_state,
+    weights,
+    opponent_danger,
+    &SearchLimits::fixed(DEPTH, WIDTH),
+  )
+}
 
-#[derive(Clone)]
-struct Candidate {
-  state: SearchState,
-  score: f64,
+// what the beam search needs from a game, so the same search runs on both engines. placements
+// are anchored the legacy way whichever engine found them, so actions mean the same thing to the
+// key finders
+pub trait SearchGame: Clone + Send + Sync {
+  // what locking a piece needs to know about the board, worked out once per parent
+  type Map: Sync;
+  // movegen state for a rayon task
+  type Scratch: Send;
+
+  fn scratch(config: &GameConfig) -> Self::Scratch;
+  fn hash(&self) -> u64;
+  fn rehash(&mut self);
+  fn lost(&self) -> bool;
+  fn hold(&mut self, config: &GameConfig, start_state: &StartState);
+  fn map(&self) -> Self::Map;
+  // calls `f` with every placement of the current piece and the state with the piece there
+  fn expand(
+    &self,
+    config: &GameConfig,
+    map: &Self::Map,
+    start_state: &StartState,
+    scratch: &mut Self::Scratch,
+    f: impl FnMut(Placement, Self),
+  );
+  // moves the current piece to `placement`
+  fn place(&mut self, placement: &Placement);
+  // returns (attack, actual sent garbage, (clear type + lines cleared))
+  fn lock(
+    &mut self,
+    config: &GameConfig,
+    map: &Self::Map,
+    start_state: &StartState,
+    depth: u8,
+  ) -> (u16, u16, (Spin, u8));
+  fn eval(&self, weights: &Weights, move_info: &MoveInfo, opponent_danger: f64) -> f64;
 }
 
-impl PartialEq for Candidate {
-  fn eq(&self, other: &Self) -> bool {
-    self.score == other.score
+impl SearchGame for Game {
+  type Map = CollisionMap;
+  // bfs buffers, boxed so the tasks of a layer can pass them along cheaply
+  type Scratch = Box<([u64; 2048], [Placement; 512])>;
+
+  fn scratch(_: &GameConfig) -> Self::Scratch {
+    Box::new((
+      [0u64; 2048],
+      [Placement {
+        x: 0,
+        y: 0,
+        rot: 0,
+        spin: Spin::None,
+      }; 512],
+    ))
   }
-}
-impl PartialOrd for Candidate {
-  fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-    Some(self.score.total_cmp(&other.score))
+
+  #[inline(always)]
+  fn hash(&self) -> u64 {
+    Game::hash(self)
   }
-}
 
-impl Eq for Candidate {}
+  fn rehash(&mut self) {
+    Game::rehash(self);
+  }
 
-impl Ord for Candidate {
-  fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-    self.partial_cmp(other).unwrap()
+  #[inline(always)]
+  fn lost(&self) -> bool {
+    Game::lost(self)
+  }
+
+  fn hold(&mut self, config: &GameConfig, start_state: &StartState) {
+    Game::hold(self, config, start_state);
+  }
+
+  fn map(&self) -> CollisionMap {
+    self.collision_map()
+  }
+
+  fn expand(
+    &self,
+    config: &GameConfig,
+    map: &CollisionMap,
+    start_state: &StartState,
+    scratch: &mut Self::Scratch,
+    mut f: impl FnMut(Placement, Self),
+  ) {
+    let (passed, res_buf) = &mut **scratch;
+    // the bfs moves the piece around, so it gets a copy
+    let mut game = self.clone();
+    let moves = expand(&mut game, config, map, start_state, passed, res_buf);
+
+    for p in &res_buf[..moves.0] {
+      let mut child = self.clone();
+      child.place(p);
+      f(*p, child);
+    }
+  }
+
+  #[inline(always)]
+  fn place(&mut self, placement: &Placement) {
+    self.piece.x = placement.x;
+    self.piece.y = placement.y;
+    self.piece.rot = placement.rot;
+    self.spin = placement.spin;
+  }
+
+  #[inline(always)]
+  fn lock(
+    &mut self,
+    config: &GameConfig,
+    map: &CollisionMap,
+    start_state: &StartState,
+    depth: u8,
+  ) -> (u16, u16, (Spin, u8)) {
+    self.hard_drop(config, map, start_state, depth)
+  }
+
+  #[inline(always)]
+  fn eval(&self, weights: &Weights, move_info: &MoveInfo, opponent_danger: f64) -> f64 {
+    weights.eval(self, move_info, opponent_danger)
   }
 }
 
-pub fn beam_search<const DEPTH: u8, const WIDTH: usize>(
-  root_game: Game,
+pub fn anytime_search<G: SearchGame>(
+  root_game: G,
   config: &GameConfig,
   start_state: &StartState,
   weights: &Weights,
   opponent_danger: f64,
-) -> Option<(Action, Game)> {
-  let init_state = SearchState {
-    game: root_game.clone(),
-    depth: 0,
-    first_move: None,
-  };
-  let init_score = weights.eval(
-    &root_game,
-    &MoveInfo {
-      clear: (Spin::None, 0),
-      sent: 0,
-      attack: 0,
-      time: 0,
-    },
+  limits: &SearchLimits,
+) -> Option<(Action, G)> {
+  resume_search(
+    root_game,
+    config,
+    start_state,
+    weights,
     opponent_danger,
-  );
+    limits,
+    None,
+  )
+  .and_then(|tree| tree.best())
+}
 
-  let mut beam: BinaryHeap<Reverse<Candidate>> = BinaryHeap::with_capacity(WIDTH);
-  beam.push(Reverse(Candidate {
-    state: init_state,
-    score: init_score,
-  }));
+// `anytime_search` that extends a tree kept from an earlier search, re-rooted on `root_game`
+pub fn resume_search<G: SearchGame>(
+  mut root_game: G,
+  config: &GameConfig,
+  start_state: &StartState,
+  weights: &Weights,
+  opponent_danger: f64,
+  limits: &SearchLimits,
+  tree: Option<Tree<G>>,
+) -> Option<Tree<G>> {
+  // roots get set up through their fields, the states below keep their hash up to date
+  root_game.rehash();
+
+  iterate(limits, tree, |tree, budget| {
+    grow(
+      tree,
+      &root_game,
+      config,
+      start_state,
+      weights,
+      opponent_danger,
+      budget,
+    )
+  })
+}
 
-  let mut passed = [0u64; 2048];
-  let mut res_buf = [Placement {
-    x: 0,
-    y: 0,
-    rot: 0,
-    spin: Spin::None,
-  }; 512];
+// adds layers below the frontier up to the depth limit, the first layer always finishes
+fn grow<G: SearchGame>(
+  mut tree: Tree<G>,
+  root_game: &G,
+  config: &GameConfig,
+  start_state: &StartState,
+  weights: &Weights,
+  opponent_danger: f64,
+  budget: &mut Budget,
+) -> Tree<G> {
+  for depth in tree.depth()..budget.depth() {
+    if depth > 0 && budget.exhausted() {
+      break;
+    }
 
-  for depth in 0..DEPTH {
-    let mut next_beam: BinaryHeap<Reverse<Candidate>> = BinaryHeap::with_capacity(WIDTH);
+    let parents: Vec<&G> = match tree.layers.last() {
+      Some(layer) => layer.iter().map(|node| &node.game).collect(),
+      None => vec![root_game],
+    };
+
+    let expanded = parents
+      .par_iter()
+      .enumerate()
+      .try_fold(
+        || (Beam::new(tree.width), 0, G::scratch(config)),
+        |(mut beam, nodes, mut scratch), (parent, game)| {
+          if depth > 0 && budget.out_of_time() {
+            return None;
+          }
+          let expanded = children(
+            (parent, *game),
+            config,
+            start_state,
+            (weights, opponent_danger),
+            depth,
+            &mut scratch,
+            &mut beam,
+          );
+          Some((beam, nodes + expanded, scratch))
+        },
+      )
+      .map(|task| task.map(|(beam, nodes, _)| (beam, nodes)))
+      .try_reduce(
+        || (Beam::new(tree.width), 0),
+        |(a, a_nodes), (b, b_nodes)| Some((a.merge(b), a_nodes + b_nodes)),
+      );
+
+    // a layer cut short by the clock isn't comparable to a finished one
+    let Some((beam, nodes)) = expanded else {
+      break;
+    };
+    budget.count(nodes);
 
-    while let Some(Reverse(cand)) = beam.pop() {
-      for n in 0..=1 {
-        let mut game_copy = cand.state.game.clone();
+    let layer = beam.into_sorted();
+    if layer.is_empty() {
+      break;
+    }
+    tree.layers.push(layer);
+  }
 
-        if n == 1 {
-          game_copy.hold(&start_state);
-        }
+  tree
+}
+
+// `action` played from `game` the way the search plays it at `depth`, none when it loses
+pub fn replay<G: SearchGame>(
+  game: &G,
+  config: &GameConfig,
+  start_state: &StartState,
+  action: Action,
+  depth: u8,
+) -> Option<G> {
+  let mut game = game.clone();
+  if action.hold {
+    game.hold(config, start_state);
+    if game.lost() {
+      return None;
+    }
+  }
 
-        let map = game_copy.collision_map();
+  let map = game.map();
+  game.place(&action.placement);
+  game.lock(config, &map, start_state, depth);
 
-        let moves = expand(
-          &mut game_copy,
-          config,
-          &map,
-          &start_state,
-          &mut passed,
-          &mut res_buf,
-        );
+  (!game.lost()).then_some(game)
+}
 
-        for i in 0..moves.0 {
-          let p = res_buf[i];
-          let mut g2 = game_copy.clone();
-          g2.piece.x = p.x;
-          g2.piece.y = p.y;
-          g2.piece.rot = p.rot;
-          g2.spin = p.spin;
+// offers the surviving placements of both the current and the held piece to `beam`, returns the
+// nodes evaluated
+fn children<G: SearchGame>(
+  (parent, game): (usize, &G),
+  config: &GameConfig,
+  start_state: &StartState,
+  (weights, opponent_danger): (&Weights, f64),
+  depth: u8,
+  scratch: &mut G::Scratch,
+  beam: &mut Beam<Node<G>>,
+) -> u64 {
+  let mut nodes = 0;
+  let mut order = 0;
+
+  for n in 0..=1 {
+    let mut game_copy = game.clone();
+
+    if n == 1 {
+      game_copy.hold(config, start_state);
+      if game_copy.lost() {
+        continue;
+      }
+    }
 
-          let (attack, sent, clear) = g2.hard_drop(config, &map, &start_state, depth);
+    let map = game_copy.map();
 
-          if g2.topped_out_raw() {
-            continue;
-          }
+    game_copy.expand(config, &map, start_state, scratch, |p, mut g2| {
+      let (attack, sent, clear) = g2.lock(config, &map, start_state, depth);
+      nodes += 1;
 
-          let next_depth = cand.state.depth + 1;
-          let next_first = cand.state.first_move.or(Some(Action {
-            placement: p,
-            hold: n == 1,
-          }));
-
-          let next_state = SearchState {
-            game: g2.clone(),
-            depth: next_depth,
-            first_move: next_first,
-          };
-
-          let score = weights.eval(
-            &g2,
-            &MoveInfo {
-              clear,
-              sent,
-              attack,
-              time: depth,
-            },
-            opponent_danger,
-          );
-          let candidate = Candidate {
-            state: next_state,
-            score,
-          };
-
-          if next_beam.len() < WIDTH {
-            next_beam.push(Reverse(candidate));
-          } else if let Some(Reverse(worst)) = next_beam.peek() {
-            if score > worst.score {
-              next_beam.pop();
-              next_beam.push(Reverse(candidate));
-            }
-          }
-        }
+      if g2.lost() {
+        return;
       }
-    }
 
-    if next_beam.is_empty() {
-      break;
-    }
-    beam = next_beam;
+      let score = g2.eval(
+        weights,
+        &MoveInfo {
+          clear,
+          sent,
+          attack,
+          time: depth,
+        },
+        opponent_danger,
+      );
+
+      let rank = Rank {
+        score,
+        order: (parent, order),
+      };
+      order += 1;
+
+      beam.push(
+        rank,
+        Node {
+          game: g2,
+          parent,
+          action: Action {
+            placement: p,
+            hold: n == 1,
+          },
+          score,
+        },
+        |node| node.game.hash(),
+      );
+    });
   }
 
-  beam
-    .into_iter()
-    .map(|rev| rev.0)
-    .max_by(|a, b| {
-      a.score
-        .partial_cmp(&b.score)
-        .unwrap_or(std::cmp::Ordering::Equal)
-    })
-    .and_then(|cand| cand.state.first_move.map(|m| (m, cand.state.game)))
+  nodes
 }
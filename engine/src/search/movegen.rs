@@ -1,6 +1,10 @@
 use triangle::{engine::queue::Mino, types::game::Spin};
 
-use crate::game::{CollisionMap, Game, GameConfig, StartState, data::Move};
+use crate::game::{
+  CollisionMap, Game, GameConfig, StartState,
+  data::Move,
+  timing::{Arrivals, Clock, Timing},
+};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Placement {
@@ -76,6 +80,109 @@ const MOVES: [[Move; 6]; 7] = [
   ],
 ];
 
+// das matters once time is limited, so the timed search also tries it
+const TIMED_MOVES: [[Move; 8]; 9] = [
+  // None
+  [
+    Move::CW,
+    Move::CCW,
+    Move::Flip,
+    Move::Left,
+    Move::Right,
+    Move::SoftDrop,
+    Move::DasLeft,
+    Move::DasRight,
+  ],
+  // Left
+  [
+    Move::CW,
+    Move::CCW,
+    Move::Flip,
+    Move::Left,
+    Move::SoftDrop,
+    Move::DasRight,
+    Move::None,
+    Move::None,
+  ],
+  // Right
+  [
+    Move::CW,
+    Move::CCW,
+    Move::Flip,
+    Move::Right,
+    Move::SoftDrop,
+    Move::DasLeft,
+    Move::None,
+    Move::None,
+  ],
+  // Softdrop
+  [
+    Move::CW,
+    Move::CCW,
+    Move::Flip,
+    Move::Left,
+    Move::Right,
+    Move::DasLeft,
+    Move::DasRight,
+    Move::None,
+  ],
+  // CCW
+  [
+    Move::CCW,
+    Move::Flip,
+    Move::Left,
+    Move::Right,
+    Move::SoftDrop,
+    Move::DasLeft,
+    Move::DasRight,
+    Move::None,
+  ],
+  // CW
+  [
+    Move::CW,
+    Move::Flip,
+    Move::Left,
+    Move::Right,
+    Move::SoftDrop,
+    Move::DasLeft,
+    Move::DasRight,
+    Move::None,
+  ],
+  // Flip
+  [
+    Move::CW,
+    Move::CCW,
+    Move::Left,
+    Move::Right,
+    Move::SoftDrop,
+    Move::DasLeft,
+    Move::DasRight,
+    Move::None,
+  ],
+  // DasLeft
+  [
+    Move::CW,
+    Move::CCW,
+    Move::Flip,
+    Move::Right,
+    Move::SoftDrop,
+    Move::DasRight,
+    Move::None,
+    Move::None,
+  ],
+  // DasRight
+  [
+    Move::CW,
+    Move::CCW,
+    Move::Flip,
+    Move::Left,
+    Move::SoftDrop,
+    Move::DasLeft,
+    Move::None,
+    Move::None,
+  ],
+];
+
 pub fn expand(
   mut state: &mut Game,
   config: &GameConfig,
@@ -84,6 +191,10 @@ pub fn expand(
   passed: &mut [u64; 2048],
   res: &mut [Placement; 512],
 ) -> (usize, u64) {
+  if config.timing.enabled() {
+    return expand_timed(state, config, &config.timing, map, start_state, passed, res);
+  }
+
   passed.iter_mut().for_each(|m| *m = 0);
 
   let mut queue = [(0, 0, 0, Spin::None, Move::None); 1024];
@@ -129,11 +240,12 @@ pub fn expand(
 
       let fail = !mv.run(&mut state, config, &map, start_state);
 
+      // x spans up to 2 columns past the widest board
       let mut compressed =
-        0u16 | (state.piece.x as u16 & 0b_1111) | ((state.piece.y as u16 & 0b_111111) << 4);
+        0u16 | (state.piece.x as u16 & 0b_11111) | ((state.piece.y as u16 & 0b_111111) << 5);
 
       if state.piece.mino != Mino::O {
-        compressed |= ((state.piece.rot as u16 & 0b11) << 10) | ((state.spin as u16 & 0b11) << 12);
+        compressed |= ((state.piece.rot as u16 & 0b11) << 11) | ((state.spin as u16 & 0b11) << 13);
       }
 
       let idx = compressed as usize / 64;
@@ -171,6 +283,97 @@ pub fn expand(
   (res_ptr, nodes)
 }
 
+// only returns placements reachable before the piece locks under gravity
+pub fn expand_timed(
+  state: &mut Game,
+  config: &GameConfig,
+  timing: &Timing,
+  map: &CollisionMap,
+  start_state: &StartState,
+  passed: &mut [u64; 2048],
+  res: &mut [Placement; 512],
+) -> (usize, u64) {
+  passed.iter_mut().for_each(|m| *m = 0);
+  // a later arrival at the same spot may have more time left than the first one
+  let mut visited = Arrivals::default();
+
+  let mut res_ptr = 0;
+  let mut nodes = 0u64;
+
+  // the piece starts falling as soon as it spawns
+  let mut clock = Clock::default();
+  state.spin = Spin::None;
+  timing.advance(state, map, &mut clock, 0.0, 0.0, false);
+
+  let mut queue = vec![(
+    state.piece.x,
+    state.piece.y,
+    state.piece.rot,
+    Spin::None,
+    Move::None,
+    clock,
+  )];
+  let mut front_ptr = 0;
+
+  while front_ptr < queue.len() {
+    let (x, y, rot, spin, prev_mv, clock) = queue[front_ptr];
+    front_ptr += 1;
+
+    for &mv in &TIMED_MOVES[prev_mv as usize] {
+      nodes += 1;
+      if mv == Move::None {
+        break;
+      }
+
+      state.piece.x = x;
+      state.piece.y = y;
+      state.piece.rot = rot;
+      state.spin = spin;
+
+      let mut next_clock = clock;
+      let result = timing.run(mv, state, config, map, start_state, &mut next_clock);
+
+      let mut compressed =
+        0u16 | (state.piece.x as u16 & 0b_11111) | ((state.piece.y as u16 & 0b_111111) << 5);
+
+      if state.piece.mino != Mino::O {
+        compressed |= ((state.piece.rot as u16 & 0b11) << 11) | ((state.spin as u16 & 0b11) << 13);
+      }
+
+      let idx = compressed as usize / 64;
+      let bit = 1 << (compressed % 64);
+
+      // a piece that locks while soft dropping still ends up in the same spot
+      if mv == Move::SoftDrop && passed[1024 + idx] & bit == 0 {
+        passed[1024 + idx] |= bit;
+        res[res_ptr] = Placement {
+          x: state.piece.x,
+          y: state.piece.y,
+          rot: state.piece.rot,
+          spin: state.spin,
+        };
+
+        res_ptr += 1;
+      }
+
+      if result != Some(true) || !visited.insert(compressed, next_clock) {
+        continue;
+      }
+
+      queue.push((
+        state.piece.x,
+        state.piece.y,
+        state.piece.rot,
+        state.spin,
+        mv,
+        next_clock,
+      ));
+    }
+  }
+
+  (res_ptr, nodes)
+}
+
 // pub fn expand_floodfill(
 //   mut state: &mut Game,
 //   config: &GameConfig,
@@ -0,0 +1,288 @@
+use std::{
+  cmp::Ordering,
+  collections::{BinaryHeap, HashMap},
+};
+
+use crate::search::Action;
+
+#[derive(Clone)]
+pub struct Node<G> {
+  pub game: G,
+  // index in the layer above, the root for the first layer
+  pub parent: usize,
+  // the placement that led here from the parent
+  pub action: Action,
+  pub score: f64,
+}
+
+// the beam of every depth searched so far, each layer best first
+#[derive(Clone)]
+pub struct Tree<G> {
+  pub layers: Vec<Vec<Node<G>>>,
+  pub width: usize,
+}
+
+impl<G: Clone> Tree<G> {
+  pub fn new(width: usize) -> Self {
+    Self {
+      layers: Vec::new(),
+      width,
+    }
+  }
+
+  pub fn depth(&self) -> u8 {
+    self.layers.len() as u8
+  }
+
+  // the first move towards the best state of the deepest layer, and that state
+  pub fn best(&self) -> Option<(Action, G)> {
+    let leaf = self.layers.last()?.first()?;
+
+    let mut node = leaf;
+    for layer in self.layers.iter().rev().skip(1) {
+      node = &layer[node.parent];
+    }
+
+    Some((node.action, leaf.game.clone()))
+  }
+
+  // keeps what's below the first layer node `played` accepts, which becomes the new root.
+  // `adjust` moves states over to the new root, e.g. shifting their queue pointer
+  pub fn reroot(self, mut adjust: impl FnMut(&mut G), played: impl Fn(&G) -> bool) -> Option<Self> {
+    let width = self.width;
+    let mut layers = self.layers.into_iter();
+
+    let mut first = layers.next()?;
+    let root = first.iter().position(|node| played(&node.game))?;
+    first.iter_mut().for_each(|node| adjust(&mut node.game));
+
+    // where each node of the layer above ended up
+    let mut kept: Vec<Option<usize>> = (0..first.len()).map(|i| (i == root).then_some(0)).collect();
+    let mut new_layers = Vec::new();
+
+    for layer in layers {
+      let mut next_kept = Vec::with_capacity(layer.len());
+      let mut new_layer = Vec::new();
+
+      for mut node in layer {
+        match kept[node.parent] {
+          Some(parent) => {
+            next_kept.push(Some(new_layer.len()));
+            node.parent = parent;
+            adjust(&mut node.game);
+            new_layer.push(node);
+          }
+          None => next_kept.push(None),
+        }
+      }
+
+      if new_layer.is_empty() {
+        break;
+      }
+      kept = next_kept;
+      new_layers.push(new_layer);
+    }
+
+    Some(Self {
+      layers: new_layers,
+      width,
+    })
+  }
+
+  // plays every node again from `root` and keeps the ones `same` says still end up where they
+  // were searched, along with what's below them. `play` gets the depth the node was searched at,
+  // e.g. for garbage that changed since
+  pub fn revalidate(
+    self,
+    root: &G,
+    play: impl Fn(&G, Action, u8) -> Option<G>,
+    same: impl Fn(&G, &G) -> bool,
+  ) -> Option<Self> {
+    let width = self.width;
+    let mut kept = vec![Some(0)];
+    let mut new_layers: Vec<Vec<Node<G>>> = Vec::new();
+
+    for (depth, layer) in self.layers.into_iter().enumerate() {
+      let mut next_kept = Vec::with_capacity(layer.len());
+      let mut new_layer = Vec::new();
+
+      for mut node in layer {
+        let parent = kept[node.parent].filter(|&parent| {
+          let game = new_layers.last().map_or(root, |layer| &layer[parent].game);
+          play(game, node.action, depth as u8).is_some_and(|game| same(&game, &node.game))
+        });
+
+        match parent {
+          Some(parent) => {
+            next_kept.push(Some(new_layer.len()));
+            node.parent = parent;
+            new_layer.push(node);
+          }
+          None => next_kept.push(None),
+        }
+      }
+
+      if new_layer.is_empty() {
+        break;
+      }
+      kept = next_kept;
+      new_layers.push(new_layer);
+    }
+
+    (!new_layers.is_empty()).then_some(Self {
+      layers: new_layers,
+      width,
+    })
+  }
+}
+
+// where a candidate ranks within its layer, best first: the higher score, then whatever came
+// first in beam order. no two candidates share an order, so the ranking is total
+#[derive(Copy, Clone, Debug)]
+pub struct Rank {
+  pub score: f64,
+  // the parent's index, then the order the parent generated it in
+  pub order: (usize, usize),
+}
+
+impl Ord for Rank {
+  fn cmp(&self, other: &Self) -> Ordering {
+    other
+      .score
+      .total_cmp(&self.score)
+      .then(self.order.cmp(&other.order))
+  }
+}
+
+impl PartialOrd for Rank {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl PartialEq for Rank {
+  fn eq(&self, other: &Self) -> bool {
+    self.cmp(other).is_eq()
+  }
+}
+
+impl Eq for Rank {}
+
+struct Entry<T> {
+  rank: Rank,
+  hash: u64,
+  item: T,
+}
+
+impl<T> Ord for Entry<T> {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.rank.cmp(&other.rank)
+  }
+}
+
+impl<T> PartialOrd for Entry<T> {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl<T> PartialEq for Entry<T> {
+  fn eq(&self, other: &Self) -> bool {
+    self.rank == other.rank
+  }
+}
+
+impl<T> Eq for Entry<T> {}
+
+// the best `width` distinct candidates seen so far. states with the same hash only keep their best
+// path. the ranking is total, so merging beams gives the same layer however it was split up
+pub struct Beam<T> {
+  width: usize,
+  // worst on top. a path that got replaced by a better one to the same state stays in here until
+  // it surfaces or the heap gets compacted
+  heap: BinaryHeap<Entry<T>>,
+  // the rank of the path each state keeps
+  live: HashMap<u64, Rank>,
+}
+
+impl<T> Beam<T> {
+  pub fn new(width: usize) -> Self {
+    Self {
+      width,
+      heap: BinaryHeap::with_capacity(width + 1),
+      live: HashMap::with_capacity(width + 1),
+    }
+  }
+
+  // `hash` only runs for candidates that would make the cut
+  pub fn push(&mut self, rank: Rank, item: T, hash: impl FnOnce(&T) -> u64) {
+    if self.live.len() >= self.width && self.heap.peek().is_none_or(|worst| rank >= worst.rank) {
+      return;
+    }
+
+    let hash = hash(&item);
+    self.insert(Entry { rank, hash, item });
+  }
+
+  pub fn merge(self, other: Self) -> Self {
+    let (mut into, from) = if self.heap.len() >= other.heap.len() {
+      (self, other)
+    } else {
+      (other, self)
+    };
+
+    for entry in from.heap {
+      if is_live(&from.live, &entry) {
+        into.insert(entry);
+      }
+    }
+    into
+  }
+
+  // best first
+  pub fn into_sorted(self) -> Vec<T> {
+    let live = self.live;
+    self
+      .heap
+      .into_sorted_vec()
+      .into_iter()
+      .filter(|entry| is_live(&live, entry))
+      .map(|entry| entry.item)
+      .collect()
+  }
+
+  fn insert(&mut self, entry: Entry<T>) {
+    if self
+      .live
+      .get(&entry.hash)
+      .is_some_and(|kept| *kept <= entry.rank)
+    {
+      return;
+    }
+    self.live.insert(entry.hash, entry.rank);
+    self.heap.push(entry);
+
+    if self.heap.len() > 2 * self.width {
+      let live = &self.live;
+      self.heap.retain(|entry| is_live(live, entry));
+    }
+
+    // the worst entry on top has to be live, and there's only room for `width` of them
+    while let Some(top) = self.heap.peek() {
+      let live = is_live(&self.live, top);
+      if live && self.live.len() <= self.width {
+        break;
+      }
+      let top = self.heap.pop().unwrap();
+      if live {
+        self.live.remove(&top.hash);
+      }
+    }
+  }
+}
+
+fn is_live<T>(live: &HashMap<u64, Rank>, entry: &Entry<T>) -> bool {
+  live
+    .get(&entry.hash)
+    .is_some_and(|rank| rank.order == entry.rank.order)
+}
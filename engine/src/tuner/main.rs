@@ -3,13 +3,15 @@ pub mod sim;
 pub mod spsa;
 
 use engine::{
-  game::{GameConfig, queue::Bag},
+  game::{
+    BOARD_HEIGHT, BOARD_WIDTH, GameConfig, SpawnRules,
+    garbage::{AttackTable, GarbageEntry, Messiness, Passthrough},
+    queue::Bag,
+    timing::Timing,
+  },
   search::eval::Weights,
 };
-use triangle::{
-  engine::utils::KickTable,
-  types::game::{ComboTable, SpinBonuses},
-};
+use triangle::{engine::utils::KickTable, types::game::SpinBonuses};
 
 fn load_checkpoint() -> Option<Weights> {
   let json = std::fs::read_to_string("tuning/weights_checkpoint.json").ok()?;
@@ -22,6 +24,20 @@ fn main() {
     .windows(2)
     .find(|w| w[0] == "--continue")
     .and_then(|w| w[1].parse::<usize>().ok());
+  let attack = args
+    .windows(2)
+    .find(|w| w[0] == "--attack")
+    .map(|w| AttackTable::preset(&w[1]).expect("unknown --attack preset"))
+    .unwrap_or_else(AttackTable::tetra_league);
+  // tunes the profile of another spin ruleset, named as in room configs
+  let spins = args
+    .windows(2)
+    .find(|w| w[0] == "--spins")
+    .map(|w| {
+      serde_json::from_value(serde_json::Value::String(w[1].clone()))
+        .expect("unknown --spins ruleset")
+    })
+    .unwrap_or(SpinBonuses::AllMiniPlus);
 
   let (start_iter, initial) = if let Some(x) = continue_iter {
     let weights = load_checkpoint()
@@ -34,17 +50,26 @@ fn main() {
 
   let config = GameConfig {
     kicks: KickTable::SRSPlus,
-    spins: SpinBonuses::AllMiniPlus,
-    b2b_chaining: false,
+    rotation: None,
+    allow_180: true,
+    spins,
+    attack,
     b2b_charging: true,
     b2b_charge_at: 4,
     b2b_charge_base: 3,
     pc_b2b: 1,
-    pc_send: 5,
-    combo_table: ComboTable::Multiplier,
     garbage_multiplier: 1.0,
     garbage_cap: 8,
     garbage_special_bonus: true,
+    messiness: Messiness::default(),
+    garbage_phase: 0,
+    garbage_queue: false,
+    garbage_entry: GarbageEntry::Instant,
+    passthrough: Passthrough::Zero,
+    board_width: BOARD_WIDTH as u8,
+    board_height: BOARD_HEIGHT as u8,
+    spawn: SpawnRules::default(),
+    timing: Timing::default(),
     bag: Bag::Bag7,
   };
 
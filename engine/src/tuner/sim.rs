@@ -1,5 +1,7 @@
 use engine::{
-  game::{BOARD_WIDTH, Game, GameConfig, Garbage, StartState, queue::Queue, rng::RNG},
+  game::{
+    Game, GameConfig, Garbage, StartState, garbage::GarbageGenerator, queue::Queue, rng::RNG,
+  },
   search::{Action, beam_search, eval::Weights},
 };
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
@@ -12,7 +14,7 @@ fn apply_move(
 ) -> (u16, u16, Vec<Garbage>, bool) {
   let double_shift = mv.hold && game.hold.is_none();
   if mv.hold {
-    game.hold(state);
+    game.hold(config, state);
   }
   let map = game.collision_map();
   game.piece.x = mv.placement.x;
@@ -34,6 +36,12 @@ fn apply_move(
     garbage.remove(0);
   }
 
+  // queued garbage is one piece closer to entering and to leaving its phase
+  for g in garbage.iter_mut() {
+    g.time = g.time.saturating_sub(1);
+    g.phase = g.phase.saturating_sub(1);
+  }
+
   (attack, sent, garbage, double_shift)
 }
 
@@ -42,6 +50,7 @@ struct Player {
   queue: Queue<32>,
   game: Game,
   garbage: Vec<Garbage>,
+  generator: GarbageGenerator,
   rng: RNG,
   sent_total: u32,
 }
@@ -63,9 +72,10 @@ pub fn run_match<const DEPTH: u8, const WIDTH: usize>(
         } else {
           weights_b.clone()
         },
-        game: Game::new(queue.shift()),
+        game: Game::with_config(queue.shift(), config),
         queue,
         garbage: Vec::new(),
+        generator: GarbageGenerator::new(seed, config.messiness, config.board_width),
         rng: RNG::new(seed),
         sent_total: 0,
       }
@@ -103,12 +113,13 @@ pub fn run_match<const DEPTH: u8, const WIDTH: usize>(
           &state,
         );
 
-        if player.game.topped_out_raw() {
+        if player.game.dead {
           return (false, i, 0);
         }
 
         player.sent_total += attack as u32;
         player.garbage = garbage;
+        player.generator.tick();
 
         if double_shift {
           player.queue.shift();
@@ -121,14 +132,21 @@ pub fn run_match<const DEPTH: u8, const WIDTH: usize>(
       })
       .collect();
 
+    // both players move at once, so this turn's attacks are in transit to each other
+    let cancelled = config
+      .passthrough
+      .in_transit(results[0].2, results[1].2);
+
     for &(_, i, sent) in &results {
+      let sent = sent - cancelled;
       if sent > 0 {
         let opponent = &mut players[1 - i];
-        opponent.garbage.push(Garbage {
-          amt: (sent as f32 * config.garbage_multiplier).floor() as u16,
-          col: (opponent.rng.next_float() * BOARD_WIDTH as f64) as u8,
-          time: 2,
-        });
+        let garbage = opponent.generator.generate(
+          (sent as f32 * config.garbage_multiplier).floor() as u16,
+          2,
+          config.garbage_phase,
+        );
+        opponent.garbage.extend(garbage);
       }
     }
 
@@ -178,9 +196,14 @@ pub fn run_solo<const DEPTH: u8, const WIDTH: usize>(
     let mut queue = Queue::new(config.bag, seed, vec![]);
     Player {
       weights: weights.clone(),
-      game: Game::new(queue.shift()),
+      game: Game::with_config(queue.shift(), config),
       queue,
       garbage: Vec::new(),
+      generator: GarbageGenerator::new(
+        seed ^ 0x9e3779b97f4a7c15,
+        config.messiness,
+        config.board_width,
+      ),
       rng: RNG::new(seed ^ 0x9e3779b97f4a7c15),
       sent_total: 0,
     }
@@ -205,7 +228,7 @@ pub fn run_solo<const DEPTH: u8, const WIDTH: usize>(
       &state,
     );
 
-    if player.game.topped_out_raw() {
+    if player.game.dead {
       return i as f64 + player.sent_total as f64 + attack as f64;
     }
 
@@ -218,24 +241,19 @@ pub fn run_solo<const DEPTH: u8, const WIDTH: usize>(
     player.game.queue_ptr = 0;
     player.game.garbage = (0, 0);
 
-    player.garbage = garbage
-      .into_iter()
-      .map(|mut g| {
-        g.time = g.time.saturating_sub(1);
-        g
-      })
-      .collect();
+    player.garbage = garbage;
+    player.generator.tick();
 
     if i % garbage_frequency == 0 {
-      player.garbage.push(Garbage {
-        amt: (player.rng.next_float() * 8.0 + 1.0).floor() as u16,
-        col: (player.rng.next_float() * BOARD_WIDTH as f64) as u8,
-        time: if player.rng.next_float() < 1.0 / 3.0 {
-          1
-        } else {
-          0
-        }, // average 60 frames/piece and 20 frames of garbage delay so 1/3 chance of time 1, otherwise time 0
-      });
+      let amt = (player.rng.next_float() * 8.0 + 1.0).floor() as u16;
+      // average 60 frames/piece and 20 frames of garbage delay so 1/3 chance of time 1, otherwise time 0
+      let time = if player.rng.next_float() < 1.0 / 3.0 {
+        1
+      } else {
+        0
+      };
+      let garbage = player.generator.generate(amt, time, config.garbage_phase);
+      player.garbage.extend(garbage);
     }
   }
 
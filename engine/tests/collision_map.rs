@@ -3,22 +3,20 @@
 use engine::game2::{board::Board, data::Mino, map::CollisionMap};
 
 fn raw_collision_map(&board: &Board, mino: Mino) -> CollisionMap {
-  let mut cmap = CollisionMap::blank();
+  let mut cmap = CollisionMap::blank_for(&board);
   // very slow version
   for rot in 0..mino.real_permutations() {
-    for x in 0..Board::WIDTH as i8 {
-      // cmap[rot][x as usize] |= (((1 << (u64::BITS - (&Board::HEIGHT - 5))) - 1) << (&Board::HEIGHT - 5));
-      for y in 0..Board::HEIGHT as i8 {
+    for x in 0..board.width() as i8 {
+      for y in 0..board.height() as i8 {
         let blocks = mino.rot(rot as u8);
         if blocks
           .iter()
           .map(|block| (block.0 + x, block.1 + y))
           .all(|(x, y)| {
             x >= 0
-              && (x as usize) < Board::WIDTH
+              && (x as usize) < board.width()
               && y >= 0
-              && (y as u32) < (&Board::HEIGHT + mino.data().w as u32)
-              && board[x as usize] & (1 << y) == 0
+              && ((y as u32) >= board.height() || board[x as usize] & (1 << y) == 0)
           })
         {
           cmap[rot][x as usize] |= 1 << y;
@@ -33,14 +31,13 @@ fn raw_collision_map(&board: &Board, mino: Mino) -> CollisionMap {
 fn test_piece<const PIECE: Mino>(&board: &Board) {
   let mut a = raw_collision_map(&board, PIECE);
   let mut b = CollisionMap::usable::<PIECE>(&board);
-  let mask = (1 << Board::HEIGHT) - 1;
+  let mut mask = board.empty();
+  for x in 0..board.width() {
+    mask[x] = board.height_mask();
+  }
   for rot in 0..4 {
-    a[rot] &= Board {
-      data: [mask; Board::WIDTH],
-    };
-		b[rot] &= Board {
-      data: [mask; Board::WIDTH],
-    };
+    a[rot] &= mask;
+		b[rot] &= mask;
   }
 
 	assert_eq!(a, b);
@@ -138,4 +135,22 @@ fn collision_map() {
   a.print::<{ Mino::O }>();
 
 	test_piece::<{ Mino::O }>(&board);
+
+  // narrower and taller boards wall off the columns past their width
+  for (width, height) in [(4, 64), (7, 30), (16, 50)] {
+    let mut board = Board::with_size(width, height);
+    for x in (0..width).step_by(2) {
+      board.set(x, 0);
+      board.set(x, (x % 3) as u8 + 1);
+    }
+    board.set(width - 1, height as u8 - 1);
+
+    test_piece::<{ Mino::I }>(&board);
+    test_piece::<{ Mino::J }>(&board);
+    test_piece::<{ Mino::L }>(&board);
+    test_piece::<{ Mino::O }>(&board);
+    test_piece::<{ Mino::S }>(&board);
+    test_piece::<{ Mino::T }>(&board);
+    test_piece::<{ Mino::Z }>(&board);
+  }
 }
@@ -0,0 +1,122 @@
+use engine::game::{
+  Board, Game,
+  fumen::{self, Page},
+};
+use triangle::engine::queue::Mino;
+
+#[test]
+fn empty_field() {
+  assert_eq!(Board::new().to_fumen(), "v115@vhAAgH");
+  assert_eq!(
+    Board::from_fumen("v115@vhAAgH").unwrap().cols,
+    Board::new().cols
+  );
+}
+
+#[test]
+fn locked_piece() {
+  let pages = fumen::decode("v115@vhAVQJ").unwrap();
+  assert_eq!(pages.len(), 1);
+
+  let piece = pages[0].piece.unwrap();
+  assert_eq!(piece.mino, Mino::T);
+  assert_eq!(piece.rot, 0);
+  assert_eq!(piece.srs_position(), (4, 0));
+
+  assert_eq!(fumen::encode(&pages).unwrap(), "v115@vhAVQJ");
+}
+
+// fumen keeps the old centers for vertical s and z, as written by tetris-fumen
+#[test]
+fn vertical_s_z() {
+  for (data, mino, rot) in [("v115@vhAvLJ", Mino::S, 1), ("v115@vhA8KJ", Mino::Z, 3)] {
+    let pages = fumen::decode(data).unwrap();
+    let piece = pages[0].piece.unwrap();
+    assert_eq!(piece.mino, mino);
+    assert_eq!(piece.rot, rot);
+    assert_eq!(piece.srs_position(), (4, 1));
+
+    assert_eq!(fumen::encode(&pages).unwrap(), data);
+  }
+}
+
+#[test]
+fn round_trip() {
+  let mut board = Board::new();
+  for x in 0..9 {
+    board.set(x, 0);
+    board.set(x, 1);
+  }
+  board.set(3, 2);
+
+  let mut game = Game::new(Mino::T);
+  game.board = board;
+  game.hold = Some(Mino::I);
+
+  let mut pages = vec![Page::from_game(&game), Page::new(board)];
+  pages[1].comment = Some("downstack 100%".to_string());
+  let decoded = fumen::decode(&fumen::encode(&pages).unwrap()).unwrap();
+
+  assert_eq!(decoded.len(), 2);
+  assert_eq!(decoded[0].board.cols, board.cols);
+  assert_eq!(decoded[1].comment.as_deref(), Some("downstack 100%"));
+
+  let loaded =
+    Game::from_fumen(&Game::to_fumen(&[(game, vec![Mino::S, Mino::Z])]).unwrap()).unwrap();
+  assert_eq!(loaded.len(), 1);
+  let (loaded, queue) = &loaded[0];
+  assert_eq!(loaded.board.cols, board.cols);
+  assert_eq!(loaded.piece.mino, Mino::T);
+  assert_eq!(loaded.hold, Some(Mino::I));
+  assert_eq!(queue, &vec![Mino::S, Mino::Z]);
+}
+
+#[test]
+fn game_sequence() {
+  let mut first = Game::new(Mino::T);
+  first.hold = Some(Mino::I);
+  let mut second = Game::new(Mino::S);
+  second.board.set(0, 0);
+  second.hold = Some(Mino::I);
+  let mut third = second.clone();
+  third.piece.mino = Mino::Z;
+  third.board.set(1, 0);
+
+  let sequence = vec![
+    (first, vec![Mino::S, Mino::Z, Mino::O]),
+    (second, vec![Mino::Z, Mino::O]),
+    (third, vec![Mino::O]),
+  ];
+  let loaded = Game::from_fumen(&Game::to_fumen(&sequence).unwrap()).unwrap();
+
+  assert_eq!(loaded.len(), sequence.len());
+  for ((game, queue), (expected, expected_queue)) in loaded.iter().zip(sequence.iter()) {
+    assert_eq!(game.board.cols, expected.board.cols);
+    assert_eq!(game.piece.mino, expected.piece.mino);
+    assert_eq!(game.hold, expected.hold);
+    assert_eq!(queue, expected_queue);
+  }
+}
+
+// engine states can hold cells that aren't pieces, those are errors rather than panics
+#[test]
+fn invalid_pieces() {
+  let mut game = Game::new(Mino::T);
+  game.hold = Some(Mino::Garbage);
+  assert_eq!(
+    Game::to_fumen(&[(game.clone(), Vec::new())]),
+    Err(fumen::FumenError::InvalidPiece)
+  );
+
+  game.hold = None;
+  assert_eq!(
+    Game::to_fumen(&[(game.clone(), vec![Mino::Garbage])]),
+    Err(fumen::FumenError::InvalidPiece)
+  );
+
+  game.piece.mino = Mino::Garbage;
+  assert_eq!(
+    fumen::encode(&[Page::from_game(&game)]),
+    Err(fumen::FumenError::InvalidPiece)
+  );
+}
@@ -0,0 +1,776 @@
+use engine::{
+  game::{
+    self, BOARD_HEIGHT, BOARD_WIDTH, GameConfig, Garbage, SpawnRules, StartState,
+    data::Move,
+    garbage::{AttackTable, GarbageEntry, Messiness, Passthrough},
+    queue::{Bag, Queue},
+    timing::Timing,
+  },
+  game2::{
+    self, Piece,
+    data::{CustomKicks, KickTable as KickTable2, Mino as Mino2, Move as Move2, RotationSystem},
+    dispatch, keyfinder,
+  },
+  search::{
+    eval::{MoveInfo, WEIGHTS_HANDTUNED},
+    movegen::{Placement, expand},
+  },
+};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use std::collections::BTreeMap;
+use triangle::{
+  engine::utils::KickTable,
+  types::game::{Spin, SpinBonuses},
+};
+
+const MOVES: [(Move, Move2); 10] = [
+  (Move::Left, Move2::Left),
+  (Move::Right, Move2::Right),
+  (Move::SoftDrop, Move2::SoftDrop),
+  (Move::CCW, Move2::CCW),
+  (Move::CW, Move2::CW),
+  (Move::Flip, Move2::Flip),
+  (Move::DasLeft, Move2::DasLeft),
+  (Move::DasRight, Move2::DasRight),
+  (Move::Hold, Move2::Hold),
+  (Move::SoftDrop, Move2::SoftDrop),
+];
+
+fn config(kicks: KickTable, spins: SpinBonuses) -> GameConfig {
+  GameConfig {
+    kicks,
+    rotation: None,
+    allow_180: true,
+    spins,
+    attack: AttackTable::tetra_league(),
+    b2b_charging: true,
+    b2b_charge_at: 4,
+    b2b_charge_base: 3,
+    pc_b2b: 2,
+    garbage_multiplier: 1.0,
+    garbage_cap: 8,
+    garbage_special_bonus: true,
+    messiness: Messiness::default(),
+    garbage_phase: 0,
+    garbage_queue: false,
+    garbage_entry: GarbageEntry::Instant,
+    passthrough: Passthrough::Zero,
+    board_width: BOARD_WIDTH as u8,
+    board_height: BOARD_HEIGHT as u8,
+    spawn: SpawnRules::default(),
+    timing: Timing::default(),
+    bag: Bag::Bag7,
+  }
+}
+
+fn assert_same(a: &game::Game, b: &game2::Game, context: &str) {
+  let mut cells_a = a
+    .piece
+    .blocks()
+    .map(|(bx, by)| (a.piece.x as i8 - bx as i8, a.piece.y as i8 - by as i8));
+  let mut cells_b = b.piece.cells();
+  cells_a.sort();
+  cells_b.sort();
+
+  assert_eq!(cells_a, cells_b, "piece: {}", context);
+  assert_eq!(a.board.cols, b.board.data, "board: {}", context);
+  assert_eq!(
+    a.board.garbage, b.garbage_level,
+    "garbage level: {}",
+    context
+  );
+  assert!(
+    a.spin == b.spin,
+    "spin: {} vs {}, {}",
+    a.spin.as_str(),
+    b.spin.as_str(),
+    context
+  );
+  assert_eq!((a.b2b, a.combo), (b.b2b, b.combo), "b2b/combo: {}", context);
+  assert_eq!(
+    a.hold.map(game2::data::Mino::from),
+    b.hold,
+    "hold: {}",
+    context
+  );
+  assert_eq!(
+    (a.queue_ptr, a.garbage, a.dead),
+    (b.queue_ptr, b.garbage, b.dead),
+    "state: {}",
+    context
+  );
+}
+
+// the hashes the moves kept up to date match ones worked out from scratch
+fn assert_hashed(a: &game::Game, b: &game2::Game, context: &str) {
+  assert_eq!(a.hash(), a.full_hash(), "legacy hash: {}", context);
+  assert_eq!(b.hash(), b.full_hash(), "hash: {}", context);
+}
+
+fn play(seed: u64, config: &GameConfig) {
+  let mut rng = StdRng::seed_from_u64(seed);
+  let mut queue = Queue::<32>::new(Bag::Bag7, seed, Vec::new());
+
+  let mut a = game::Game::with_config(queue.shift(), config);
+  let mut b = game2::Game::from_legacy(&a);
+  assert_eq!(
+    (a.board.width(), a.board.height()),
+    (b.board.width(), b.board.height() as usize)
+  );
+
+  // a messy stack makes spins, clears and garbage cancels likely
+  for _ in 0..rng.random_range(0..8) {
+    let (amt, col) = (
+      rng.random_range(1..3),
+      rng.random_range(0..config.board_width),
+    );
+    a.insert_garbage(amt, col);
+    b.insert_garbage(amt, col);
+  }
+  assert_hashed(&a, &b, &format!("seed {} garbage", seed));
+
+  for piece in 0..200 {
+    let queue_arr = queue.as_array();
+    let garbage: Vec<Garbage> = (0..rng.random_range(0..3))
+      .map(|_| Garbage {
+        col: rng.random_range(0..config.board_width),
+        amt: rng.random_range(1..5),
+        time: rng.random_range(0..2),
+        phase: rng.random_range(0..2),
+      })
+      .collect();
+    let start_state = StartState {
+      queue: &queue_arr,
+      garbage: &garbage,
+    };
+
+    a.garbage = (0, 0);
+    b.garbage = (0, 0);
+    a.rehash();
+    b.rehash();
+    assert_same(&a, &b, &format!("seed {} piece {} spawn", seed, piece));
+
+    for i in 0..rng.random_range(0..10) {
+      let (mv, mv2) = MOVES[rng.random_range(0..MOVES.len())];
+      let map = a.collision_map();
+      let ok = mv.run(&mut a, config, &map, &start_state);
+      let ok2 = mv2.run(&mut b, config, &start_state);
+
+      let context = format!("seed {} piece {} move {} ({})", seed, piece, i, mv.str());
+      assert_eq!(ok, ok2, "result: {}", context);
+      assert_same(&a, &b, &context);
+      assert_hashed(&a, &b, &context);
+    }
+
+    let timer = rng.random_range(0..3);
+    let map = a.collision_map();
+    let res = a.hard_drop(config, &map, &start_state, timer);
+    let res2 = b.hard_drop(config, &start_state, timer);
+
+    let context = format!("seed {} piece {} hard drop", seed, piece);
+    assert_eq!(
+      (res.0, res.1, res.2.1),
+      (res2.0, res2.1, res2.2.1),
+      "{}",
+      context
+    );
+    assert!(res.2.0 == res2.2.0, "clear type: {}", context);
+    assert_same(&a, &b, &context);
+    assert_hashed(&a, &b, &context);
+
+    // the bitboard search evaluates game2 boards directly
+    let info = MoveInfo {
+      clear: res.2,
+      sent: res.1,
+      attack: res.0,
+      time: timer,
+    };
+    assert_eq!(
+      WEIGHTS_HANDTUNED.eval(&a, &info, 0.0),
+      WEIGHTS_HANDTUNED.eval2(&b, &info, 0.0),
+      "eval: {}",
+      context
+    );
+
+    for _ in 0..a.queue_ptr {
+      queue.shift();
+    }
+    a.set_queue_ptr(0);
+    b.set_queue_ptr(0);
+    assert_hashed(&a, &b, &format!("seed {} piece {} shift", seed, piece));
+
+    if a.dead {
+      break;
+    }
+  }
+}
+
+#[test]
+fn matches_legacy_game() {
+  let all_spins = [
+    SpinBonuses::None,
+    SpinBonuses::Stupid,
+    SpinBonuses::TSpins,
+    SpinBonuses::TSpinsPlus,
+    SpinBonuses::All,
+    SpinBonuses::AllMini,
+    SpinBonuses::AllPlus,
+    SpinBonuses::AllMiniPlus,
+    SpinBonuses::MiniOnly,
+    SpinBonuses::Handheld,
+  ];
+
+  for kicks in [KickTable::SRS, KickTable::SRSPlus, KickTable::SRSX] {
+    for (i, &spins) in all_spins.iter().enumerate() {
+      let config = config(kicks, spins);
+      for seed in 0..8 {
+        play(seed * all_spins.len() as u64 + i as u64, &config);
+      }
+    }
+  }
+
+  for spins in [SpinBonuses::TSpins, SpinBonuses::AllMini] {
+    let config = GameConfig {
+      rotation: Some(RotationSystem::Custom(custom_kicks())),
+      ..config(KickTable::SRS, spins)
+    };
+    for seed in 0..8 {
+      play(1000 + seed, &config);
+    }
+  }
+
+  for (i, rotation) in [RotationSystem::Ars, RotationSystem::Nrs]
+    .into_iter()
+    .enumerate()
+  {
+    for spins in [SpinBonuses::TSpins, SpinBonuses::AllMini] {
+      let config = GameConfig {
+        rotation: Some(rotation.clone()),
+        ..config(KickTable::SRS, spins)
+      };
+      for seed in 0..8 {
+        play(3000 + i as u64 * 8 + seed, &config);
+      }
+    }
+  }
+
+  for (i, (width, height)) in [(4, 64), (7, 30), (16, 50)].into_iter().enumerate() {
+    let config = GameConfig {
+      board_width: width,
+      board_height: height,
+      ..config(KickTable::SRSPlus, SpinBonuses::AllMini)
+    };
+    for seed in 0..8 {
+      play(2000 + i as u64 * 8 + seed, &config);
+    }
+  }
+}
+
+// starts some transitions with an offset, and allows 180s through a single transition
+fn custom_kicks() -> CustomKicks {
+  serde_json::from_str(
+    r#"{
+      "standard": {
+        "01": [[1, 0], [0, 0], [-1, -1]],
+        "12": [[0, 0], [0, 2]],
+        "30": [[0, 0], [-1, 0], [-1, 1], [1, -2]],
+        "02": [[0, 0], [1, 0]]
+      },
+      "i": {
+        "01": [[0, 0], [-2, 0], [1, 0]],
+        "23": [[0, 1]]
+      }
+    }"#,
+  )
+  .unwrap()
+}
+
+#[test]
+fn custom_kicks_from_json() {
+  let custom = custom_kicks();
+  let json = serde_json::to_string(&custom).unwrap();
+  assert_eq!(serde_json::from_str::<CustomKicks>(&json).unwrap(), custom);
+
+  let rotation = RotationSystem::Custom(custom);
+  assert!(rotation.allows_180());
+  let custom_config = GameConfig {
+    rotation: Some(rotation.clone()),
+    ..config(KickTable::SRS, SpinBonuses::TSpins)
+  };
+  // the bitboard movegen has no specialization for custom tables
+  assert_eq!(custom_config.kick_table(), None);
+
+  let data = rotation.legacy_data(Mino2::T);
+  assert_eq!(data.real_size, 4);
+  assert_eq!(data.data(Mino2::T, 0, 1)[..3], [(1, 0), (0, 0), (-1, -1)]);
+  // padded with the last kick
+  assert_eq!(data.data(Mino2::T, 0, 1)[3], (-1, -1));
+  // missing transitions rotate in place
+  assert_eq!(data.data(Mino2::T, 2, 3)[0], (0, 0));
+  assert_eq!(data.data(Mino2::I, 2, 3)[0], (0, 1));
+
+  let no_180: CustomKicks = serde_json::from_str(r#"{"standard": {"01": [[0, 0]]}}"#).unwrap();
+  assert!(!RotationSystem::Custom(no_180).allows_180());
+  // i defaults to the standard kicks
+  let standard_only: RotationSystem =
+    serde_json::from_str(r#"{"custom": {"standard": {"01": [[0, 0], [-1, 0]]}}}"#).unwrap();
+  assert_eq!(
+    standard_only.legacy_data(Mino2::I).data(Mino2::I, 0, 1)[1],
+    (-1, 0)
+  );
+
+  for invalid in [
+    r#"{"standard": {"00": [[0, 0]]}}"#,
+    r#"{"standard": {"14": [[0, 0]]}}"#,
+    r#"{"standard": {"01": []}}"#,
+    r#"{"standard": {"01": [[0, 0], [0, 0], [0, 0], [0, 0], [0, 0], [0, 0], [0, 0], [0, 0], [0, 0], [0, 0], [0, 0], [0, 0]]}}"#,
+  ] {
+    assert!(
+      serde_json::from_str::<CustomKicks>(invalid).is_err(),
+      "{}",
+      invalid
+    );
+  }
+}
+
+#[test]
+fn rotation_systems_without_180() {
+  let no_180: CustomKicks = serde_json::from_str(r#"{"standard": {"01": [[0, 0]]}}"#).unwrap();
+  let config = GameConfig {
+    rotation: Some(RotationSystem::Custom(no_180)),
+    ..config(KickTable::SRS, SpinBonuses::TSpins)
+  };
+  let mut game = game2::Game::new(Mino2::T);
+  assert_eq!(game.rotate(2, &config), (false, false));
+  assert_eq!(game.rotate(1, &config), (true, false));
+
+  // the room can forbid 180s for kicks that have them
+  let config = GameConfig {
+    allow_180: false,
+    ..config(KickTable::SRSPlus, SpinBonuses::TSpins)
+  };
+  let mut game = game2::Game::new(Mino2::T);
+  assert_eq!(game.rotate(2, &config), (false, false));
+
+  // upside down on the floor takes two rotations instead
+  let target = Piece {
+    y: 1,
+    rot: 2,
+    ..game.piece
+  };
+  let keys = keyfinder::get_keys(&game, &config, target, Spin::None).unwrap();
+  assert!(!keys.contains(&Move2::Flip));
+  assert_keys(&game, &config, target, Spin::None);
+}
+
+#[test]
+fn custom_kicks_skip_srs_spin_upgrade() {
+  // the only 2 -> 1 test is the kick that turns a mini into a full t-spin under srs
+  let kicks: CustomKicks = serde_json::from_str(r#"{"standard": {"21": [[-1, -2]]}}"#).unwrap();
+  let config = GameConfig {
+    rotation: Some(RotationSystem::Custom(kicks)),
+    ..config(KickTable::SRS, SpinBonuses::TSpins)
+  };
+
+  // three corners around (4, 5) with only one on the pointing side
+  let mut game = game2::Game::new(Mino2::T);
+  for (x, y) in [(3, 6), (3, 4), (5, 4)] {
+    game.board.set(x, y);
+  }
+  let start = Piece {
+    mino: Mino2::T,
+    x: 5,
+    y: 3,
+    rot: 2,
+  };
+  game.piece = start;
+
+  assert_eq!(game.rotate(3, &config), (true, false));
+  assert_eq!((game.piece.x, game.piece.y, game.piece.rot), (4, 5, 1));
+  assert_eq!(game.spin_for(true, config.spins), Spin::Normal);
+  assert!(game.spin == Spin::Mini);
+
+  // the legacy game skips the upgrade as well
+  let mut legacy = to_legacy(&game, start);
+  let map = legacy.collision_map();
+  assert_eq!(legacy.rotate(3, &config, &map), (true, false));
+  assert_same(&legacy, &game, "custom kicks");
+}
+
+// the legacy game on the same board with `piece` falling, it anchors pieces at the top right of
+// their blocks
+fn to_legacy(game: &game2::Game, piece: Piece) -> game::Game {
+  let mut legacy = game::Game::new(piece.mino.into());
+  legacy.board.cols = game.board.data;
+  legacy.piece.rot = piece.rot;
+  let cells = piece.cells();
+  let blocks = legacy.piece.blocks();
+  legacy.piece.x = (cells.iter().map(|c| c.0).min().unwrap()
+    + blocks.iter().map(|b| b.0).max().unwrap() as i8) as u8;
+  legacy.piece.y = (cells.iter().map(|c| c.1).min().unwrap()
+    + blocks.iter().map(|b| b.1).max().unwrap() as i8) as u8;
+  legacy
+}
+
+fn sorted_cells(piece: &Piece) -> [(i8, i8); 4] {
+  let mut cells = piece.cells();
+  cells.sort();
+  cells
+}
+
+#[test]
+fn rotation_systems_from_json() {
+  for (json, rotation) in [
+    (r#""ars""#, RotationSystem::Ars),
+    (r#""nrs""#, RotationSystem::Nrs),
+  ] {
+    assert_eq!(
+      serde_json::from_str::<RotationSystem>(json).unwrap(),
+      rotation
+    );
+    assert!(!rotation.allows_180());
+  }
+}
+
+#[test]
+fn ars_rotation_states() {
+  let config = GameConfig {
+    rotation: Some(RotationSystem::Ars),
+    ..config(KickTable::SRS, SpinBonuses::TSpins)
+  };
+  let queue = [triangle::engine::queue::Mino::I; 32];
+  let start_state = StartState {
+    queue: &queue,
+    garbage: &[],
+  };
+
+  // t spawns flat side up, then turns inside its box
+  let mut game = game2::Game::new(Mino2::T);
+  game.set_falling(Mino2::T, &config);
+  assert_eq!(game.piece.rot, 2);
+  for cells in [
+    [(3, 21), (4, 20), (4, 21), (4, 22)],
+    // pointing up it sits on the bottom of the box, one row lower
+    [(3, 20), (4, 20), (4, 21), (5, 20)],
+    [(4, 20), (4, 21), (4, 22), (5, 21)],
+    [(3, 21), (4, 20), (4, 21), (5, 21)],
+  ] {
+    assert_eq!(game.rotate(1, &config), (true, false));
+    assert_eq!(sorted_cells(&game.piece), cells);
+  }
+  assert_eq!(game.rotate(2, &config), (false, false));
+
+  // the i piece only has two states, vertical in the third column of its box
+  game.set_falling(Mino2::I, &config);
+  let spawn = sorted_cells(&game.piece);
+  assert_eq!(spawn, [(3, 21), (4, 21), (5, 21), (6, 21)]);
+  assert_eq!(game.rotate(1, &config), (true, false));
+  assert_eq!(
+    sorted_cells(&game.piece),
+    [(5, 19), (5, 20), (5, 21), (5, 22)]
+  );
+  assert_eq!(game.rotate(1, &config), (true, false));
+  assert_eq!(sorted_cells(&game.piece), spawn);
+
+  // both games spawn the same way out of hold
+  let mut legacy = game::Game::with_config(triangle::engine::queue::Mino::J, &config);
+  let mut game = game2::Game::from_legacy(&legacy);
+  assert_eq!(game.piece.rot, 2);
+  legacy.hold(&config, &start_state);
+  game.hold(&config, &start_state);
+  legacy.hold(&config, &start_state);
+  game.hold(&config, &start_state);
+  assert_eq!(game.piece.rot, 2);
+  assert_same(&legacy, &game, "ars hold");
+}
+
+#[test]
+fn ars_center_column_rule() {
+  let config = GameConfig {
+    rotation: Some(RotationSystem::Ars),
+    ..config(KickTable::SRS, SpinBonuses::TSpins)
+  };
+  let mut game = game2::Game::new(Mino2::T);
+  game.set_falling(Mino2::T, &config);
+  let spawn = game.piece;
+
+  // blocked above the center first, so it doesn't kick right even though that fits
+  game.board.set(4, 22);
+  assert_eq!(game.rotate(1, &config), (false, false));
+  assert_eq!(game.piece, spawn);
+  let mut legacy = to_legacy(&game, spawn);
+  let map = legacy.collision_map();
+  assert_eq!(legacy.rotate(1, &config, &map), (false, false));
+  assert_same(&legacy, &game, "center column");
+
+  // pointing left to pointing up, blocked first on the left, so it kicks right
+  let mut game = game2::Game::new(Mino2::T);
+  let start = Piece { rot: 3, ..spawn };
+  game.piece = start;
+  game.board.set(3, 20);
+  assert_eq!(game.rotate(1, &config), (true, true));
+  assert_eq!(
+    sorted_cells(&game.piece),
+    [(4, 20), (5, 20), (5, 21), (6, 20)]
+  );
+  let mut legacy = to_legacy(&game, start);
+  let map = legacy.collision_map();
+  assert_eq!(legacy.rotate(1, &config, &map), (true, true));
+  assert_same(&legacy, &game, "side column");
+}
+
+#[test]
+fn nrs_rotation_states() {
+  let config = GameConfig {
+    rotation: Some(RotationSystem::Nrs),
+    ..config(KickTable::SRS, SpinBonuses::TSpins)
+  };
+
+  // 3 wide pieces spawn one column further right, t pointing down
+  let mut game = game2::Game::new(Mino2::T);
+  game.set_falling(Mino2::T, &config);
+  let spawn = game.piece;
+  assert_eq!(sorted_cells(&spawn), [(4, 21), (5, 20), (5, 21), (6, 21)]);
+
+  // no kicks at all
+  game.board.set(5, 22);
+  assert_eq!(game.rotate(1, &config), (false, false));
+  assert_eq!(game.piece, spawn);
+  assert_eq!(game.rotate(3, &config), (false, false));
+
+  // s turns around the left cell of its top row and back between two states
+  let mut game = game2::Game::new(Mino2::S);
+  game.set_falling(Mino2::S, &config);
+  let spawn = sorted_cells(&game.piece);
+  assert_eq!(spawn, [(4, 21), (5, 21), (5, 22), (6, 22)]);
+  for _ in 0..2 {
+    assert_eq!(game.rotate(1, &config), (true, false));
+    assert_eq!(
+      sorted_cells(&game.piece),
+      [(5, 22), (5, 23), (6, 21), (6, 22)]
+    );
+    assert_eq!(game.rotate(1, &config), (true, false));
+    assert_eq!(sorted_cells(&game.piece), spawn);
+  }
+}
+
+fn rank(spin: Spin) -> u8 {
+  match spin {
+    Spin::Normal => 2,
+    Spin::Mini => 1,
+    Spin::None => 0,
+  }
+}
+
+// the bitboard search presses keys for kicks it isn't specialized for, the key finder reaches
+// everything it finds and it finds everything the legacy movegen does
+#[test]
+fn expand_keys_matches_legacy_movegen() {
+  let rotations = [
+    RotationSystem::Ars,
+    RotationSystem::Nrs,
+    RotationSystem::Custom(custom_kicks()),
+  ];
+
+  for seed in 0..12 {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let config = GameConfig {
+      rotation: Some(rotations[seed as usize % 3].clone()),
+      ..config(KickTable::SRS, SpinBonuses::AllMini)
+    };
+
+    let mut game = game2::Game::new(Mino2::T);
+    for _ in 0..rng.random_range(0..6) {
+      game.insert_garbage(
+        rng.random_range(1..3),
+        rng.random_range(0..BOARD_WIDTH as u8),
+      );
+    }
+    for _ in 0..rng.random_range(0..12) {
+      game
+        .board
+        .set(rng.random_range(0..BOARD_WIDTH), rng.random_range(0..8));
+    }
+
+    for mino in [
+      Mino2::I,
+      Mino2::J,
+      Mino2::L,
+      Mino2::O,
+      Mino2::S,
+      Mino2::T,
+      Mino2::Z,
+    ] {
+      game.set_falling(mino, &config);
+      let [all, spins, minis] = game2::movegen::expand_keys(&game, &config);
+
+      let mut found = BTreeMap::new();
+      all.for_each_filled(mino, |rot, x, y| {
+        let target = Piece {
+          mino,
+          x: x as i8,
+          y: y as i8,
+          rot,
+        };
+        let spin = if spins[rot as usize].get(x as usize, y) {
+          Spin::Normal
+        } else if minis[rot as usize].get(x as usize, y) {
+          Spin::Mini
+        } else {
+          Spin::None
+        };
+        found.insert(sorted_cells(&target), spin);
+        assert_keys(&game, &config, target, spin);
+      });
+
+      let mut legacy = to_legacy(&game, game.piece);
+      let map = legacy.collision_map();
+      let queue = [triangle::engine::queue::Mino::I; 32];
+      let start_state = StartState {
+        queue: &queue,
+        garbage: &[],
+      };
+      let mut passed = [0u64; 2048];
+      let mut res = [Placement {
+        x: 0,
+        y: 0,
+        rot: 0,
+        spin: Spin::None,
+      }; 512];
+      let (count, _) = expand(
+        &mut legacy,
+        &config,
+        &map,
+        &start_state,
+        &mut passed,
+        &mut res,
+      );
+
+      // the legacy movegen skips undoing a move, so it can only find less, and lists every spin
+      // a landing is reachable with
+      for placement in &res[..count] {
+        let falling = game::Falling {
+          x: placement.x,
+          y: placement.y,
+          rot: placement.rot,
+          mino: mino.into(),
+        };
+        let mut cells = falling
+          .blocks()
+          .map(|(bx, by)| (placement.x as i8 - bx as i8, placement.y as i8 - by as i8));
+        cells.sort();
+
+        let spin = found
+          .get(&cells)
+          .unwrap_or_else(|| panic!("seed {} {}: missed {:?}", seed, mino.str(), cells));
+        assert!(
+          rank(*spin) >= rank(placement.spin),
+          "seed {} {}: {:?} as {}",
+          seed,
+          mino.str(),
+          cells,
+          placement.spin.as_str()
+        );
+      }
+    }
+  }
+}
+
+// replays the keys and checks where the piece locks
+fn assert_keys(game: &game2::Game, config: &GameConfig, target: Piece, spin: Spin) {
+  let keys = keyfinder::get_keys(game, config, target, spin).unwrap();
+  assert_eq!(keys.last(), Some(&Move2::HardDrop));
+
+  let queue = [triangle::engine::queue::Mino::I; 32];
+  let start_state = StartState {
+    queue: &queue,
+    garbage: &[],
+  };
+
+  let mut replay = game.clone();
+  for key in keys {
+    key.run(&mut replay, config, &start_state);
+  }
+
+  let mut cells = replay.piece.cells();
+  let mut target_cells = target.cells();
+  cells.sort();
+  target_cells.sort();
+  assert_eq!(cells, target_cells);
+  assert!(replay.spin == spin);
+}
+
+#[test]
+fn keyfinder_reaches_every_placement() {
+  let config = config(KickTable::SRSPlus, SpinBonuses::None);
+
+  for seed in 0..32 {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (width, height) =
+      [(BOARD_WIDTH, BOARD_HEIGHT), (4, 64), (7, 30), (16, 50)][seed as usize / 8];
+    let mut game = game2::Game::with_size(Mino2::T, width, height);
+    for _ in 0..rng.random_range(0..6) {
+      game.insert_garbage(rng.random_range(1..3), rng.random_range(0..width as u8));
+    }
+
+    for mino in [
+      Mino2::I,
+      Mino2::J,
+      Mino2::L,
+      Mino2::O,
+      Mino2::S,
+      Mino2::T,
+      Mino2::Z,
+    ] {
+      game.piece = Piece::spawn(mino, &game.board);
+      let [all, _, _] = dispatch::expand(
+        mino,
+        KickTable2::SRSPlus,
+        true,
+        &game.board,
+        (game.piece.x as usize, game.board.real_height() as u8),
+        config.spins,
+      );
+
+      all.for_each_filled(mino, |rot, x, y| {
+        let target = Piece {
+          mino,
+          x: x as i8,
+          y: y as i8,
+          rot,
+        };
+        assert_keys(&game, &config, target, Spin::None);
+      });
+    }
+  }
+}
+
+#[test]
+fn keyfinder_spins() {
+  let config = config(KickTable::SRSPlus, SpinBonuses::TSpins);
+
+  // a t-spin double slot at x = 4 with an overhang on the left
+  let mut game = game2::Game::new(Mino2::T);
+  for x in 0..game.board.width() {
+    if x != 4 {
+      game.board.set(x, 0);
+    }
+    if !(3..=5).contains(&x) {
+      game.board.set(x, 1);
+    }
+  }
+  game.board.set(3, 2);
+
+  let target = Piece {
+    mino: Mino2::T,
+    x: 4,
+    y: 1,
+    rot: 2,
+  };
+  assert_keys(&game, &config, target, Spin::Normal);
+  // the mini isn't a way into the slot, both corners under the point are filled
+  assert!(keyfinder::get_keys(&game, &config, target, Spin::Mini).is_err());
+
+  // the slot can't be dropped into, so there's nothing to find without the spin
+  assert!(keyfinder::get_keys(&game, &config, target, Spin::None).is_err());
+}
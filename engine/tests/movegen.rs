@@ -14,9 +14,11 @@ use engine::game2::{
   board::Board,
   config::ConstConfig,
   data::{KickTable, Mino},
+  dispatch,
   map::CollisionMap,
   movegen::expand,
 };
+use triangle::types::game::SpinBonuses;
 
 fn test_piece<const PIECE: Mino>(board: &Board) {
   let map = CollisionMap::usable::<PIECE>(board);
@@ -37,7 +39,7 @@ fn test_piece<const PIECE: Mino>(board: &Board) {
         enable_180: true,
       }
     },
-  >(&map, (4, 21));
+  >(board, &map, (4, 21), SpinBonuses::None);
 
   let result_map = result[0];
 
@@ -76,3 +78,105 @@ fn collision_map() {
 
   test_piece::<{ Mino::T }>(&board);
 }
+
+#[test]
+fn spin_maps() {
+  // a t-spin double slot at x = 4 with an overhang on the left
+  let mut board = Board::new();
+  for x in 0..Board::WIDTH {
+    if x != 4 {
+      board.set(x, 0);
+    }
+    if !(3..=5).contains(&x) {
+      board.set(x, 1);
+    }
+  }
+  board.set(3, 2);
+
+  let map = CollisionMap::usable::<{ Mino::T }>(&board);
+  let run = |spins| {
+    expand::<
+      { Mino::T },
+      {
+        ConstConfig {
+          kicktable: KickTable::SRSPlus,
+          enable_180: true,
+        }
+      },
+    >(&board, &map, (4, 21), spins)
+  };
+
+  let [all, full, mini] = run(SpinBonuses::TSpins);
+  assert!(all[2].get(4, 1), "the slot should be reachable");
+  // both corners under the point are filled, so it's a full t-spin and never a mini
+  assert!(full[2].get(4, 1), "rotating into the slot is a t-spin");
+  assert!(!mini[2].get(4, 1));
+
+  // flat on the stack with only two corners filled
+  assert!(all[0].get(8, 2));
+  assert!(!full[0].get(8, 2) && !mini[0].get(8, 2));
+
+  // spin landings are always landings
+  for rot in 0..4 {
+    assert_eq!(full[rot] & !all[rot], Board::new());
+    assert_eq!(mini[rot] & !all[rot], Board::new());
+  }
+
+  let [_, full, mini] = run(SpinBonuses::None);
+  assert_eq!(full.count_ones() + mini.count_ones(), 0);
+
+  // only the t piece spins under t-spin rules, other pieces keep the early exit
+  let map = CollisionMap::usable::<{ Mino::S }>(&board);
+  let [_, full, mini] = expand::<
+    { Mino::S },
+    {
+      ConstConfig {
+        kicktable: KickTable::SRSPlus,
+        enable_180: true,
+      }
+    },
+  >(&board, &map, (4, 21), SpinBonuses::TSpins);
+  assert_eq!(full.count_ones() + mini.count_ones(), 0);
+}
+
+#[test]
+fn dispatch_matches_const_generics() {
+  let mut board = Board::new();
+  for (x, y) in [(0, 0), (1, 0), (3, 0), (4, 1), (5, 1), (8, 2), (9, 0)] {
+    board.set(x, y);
+  }
+
+  macro_rules! check {
+    ($mino:ident, $kicks:ident, $enable_180:expr) => {
+      let map = CollisionMap::usable::<{ Mino::$mino }>(&board);
+      assert_eq!(dispatch::usable(Mino::$mino, &board), map);
+
+      let direct = expand::<
+        { Mino::$mino },
+        {
+          ConstConfig {
+            kicktable: KickTable::$kicks,
+            enable_180: $enable_180,
+          }
+        },
+      >(&board, &map, (4, 21), SpinBonuses::All);
+      let dispatched = dispatch::expand(
+        Mino::$mino,
+        KickTable::$kicks,
+        $enable_180,
+        &board,
+        (4, 21),
+        SpinBonuses::All,
+      );
+      assert_eq!(direct, dispatched);
+    };
+  }
+
+  check!(I, SRS, false);
+  check!(J, SRSPlus, true);
+  check!(L, SRSX, true);
+  check!(O, None, false);
+  check!(S, SRSX, false);
+  check!(T, SRSPlus, true);
+  check!(Z, SRS, true);
+}